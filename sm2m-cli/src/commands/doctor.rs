@@ -0,0 +1,179 @@
+use std::fmt;
+
+use serde::Serialize;
+use sm2m_transcoder_driver::{
+    diagnose::{self, DeviceReport, Diagnosis, Roots},
+    driver::{Backend, UsbDriver},
+};
+
+use crate::{error::CliError, output::Output};
+
+#[derive(Debug, Serialize)]
+struct DeviceRow {
+    product: String,
+    serial_number: String,
+    path: String,
+    node: String,
+    node_accessible: bool,
+    drivers: Vec<String>,
+    ports: Vec<String>,
+}
+
+impl From<&DeviceReport> for DeviceRow {
+    fn from(report: &DeviceReport) -> Self {
+        Self {
+            product: report.product.clone(),
+            serial_number: report.serial_number.clone(),
+            path: report.path(),
+            node: report.node.display().to_string(),
+            node_accessible: report.node_accessible,
+            drivers: report.drivers.clone(),
+            ports: report
+                .ports
+                .iter()
+                .map(|port| port.path.display().to_string())
+                .collect(),
+        }
+    }
+}
+
+impl fmt::Display for DeviceRow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} at {}, node {} {}, drivers [{}], ports [{}]",
+            self.product,
+            self.serial_number,
+            self.path,
+            self.node,
+            if self.node_accessible {
+                "accessible"
+            } else {
+                "denied"
+            },
+            self.drivers.join(", "),
+            self.ports.join(", "),
+        )
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct DoctorReport {
+    devices: Vec<DeviceRow>,
+    modem_manager: bool,
+    udev_rules_installed: bool,
+    problems: Vec<String>,
+}
+
+impl From<&Diagnosis> for DoctorReport {
+    fn from(diagnosis: &Diagnosis) -> Self {
+        Self {
+            devices: diagnosis.devices.iter().map(DeviceRow::from).collect(),
+            modem_manager: diagnosis.modem_manager,
+            udev_rules_installed: diagnosis.udev_rules_installed,
+            problems: diagnosis
+                .problems()
+                .iter()
+                .map(|problem| problem.to_string())
+                .collect(),
+        }
+    }
+}
+
+impl fmt::Display for DoctorReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for device in &self.devices {
+            writeln!(f, "{}", device)?;
+        }
+        writeln!(
+            f,
+            "udev rules {}",
+            if self.udev_rules_installed {
+                "installed"
+            } else {
+                "missing, print them with `sm2m doctor --udev-rules`"
+            }
+        )?;
+        if self.problems.is_empty() {
+            return write!(f, "no problems found");
+        }
+        let problems = self
+            .problems
+            .iter()
+            .map(|problem| format!("problem: {}", problem))
+            .collect::<Vec<_>>();
+        write!(f, "{}", problems.join("\n"))
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(transparent)]
+struct UdevRules(String);
+
+impl fmt::Display for UdevRules {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0.trim_end())
+    }
+}
+
+pub fn run(backend: Backend, udev_rules: bool, output: Output) -> Result<(), CliError> {
+    if udev_rules {
+        return output.print(&UdevRules(diagnose::udev_rules()));
+    }
+    // libusb fails to initialize without usbfs, sysfs still tells what is attached.
+    let diagnosis = match UsbDriver::with_backend(backend) {
+        Ok(driver) => driver.diagnose()?,
+        Err(_) => diagnose::inspect(&Roots::default(), backend)?,
+    };
+    let report = DoctorReport::from(&diagnosis);
+    output.print(&report)?;
+    if report.problems.is_empty() {
+        Ok(())
+    } else {
+        Err(CliError::DoctorFailed(report.problems.len()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use sm2m_transcoder_driver::{diagnose::PortReport, hotplug::DeviceLocation};
+
+    use super::*;
+
+    #[test]
+    fn format_doctor_report() {
+        let diagnosis = Diagnosis {
+            backend: Backend::Tty,
+            devices: vec![DeviceReport {
+                product: "An26 SM2M Decoder".to_owned(),
+                serial_number: "1A2B".to_owned(),
+                location: DeviceLocation {
+                    bus: 3,
+                    address: 7,
+                    ports: vec![1, 4],
+                },
+                node: PathBuf::from("/dev/bus/usb/003/007"),
+                node_accessible: false,
+                open_error: None,
+                drivers: vec!["cdc_acm".to_owned()],
+                ports: vec![PortReport {
+                    path: PathBuf::from("/dev/ttyACM0"),
+                    accessible: false,
+                }],
+            }],
+            modem_manager: false,
+            udev_rules_installed: false,
+        };
+
+        let report = DoctorReport::from(&diagnosis);
+
+        assert_eq!(
+            report.to_string(),
+            "An26 SM2M Decoder 1A2B at 3-1.4, node /dev/bus/usb/003/007 denied, drivers [cdc_acm], ports [/dev/ttyACM0]\n\
+             udev rules missing, print them with `sm2m doctor --udev-rules`\n\
+             problem: An26 SM2M Decoder 1A2B serial port /dev/ttyACM0 is not readable and writable by the current user, install the udev rules"
+        );
+    }
+}
@@ -0,0 +1,212 @@
+use std::{fmt, time};
+
+use clap::{Subcommand, ValueEnum};
+use serde::Serialize;
+use sm2m_transcoder_driver::{
+    devices::emulator_session::{EmulatorSession, GeneratorConfig},
+    driver::UsbDriver,
+};
+
+use crate::{device, error::CliError, output::Output, RoleArg};
+
+#[derive(Subcommand)]
+pub enum EmulatorCommand {
+    /// Enable or disable generator of a single channel.
+    Gen {
+        /// Channel index.
+        channel: usize,
+        /// Initial channel value.
+        #[arg(long, default_value_t = 0)]
+        from: u16,
+        /// Value added to the channel every period.
+        #[arg(long, default_value_t = 1)]
+        step: u16,
+        /// Number of produced frames between channel changes.
+        #[arg(long, default_value_t = 1)]
+        every: u8,
+        /// Keep channel at constant value.
+        #[arg(long, conflicts_with_all = ["from", "step", "every"])]
+        constant: Option<u16>,
+        /// Disable channel generator.
+        #[arg(long, conflicts_with_all = ["from", "step", "every", "constant"])]
+        disable: bool,
+    },
+    /// Start producing parameters frames.
+    Start {
+        /// Frames produced per second.
+        #[arg(long, default_value_t = 20)]
+        fps: u8,
+    },
+    /// Stop producing parameters frames.
+    Stop,
+    /// Append checksum word to every produced frame, decoder must verify it too.
+    Checksum {
+        #[arg(value_enum)]
+        mode: SwitchArg,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum SwitchArg {
+    On,
+    Off,
+}
+
+#[derive(Debug, Serialize)]
+struct GeneratorRow {
+    channel: usize,
+    enabled: bool,
+    value: u16,
+    step: u16,
+    period: u8,
+}
+
+impl fmt::Display for GeneratorRow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.enabled {
+            write!(f, "channel {} disabled", self.channel)
+        } else if self.period == 0 {
+            write!(f, "channel {} constant {}", self.channel, self.value)
+        } else {
+            write!(
+                f,
+                "channel {} from {} step {} every {} frames",
+                self.channel, self.value, self.step, self.period
+            )
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct ProducerRow {
+    running: bool,
+    fps: Option<u8>,
+}
+
+impl fmt::Display for ProducerRow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.fps {
+            Some(fps) if self.running => write!(f, "producer started at {} fps", fps),
+            _ => write!(f, "producer stopped"),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct ChecksumRow {
+    checksum: bool,
+}
+
+impl fmt::Display for ChecksumRow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.checksum {
+            write!(f, "checksum enabled")
+        } else {
+            write!(f, "checksum disabled")
+        }
+    }
+}
+
+pub fn run(
+    driver: &mut UsbDriver,
+    command: EmulatorCommand,
+    serial: Option<&str>,
+    timeout: time::Duration,
+    output: Output,
+) -> Result<(), CliError> {
+    let device = device::open(driver, RoleArg::Emulator, serial, timeout)?;
+    let mut session = EmulatorSession::open(device)?;
+    match command {
+        EmulatorCommand::Gen {
+            channel,
+            disable: true,
+            ..
+        } => {
+            session.disable(channel)?;
+            output.print(&GeneratorRow {
+                channel,
+                enabled: false,
+                value: 0,
+                step: 0,
+                period: 0,
+            })
+        }
+        EmulatorCommand::Gen {
+            channel,
+            from,
+            step,
+            every,
+            constant,
+            ..
+        } => {
+            let builder = session.channel(channel);
+            let builder = match constant {
+                Some(value) => builder.constant(value),
+                None => builder.sequential().from(from).step(step).every(every),
+            };
+            let GeneratorConfig {
+                value,
+                step,
+                period,
+            } = builder.config();
+            builder.enable()?;
+            output.print(&GeneratorRow {
+                channel,
+                enabled: true,
+                value,
+                step,
+                period,
+            })
+        }
+        EmulatorCommand::Start { fps } => {
+            session.start(fps)?;
+            output.print(&ProducerRow {
+                running: fps > 0,
+                fps: Some(fps),
+            })
+        }
+        EmulatorCommand::Stop => {
+            session.stop()?;
+            output.print(&ProducerRow {
+                running: false,
+                fps: None,
+            })
+        }
+        EmulatorCommand::Checksum { mode } => {
+            let checksum = matches!(mode, SwitchArg::On);
+            session.set_checksum(checksum)?;
+            output.print(&ChecksumRow { checksum })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_generator_row() {
+        let row = GeneratorRow {
+            channel: 3,
+            enabled: true,
+            value: 100,
+            step: 5,
+            period: 2,
+        };
+
+        assert_eq!(row.to_string(), "channel 3 from 100 step 5 every 2 frames");
+    }
+
+    #[test]
+    fn format_constant_generator_row() {
+        let row = GeneratorRow {
+            channel: 4,
+            enabled: true,
+            value: 0x1234,
+            step: 0,
+            period: 0,
+        };
+
+        assert_eq!(row.to_string(), "channel 4 constant 4660");
+    }
+}
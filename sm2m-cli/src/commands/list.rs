@@ -0,0 +1,113 @@
+use std::{fmt, time};
+
+use serde::Serialize;
+use sm2m_transcoder_driver::{driver::UsbDriver, info::DeviceInfo};
+
+use crate::{error::CliError, output::Output};
+
+#[derive(Debug, Serialize)]
+pub struct DeviceRow {
+    pub role: String,
+    pub serial_number: String,
+    pub product: String,
+    pub path: String,
+    pub firmware: Option<String>,
+    pub protocol: Option<u8>,
+    pub max_params: Option<u8>,
+}
+
+impl From<&DeviceInfo> for DeviceRow {
+    fn from(info: &DeviceInfo) -> Self {
+        Self {
+            role: info.role.to_string(),
+            serial_number: info.serial_number.clone(),
+            product: info.product.clone(),
+            path: info.path(),
+            firmware: info.firmware.map(|firmware| firmware.to_string()),
+            protocol: info.capabilities.map(|capabilities| capabilities.revision),
+            max_params: info
+                .capabilities
+                .map(|capabilities| capabilities.max_params),
+        }
+    }
+}
+
+impl fmt::Display for DeviceRow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:<8} {:<28} {:<10} firmware {:<8} protocol {}",
+            self.role,
+            self.serial_number,
+            self.path,
+            self.firmware.as_deref().unwrap_or("unknown"),
+            self.protocol
+                .map(|protocol| protocol.to_string())
+                .unwrap_or_else(|| "incompatible".to_owned()),
+        )
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(transparent)]
+struct DeviceList(Vec<DeviceRow>);
+
+impl fmt::Display for DeviceList {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0.is_empty() {
+            return write!(f, "no SM2M devices attached");
+        }
+        let rows = self.0.iter().map(DeviceRow::to_string).collect::<Vec<_>>();
+        write!(f, "{}", rows.join("\n"))
+    }
+}
+
+pub fn run(
+    driver: &mut UsbDriver,
+    timeout: time::Duration,
+    output: Output,
+) -> Result<(), CliError> {
+    let devices = driver.list_devices(timeout)?;
+    output.print(&DeviceList(devices.iter().map(DeviceRow::from).collect()))
+}
+
+#[cfg(test)]
+mod tests {
+    use sm2m_transcoder_driver::info::{Capabilities, DeviceRole, Features, FirmwareVersion, Role};
+
+    use super::*;
+
+    fn decoder_info() -> DeviceInfo {
+        DeviceInfo {
+            role: DeviceRole::Decoder,
+            product: "An26 SM2M Decoder".to_owned(),
+            serial_number: "1A2B".to_owned(),
+            bus: 3,
+            ports: vec![1, 4],
+            firmware: Some(FirmwareVersion::new(1, 2, 3)),
+            capabilities: Some(Capabilities::new(Role::Decoder, 30, Features::FRAMING)),
+        }
+    }
+
+    #[test]
+    fn format_device_row() {
+        let row = DeviceRow::from(&decoder_info());
+
+        assert_eq!(
+            row.to_string(),
+            "decoder  1A2B                         3-1.4      firmware 1.2.3    protocol 2"
+        );
+    }
+
+    #[test]
+    fn serialize_device_list() {
+        let list = DeviceList(vec![DeviceRow::from(&decoder_info())]);
+
+        let json = serde_json::to_value(&list).unwrap();
+
+        assert_eq!(json[0]["role"], "decoder");
+        assert_eq!(json[0]["path"], "3-1.4");
+        assert_eq!(json[0]["firmware"], "1.2.3");
+        assert_eq!(json[0]["max_params"], 30);
+    }
+}
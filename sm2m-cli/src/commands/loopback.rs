@@ -0,0 +1,176 @@
+use std::{fmt, time};
+
+use clap::ValueEnum;
+use serde::Serialize;
+use sm2m_transcoder_driver::{
+    devices::loopback::{LineStatus, LoopbackOptions, LoopbackReport, LoopbackTest, Pattern},
+    driver::UsbDriver,
+};
+
+use crate::{error::CliError, output::Output};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum PatternArg {
+    WalkingOnes,
+    WalkingZeros,
+    Counters,
+    Random,
+}
+
+impl From<PatternArg> for Pattern {
+    fn from(pattern: PatternArg) -> Self {
+        match pattern {
+            PatternArg::WalkingOnes => Pattern::WalkingOnes,
+            PatternArg::WalkingZeros => Pattern::WalkingZeros,
+            PatternArg::Counters => Pattern::Counters,
+            PatternArg::Random => Pattern::PseudoRandom,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct LineFault {
+    line: usize,
+    status: String,
+}
+
+impl From<(usize, LineStatus)> for LineFault {
+    fn from((line, status): (usize, LineStatus)) -> Self {
+        Self {
+            line,
+            status: status.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct LoopbackRow {
+    passed: bool,
+    frames: usize,
+    frame_rate: f64,
+    words: usize,
+    word_errors: usize,
+    dropped_frames: usize,
+    malformed_frames: usize,
+    faults: Vec<LineFault>,
+    /// Faults which hide start marker from decoder, reported when no frame arrived.
+    suspects: Vec<LineFault>,
+}
+
+impl From<&LoopbackReport> for LoopbackRow {
+    fn from(report: &LoopbackReport) -> Self {
+        let suspects = if report.frames_received == 0 {
+            LoopbackReport::marker_faults()
+        } else {
+            Vec::new()
+        };
+        Self {
+            passed: report.is_passed(),
+            frames: report.frames_received,
+            frame_rate: report.frame_rate(),
+            words: report.words_checked,
+            word_errors: report.word_errors,
+            dropped_frames: report.dropped_frames,
+            malformed_frames: report.malformed_frames,
+            faults: report.faults().into_iter().map(LineFault::from).collect(),
+            suspects: suspects.into_iter().map(LineFault::from).collect(),
+        }
+    }
+}
+
+impl fmt::Display for LoopbackRow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} frames {} at {:.1} fps word errors {}/{} dropped {} malformed {}",
+            if self.passed { "PASSED" } else { "FAILED" },
+            self.frames,
+            self.frame_rate,
+            self.word_errors,
+            self.words,
+            self.dropped_frames,
+            self.malformed_frames
+        )?;
+        for fault in &self.faults {
+            write!(f, "\nline {} {}", fault.line, fault.status)?;
+        }
+        if !self.suspects.is_empty() {
+            write!(f, "\nno frames received, check interrupt line or any of:")?;
+            for suspect in &self.suspects {
+                write!(f, "\nline {} {}", suspect.line, suspect.status)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Runs loopback test on the first attached decoder and emulator, which must be
+/// wired back-to-back, fails when any error is found.
+pub fn run(
+    driver: &mut UsbDriver,
+    patterns: Vec<PatternArg>,
+    fps: u8,
+    frames: usize,
+    seed: u32,
+    timeout: time::Duration,
+    output: Output,
+) -> Result<(), CliError> {
+    let mut options = LoopbackOptions {
+        fps,
+        frames,
+        seed,
+        ..LoopbackOptions::default()
+    };
+    if !patterns.is_empty() {
+        options.patterns = patterns.into_iter().map(Pattern::from).collect();
+    }
+
+    let report = LoopbackTest::open(driver, timeout)?
+        .with_options(options)
+        .run()?;
+    output.print(&LoopbackRow::from(&report))?;
+    if report.is_passed() {
+        Ok(())
+    } else {
+        Err(CliError::LoopbackFailed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sm2m_transcoder_driver::devices::loopback::LineReport;
+
+    use super::*;
+
+    #[test]
+    fn format_failed_report() {
+        let mut report = LoopbackReport {
+            frames_received: 10,
+            words_checked: 120,
+            word_errors: 10,
+            elapsed: time::Duration::from_secs(1),
+            ..LoopbackReport::default()
+        };
+        report.lines[10] = LineReport {
+            expected_high: 0,
+            expected_low: 10,
+            errors_high: 0,
+            errors_low: 10,
+        };
+
+        assert_eq!(
+            LoopbackRow::from(&report).to_string(),
+            "FAILED frames 10 at 10.0 fps word errors 10/120 dropped 0 malformed 0\nline 10 stuck high"
+        );
+    }
+
+    #[test]
+    fn report_marker_suspects_without_frames() {
+        let row = LoopbackRow::from(&LoopbackReport::default());
+
+        assert!(!row.passed);
+        assert_eq!(row.suspects.len(), 16);
+        assert_eq!(row.suspects[0].status, "stuck low");
+        assert_eq!(row.suspects[1].status, "stuck high");
+    }
+}
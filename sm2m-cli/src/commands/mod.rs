@@ -0,0 +1,9 @@
+#[cfg(target_os = "linux")]
+pub mod doctor;
+pub mod emulator;
+pub mod list;
+pub mod loopback;
+pub mod monitor;
+pub mod raw;
+pub mod stats;
+pub mod version;
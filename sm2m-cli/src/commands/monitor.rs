@@ -0,0 +1,437 @@
+use std::{fmt, path::PathBuf, time};
+
+use serde::Serialize;
+use sm2m_protocol::deframer::FrameStats;
+use sm2m_transcoder_driver::{
+    base::{framed::Framed, transport::Transport},
+    capture::{
+        format::CaptureHeader,
+        recorder::Recorder,
+        replay::{Pace, ReplayTransport},
+    },
+    channel_map::{ChannelMap, ChannelValue},
+    devices::decoder::{DecoderDevice, DeliveryPolicy, ParamsFrame},
+    driver::UsbDriver,
+    error::DriverError,
+    stats::{DeviceStats, Metered},
+};
+
+use crate::{device, error::CliError, output::Output, RoleArg};
+
+pub struct Options {
+    pub count: Option<usize>,
+    pub interval: time::Duration,
+    pub quiet: bool,
+    pub record: Option<PathBuf>,
+    pub map: Option<ChannelMap>,
+    pub checksum: bool,
+    /// Policy sent to decoder, the decoder keeps its own when `None`.
+    pub delivery: Option<DeliveryPolicy>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename = "frame")]
+struct FrameRow {
+    words: Vec<u16>,
+    timestamp: u64,
+    overflow: Option<Overflow>,
+    corrupted: Option<u32>,
+}
+
+#[derive(Debug, Serialize)]
+struct Overflow {
+    expected: u8,
+    received: u8,
+}
+
+impl From<&ParamsFrame> for FrameRow {
+    fn from(frame: &ParamsFrame) -> Self {
+        Self {
+            words: frame.words.to_vec(),
+            timestamp: frame.timestamp,
+            overflow: frame.overflow.map(|overflow| Overflow {
+                expected: overflow.expected,
+                received: overflow.received,
+            }),
+            corrupted: frame.corrupted,
+        }
+    }
+}
+
+impl fmt::Display for FrameRow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.overflow, self.corrupted) {
+            (Some(overflow), _) => write!(
+                f,
+                "overflow expected {} received {}",
+                overflow.expected, overflow.received
+            ),
+            (None, Some(corrupted)) => write!(f, "corrupted, {} frames dropped", corrupted),
+            (None, None) => {
+                let words = self
+                    .words
+                    .iter()
+                    .map(|word| format!("{:04x}", word))
+                    .collect::<Vec<_>>();
+                write!(f, "frame {}", words.join(" "))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename = "values")]
+struct ValuesRow {
+    values: Vec<NamedValue>,
+}
+
+#[derive(Debug, Serialize)]
+struct NamedValue {
+    name: String,
+    value: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    units: Option<String>,
+}
+
+impl From<Vec<ChannelValue<'_>>> for ValuesRow {
+    fn from(values: Vec<ChannelValue<'_>>) -> Self {
+        Self {
+            values: values
+                .into_iter()
+                .map(|value| NamedValue {
+                    name: value.name.to_owned(),
+                    value: value.value,
+                    units: value.units.map(str::to_owned),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl fmt::Display for ValuesRow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let values = self
+            .values
+            .iter()
+            .map(|value| match &value.units {
+                Some(units) => format!("{}={} {}", value.name, value.value, units),
+                None => format!("{}={}", value.name, value.value),
+            })
+            .collect::<Vec<_>>();
+        write!(f, "values {}", values.join(", "))
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(tag = "type", rename = "stats")]
+struct StatsReport {
+    rate: f64,
+    frames: u64,
+    overflows: u64,
+    corrupted_frames: u64,
+    decode_errors: u64,
+    crc_errors: u32,
+    lost_frames: u32,
+    dropped_bytes: u32,
+    bytes_read: u64,
+    read_timeouts: u64,
+    io_errors: u64,
+}
+
+impl fmt::Display for StatsReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "stats {:.1} fps, {} frames, {} overflows, {} corrupted frames, {} decode errors, {} crc errors, {} lost frames, {} dropped bytes, {} bytes read, {} read timeouts, {} io errors",
+            self.rate,
+            self.frames,
+            self.overflows,
+            self.corrupted_frames,
+            self.decode_errors,
+            self.crc_errors,
+            self.lost_frames,
+            self.dropped_bytes,
+            self.bytes_read,
+            self.read_timeouts,
+            self.io_errors
+        )
+    }
+}
+
+/// Counts received frames and errors, frame rate is measured between reports.
+struct MonitorStats {
+    frames: u64,
+    overflows: u64,
+    corrupted_frames: u64,
+    decode_errors: u64,
+    window_frames: u64,
+    window_start: time::Instant,
+}
+
+impl MonitorStats {
+    fn new(now: time::Instant) -> Self {
+        Self {
+            frames: 0,
+            overflows: 0,
+            corrupted_frames: 0,
+            decode_errors: 0,
+            window_frames: 0,
+            window_start: now,
+        }
+    }
+
+    fn record_frame(&mut self, frame: &ParamsFrame) {
+        // Decoder reports its running total of dropped frames, not a frame.
+        if let Some(corrupted) = frame.corrupted {
+            self.corrupted_frames = corrupted as u64;
+            return;
+        }
+        self.frames += 1;
+        self.window_frames += 1;
+        if frame.overflow.is_some() {
+            self.overflows += 1;
+        }
+    }
+
+    fn record_decode_error(&mut self) {
+        self.decode_errors += 1;
+    }
+
+    fn is_report_due(&self, now: time::Instant, interval: time::Duration) -> bool {
+        now.saturating_duration_since(self.window_start) >= interval
+    }
+
+    fn report(
+        &mut self,
+        now: time::Instant,
+        frame_stats: FrameStats,
+        device_stats: &DeviceStats,
+    ) -> StatsReport {
+        let elapsed = now
+            .saturating_duration_since(self.window_start)
+            .as_secs_f64();
+        let rate = if elapsed > 0.0 {
+            self.window_frames as f64 / elapsed
+        } else {
+            0.0
+        };
+        self.window_frames = 0;
+        self.window_start = now;
+        StatsReport {
+            rate,
+            frames: self.frames,
+            overflows: self.overflows,
+            corrupted_frames: self.corrupted_frames,
+            decode_errors: self.decode_errors,
+            crc_errors: frame_stats.crc_errors,
+            lost_frames: frame_stats.lost_frames,
+            dropped_bytes: frame_stats.dropped_bytes,
+            bytes_read: device_stats.bytes_read,
+            read_timeouts: device_stats.read_timeouts,
+            io_errors: device_stats.errors(),
+        }
+    }
+}
+
+pub fn run(
+    driver: &mut UsbDriver,
+    serial: Option<&str>,
+    timeout: time::Duration,
+    options: Options,
+    output: Output,
+) -> Result<(), CliError> {
+    let info = device::find_info(driver, RoleArg::Decoder, serial, timeout)?;
+    let device = driver.open(&info, timeout)?;
+    match &options.record {
+        Some(path) => {
+            let recorder = Recorder::create(device, path, &CaptureHeader::new(&info))?;
+            monitor(recorder, timeout, &options, output)
+        }
+        None => monitor(device, timeout, &options, output),
+    }
+}
+
+/// Monitors decoder traffic played back from capture instead of attached decoder.
+pub fn replay(
+    path: PathBuf,
+    pace: Pace,
+    timeout: time::Duration,
+    options: Options,
+    output: Output,
+) -> Result<(), CliError> {
+    let device = ReplayTransport::open(path, pace)?;
+    monitor(device, timeout, &options, output)
+}
+
+fn monitor<T: Transport>(
+    device: T,
+    timeout: time::Duration,
+    options: &Options,
+    output: Output,
+) -> Result<(), CliError> {
+    let device = Metered::new(device);
+    let recorder = device.recorder();
+    let mut device = Framed::new(device);
+    device.set_read_timeout(timeout.min(options.interval));
+    if options.checksum {
+        device.set_checksum(true)?;
+    }
+    if let Some(policy) = options.delivery {
+        device.set_delivery(policy)?;
+    }
+    let mut stats = MonitorStats::new(time::Instant::now());
+    loop {
+        match device.read_params() {
+            Ok(frame) => {
+                stats.record_frame(&frame);
+                match &options.map {
+                    Some(map) if frame.overflow.is_none() && frame.corrupted.is_none() => match map
+                        .decode(&frame)
+                    {
+                        Ok(values) if !options.quiet => output.print(&ValuesRow::from(values))?,
+                        Ok(_) => {}
+                        Err(_) => stats.record_decode_error(),
+                    },
+                    _ if !options.quiet => output.print(&FrameRow::from(&frame))?,
+                    _ => {}
+                }
+            }
+            Err(error) if error.is_timeout() => {}
+            Err(DriverError::DecodePacket(_)) => stats.record_decode_error(),
+            Err(DriverError::CaptureEnded) => {
+                return output.print(&stats.report(
+                    time::Instant::now(),
+                    device.stats(),
+                    &recorder.snapshot(),
+                ))
+            }
+            Err(error) => return Err(error.into()),
+        }
+
+        let now = time::Instant::now();
+        if options
+            .count
+            .is_some_and(|count| stats.frames >= count as u64)
+        {
+            return output.print(&stats.report(now, device.stats(), &recorder.snapshot()));
+        }
+        if stats.is_report_due(now, options.interval) {
+            output.print(&stats.report(now, device.stats(), &recorder.snapshot()))?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sm2m_transcoder_driver::devices::decoder::ParamsOverflow;
+
+    use super::*;
+
+    #[test]
+    fn measure_frame_rate_between_reports() {
+        let start = time::Instant::now();
+        let mut stats = MonitorStats::new(start);
+        for _ in 0..40 {
+            stats.record_frame(&ParamsFrame::default());
+        }
+        stats.record_frame(&ParamsFrame {
+            overflow: Some(ParamsOverflow {
+                expected: 40,
+                received: 30,
+            }),
+            ..Default::default()
+        });
+        stats.record_frame(&ParamsFrame {
+            corrupted: Some(3),
+            ..Default::default()
+        });
+        stats.record_decode_error();
+
+        let now = start + time::Duration::from_secs(2);
+        assert!(stats.is_report_due(now, time::Duration::from_secs(1)));
+        let device_stats = DeviceStats {
+            bytes_read: 120,
+            read_timeouts: 2,
+            stalls: 1,
+            ..Default::default()
+        };
+        let report = stats.report(now, FrameStats::default(), &device_stats);
+
+        assert_eq!(report.rate, 20.5);
+        assert_eq!(report.frames, 41);
+        assert_eq!(report.overflows, 1);
+        assert_eq!(report.corrupted_frames, 3);
+        assert_eq!(report.decode_errors, 1);
+        assert_eq!(
+            (report.bytes_read, report.read_timeouts, report.io_errors),
+            (120, 2, 1)
+        );
+        assert!(!stats.is_report_due(now, time::Duration::from_secs(1)));
+        assert_eq!(
+            stats.report(now, FrameStats::default(), &device_stats).rate,
+            0.0
+        );
+    }
+
+    #[test]
+    fn format_values_row() {
+        let map = ChannelMap::from_toml(
+            "[[channel]]\nname = \"heading\"\nword = 0\nunits = \"deg\"\n\n[[channel]]\nname = \"reset\"\nword = 1",
+        )
+        .unwrap();
+        let frame = ParamsFrame {
+            words: [90, 1].iter().copied().collect(),
+            ..ParamsFrame::default()
+        };
+
+        let row = ValuesRow::from(map.decode(&frame).unwrap());
+
+        assert_eq!(row.to_string(), "values heading=90 deg, reset=1");
+        assert_eq!(
+            serde_json::to_value(&row).unwrap(),
+            serde_json::json!({
+                "type": "values",
+                "values": [
+                    { "name": "heading", "value": 90.0, "units": "deg" },
+                    { "name": "reset", "value": 1.0 }
+                ]
+            })
+        );
+    }
+
+    #[test]
+    fn format_frame_row() {
+        let frame = ParamsFrame {
+            words: [0x5555, 0x0102].iter().copied().collect(),
+            timestamp: 1_500_000,
+            ..ParamsFrame::default()
+        };
+
+        let row = FrameRow::from(&frame);
+
+        assert_eq!(row.to_string(), "frame 5555 0102");
+        assert_eq!(
+            serde_json::to_value(&row).unwrap(),
+            serde_json::json!({
+                "type": "frame",
+                "words": [0x5555, 0x0102],
+                "timestamp": 1_500_000,
+                "overflow": null,
+                "corrupted": null
+            })
+        );
+    }
+
+    #[test]
+    fn format_corrupted_frame_row() {
+        let frame = ParamsFrame {
+            corrupted: Some(2),
+            ..ParamsFrame::default()
+        };
+
+        assert_eq!(
+            FrameRow::from(&frame).to_string(),
+            "corrupted, 2 frames dropped"
+        );
+    }
+}
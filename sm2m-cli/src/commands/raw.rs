@@ -0,0 +1,124 @@
+use std::{fmt, time};
+
+use clap::{Args, Subcommand};
+use serde::Serialize;
+use sm2m_protocol::frame::MAX_FRAME_SIZE;
+use sm2m_transcoder_driver::{
+    base::{framed::Framed, transport::Transport},
+    driver::{UsbDevice, UsbDriver},
+};
+
+use crate::{device, error::CliError, hex, output::Output, RoleArg};
+
+#[derive(Args)]
+pub struct Target {
+    /// Role of the device to open when no serial number is given.
+    #[arg(long, value_enum, default_value_t = RoleArg::Decoder)]
+    role: RoleArg,
+    /// Exchange bytes as is without framing, used by legacy encoder.
+    #[arg(long)]
+    unframed: bool,
+}
+
+#[derive(Subcommand)]
+pub enum RawCommand {
+    /// Send packet written in hex, for example `01` requests firmware version.
+    Send {
+        packet: String,
+        #[command(flatten)]
+        target: Target,
+    },
+    /// Receive packets and print them in hex.
+    Recv {
+        /// Number of packets to receive.
+        #[arg(long, default_value_t = 1)]
+        count: usize,
+        #[command(flatten)]
+        target: Target,
+    },
+}
+
+#[derive(Debug, Serialize)]
+struct PacketRow {
+    direction: &'static str,
+    seq: Option<u8>,
+    packet: String,
+}
+
+impl fmt::Display for PacketRow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.seq {
+            Some(seq) => write!(f, "{} seq {}: {}", self.direction, seq, self.packet),
+            None => write!(f, "{}: {}", self.direction, self.packet),
+        }
+    }
+}
+
+pub fn run(
+    driver: &mut UsbDriver,
+    command: RawCommand,
+    serial: Option<&str>,
+    timeout: time::Duration,
+    output: Output,
+) -> Result<(), CliError> {
+    match command {
+        RawCommand::Send { packet, target } => {
+            let packet = hex::parse(&packet)?;
+            let mut device = device::open(driver, target.role, serial, timeout)?;
+            if target.unframed {
+                device.write_all(&packet)?;
+            } else {
+                Framed::new(device).write_frame(&packet)?;
+            }
+            output.print(&PacketRow {
+                direction: "sent",
+                seq: None,
+                packet: hex::format(&packet),
+            })
+        }
+        RawCommand::Recv { count, target } => {
+            let device = device::open(driver, target.role, serial, timeout)?;
+            if target.unframed {
+                receive_unframed(device, count, timeout, output)
+            } else {
+                receive_frames(device, count, timeout, output)
+            }
+        }
+    }
+}
+
+fn receive_frames(
+    device: UsbDevice,
+    count: usize,
+    timeout: time::Duration,
+    output: Output,
+) -> Result<(), CliError> {
+    let mut device = Framed::new(device);
+    for _ in 0..count {
+        let frame = device.read_frame(timeout)?;
+        output.print(&PacketRow {
+            direction: "received",
+            seq: Some(frame.seq),
+            packet: hex::format(frame.payload()),
+        })?;
+    }
+    Ok(())
+}
+
+fn receive_unframed(
+    mut device: UsbDevice,
+    count: usize,
+    timeout: time::Duration,
+    output: Output,
+) -> Result<(), CliError> {
+    let mut buf = [0u8; MAX_FRAME_SIZE];
+    for _ in 0..count {
+        let size = device.read(&mut buf, timeout)?;
+        output.print(&PacketRow {
+            direction: "received",
+            seq: None,
+            packet: hex::format(&buf[..size]),
+        })?;
+    }
+    Ok(())
+}
@@ -0,0 +1,101 @@
+use std::{fmt, time};
+
+use serde::Serialize;
+use sm2m_transcoder_driver::{
+    base::framed::Framed,
+    devices::decoder::{DecoderDevice, DecoderStats},
+    driver::UsbDriver,
+};
+
+use crate::{device, error::CliError, output::Output, RoleArg};
+
+#[derive(Debug, Serialize)]
+struct StatsRow {
+    words: u32,
+    dropped_words: u32,
+    markers: u32,
+    frames: u32,
+    overflows: u32,
+    corrupted: u32,
+    resyncs: u32,
+    length_changes: u32,
+    skipped_words: u32,
+    dropped_frames: u32,
+    usb_write_errors: u32,
+}
+
+impl From<DecoderStats> for StatsRow {
+    fn from(stats: DecoderStats) -> Self {
+        Self {
+            words: stats.words,
+            dropped_words: stats.dropped_words,
+            markers: stats.markers,
+            frames: stats.frames,
+            overflows: stats.overflows,
+            corrupted: stats.corrupted,
+            resyncs: stats.resyncs,
+            length_changes: stats.length_changes,
+            skipped_words: stats.skipped_words,
+            dropped_frames: stats.dropped_frames,
+            usb_write_errors: stats.usb_write_errors,
+        }
+    }
+}
+
+impl fmt::Display for StatsRow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} words, {} dropped words, {} markers, {} frames, {} overflows, {} corrupted frames, {} resyncs, {} length changes, {} skipped words, {} dropped frames, {} usb write errors",
+            self.words,
+            self.dropped_words,
+            self.markers,
+            self.frames,
+            self.overflows,
+            self.corrupted,
+            self.resyncs,
+            self.length_changes,
+            self.skipped_words,
+            self.dropped_frames,
+            self.usb_write_errors
+        )
+    }
+}
+
+/// Prints decoder firmware counters, `reset` clears them after printing.
+pub fn run(
+    driver: &mut UsbDriver,
+    reset: bool,
+    serial: Option<&str>,
+    timeout: time::Duration,
+    output: Output,
+) -> Result<(), CliError> {
+    let mut device = Framed::new(device::open(driver, RoleArg::Decoder, serial, timeout)?);
+    let stats = device.decoder_stats_within(timeout)?;
+    if reset {
+        device.reset_decoder_stats()?;
+    }
+    output.print(&StatsRow::from(stats))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_stats_row() {
+        let row = StatsRow::from(DecoderStats {
+            words: 310,
+            markers: 10,
+            frames: 8,
+            skipped_words: 30,
+            dropped_frames: 1,
+            ..DecoderStats::default()
+        });
+
+        assert_eq!(
+            row.to_string(),
+            "310 words, 0 dropped words, 10 markers, 8 frames, 0 overflows, 0 corrupted frames, 0 resyncs, 0 length changes, 30 skipped words, 1 dropped frames, 0 usb write errors"
+        );
+    }
+}
@@ -0,0 +1,44 @@
+use std::{fmt, time};
+
+use serde::Serialize;
+use sm2m_transcoder_driver::{driver::UsbDriver, info::PROTOCOL_REVISION};
+
+use crate::{device, error::CliError, output::Output, RoleArg};
+
+use super::list::DeviceRow;
+
+#[derive(Debug, Serialize)]
+struct VersionReport {
+    driver: String,
+    libusb: String,
+    protocol: u8,
+    device: DeviceRow,
+}
+
+impl fmt::Display for VersionReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "driver {} libusb {} protocol {}",
+            self.driver, self.libusb, self.protocol
+        )?;
+        write!(f, "{}", self.device)
+    }
+}
+
+/// Reports versions from device list, so firmware of incompatible devices is shown as well.
+pub fn run(
+    driver: &mut UsbDriver,
+    role: RoleArg,
+    serial: Option<&str>,
+    timeout: time::Duration,
+    output: Output,
+) -> Result<(), CliError> {
+    let info = device::find_info(driver, role, serial, timeout)?;
+    output.print(&VersionReport {
+        driver: UsbDriver::version(),
+        libusb: UsbDriver::libusb_version(),
+        protocol: PROTOCOL_REVISION,
+        device: DeviceRow::from(&info),
+    })
+}
@@ -0,0 +1,59 @@
+use std::time;
+
+use sm2m_transcoder_driver::{
+    driver::{UsbDevice, UsbDriver},
+    info::{DeviceInfo, DeviceRole},
+};
+
+use crate::{error::CliError, RoleArg};
+
+impl From<RoleArg> for DeviceRole {
+    fn from(role: RoleArg) -> Self {
+        match role {
+            RoleArg::Decoder => DeviceRole::Decoder,
+            RoleArg::Emulator => DeviceRole::Emulator,
+            RoleArg::Encoder => DeviceRole::Encoder,
+        }
+    }
+}
+
+/// Opens device with `serial` number or the first attached device with `role`.
+pub fn open(
+    driver: &mut UsbDriver,
+    role: RoleArg,
+    serial: Option<&str>,
+    timeout: time::Duration,
+) -> Result<UsbDevice, CliError> {
+    let device = match (serial, role) {
+        (Some(serial), _) => driver.open_by_serial(serial, timeout)?,
+        (None, RoleArg::Decoder) => driver.find_decoder(timeout)?,
+        (None, RoleArg::Emulator) => driver.find_emulator(timeout)?,
+        (None, RoleArg::Encoder) => driver.find_encoder(timeout)?,
+    };
+    device.ok_or_else(|| not_found(role.into(), serial))
+}
+
+/// Finds description of device with `serial` number or the first attached device with `role`.
+pub fn find_info(
+    driver: &mut UsbDriver,
+    role: RoleArg,
+    serial: Option<&str>,
+    timeout: time::Duration,
+) -> Result<DeviceInfo, CliError> {
+    let role = DeviceRole::from(role);
+    let info = driver
+        .list_devices(timeout)?
+        .into_iter()
+        .find(|info| match serial {
+            Some(serial) => info.serial_number == serial,
+            None => info.role == role,
+        });
+    info.ok_or_else(|| not_found(role, serial))
+}
+
+fn not_found(role: DeviceRole, serial: Option<&str>) -> CliError {
+    match serial {
+        Some(serial) => CliError::DeviceNotFound(format!("device {}", serial)),
+        None => CliError::DeviceNotFound(role.to_string()),
+    }
+}
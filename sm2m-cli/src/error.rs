@@ -0,0 +1,18 @@
+use sm2m_transcoder_driver::error::DriverError;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum CliError {
+    #[error(transparent)]
+    Driver(#[from] DriverError),
+    #[error("no attached {0} found")]
+    DeviceNotFound(String),
+    #[error("invalid hex string {0:?}, expected pairs of hex digits")]
+    InvalidHex(String),
+    #[error("loopback test found errors")]
+    LoopbackFailed,
+    #[error("doctor found {0} problem(s)")]
+    DoctorFailed(usize),
+    #[error("can't serialize output, reason: {0}")]
+    Json(#[from] serde_json::Error),
+}
@@ -0,0 +1,53 @@
+use crate::error::CliError;
+
+/// Parses bytes written as hex digit pairs, spaces and `0x` prefixes are ignored.
+pub fn parse(input: &str) -> Result<Vec<u8>, CliError> {
+    let digits = input
+        .split_whitespace()
+        .map(|part| part.trim_start_matches("0x").trim_start_matches("0X"))
+        .collect::<String>();
+    if digits.len() % 2 != 0 || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(CliError::InvalidHex(input.to_owned()));
+    }
+
+    (0..digits.len())
+        .step_by(2)
+        .map(|index| {
+            u8::from_str_radix(&digits[index..index + 2], 16)
+                .map_err(|_| CliError::InvalidHex(input.to_owned()))
+        })
+        .collect()
+}
+
+pub fn format(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_hex_pairs() {
+        assert_eq!(parse("01 0f a5").unwrap(), vec![0x01, 0x0f, 0xa5]);
+        assert_eq!(parse("010FA5").unwrap(), vec![0x01, 0x0f, 0xa5]);
+        assert_eq!(parse("0x01 0x0f").unwrap(), vec![0x01, 0x0f]);
+        assert_eq!(parse("").unwrap(), Vec::<u8>::new());
+    }
+
+    #[test]
+    fn reject_malformed_hex() {
+        assert!(matches!(parse("1"), Err(CliError::InvalidHex(_))));
+        assert!(matches!(parse("zz"), Err(CliError::InvalidHex(_))));
+    }
+
+    #[test]
+    fn format_bytes_as_hex_pairs() {
+        assert_eq!(format(&[0x01, 0x0f, 0xa5]), "01 0f a5");
+        assert_eq!(format(&[]), "");
+    }
+}
@@ -0,0 +1,235 @@
+mod commands;
+mod device;
+mod error;
+mod hex;
+mod output;
+
+use std::{path::PathBuf, process, time};
+
+use clap::{Parser, Subcommand, ValueEnum};
+use sm2m_transcoder_driver::{
+    capture::replay::Pace,
+    channel_map::ChannelMap,
+    devices::decoder::DeliveryPolicy,
+    driver::{Backend, UsbDriver},
+};
+
+use commands::{emulator::EmulatorCommand, loopback::PatternArg, raw::RawCommand};
+use error::CliError;
+use output::Output;
+
+/// Bench tool for SM2M decoders, emulators and encoders.
+#[derive(Parser)]
+#[command(name = "sm2m", version)]
+struct Cli {
+    /// Print machine readable JSON instead of human readable text.
+    #[arg(long, global = true)]
+    json: bool,
+    /// USB backend used to talk to devices.
+    #[arg(long, global = true, value_enum, default_value_t = BackendArg::Libusb)]
+    backend: BackendArg,
+    /// Serial number of the device to open instead of the first one with matching role.
+    #[arg(long, global = true)]
+    serial: Option<String>,
+    /// Time in milliseconds to wait for device answers.
+    #[arg(long, global = true, default_value_t = 1000)]
+    timeout: u64,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// List attached SM2M devices.
+    List,
+    /// Print driver version and firmware version of a device.
+    Version {
+        #[arg(long, value_enum, default_value_t = RoleArg::Decoder)]
+        role: RoleArg,
+    },
+    /// Print parameters frames received by decoder with frame rate and errors.
+    Monitor {
+        /// Stop after receiving this many frames.
+        #[arg(long)]
+        count: Option<usize>,
+        /// Seconds between statistics reports.
+        #[arg(long, default_value_t = 1)]
+        interval: u64,
+        /// Print statistics reports only.
+        #[arg(long)]
+        quiet: bool,
+        /// Record decoder traffic into capture file.
+        #[arg(long, conflicts_with = "replay")]
+        record: Option<PathBuf>,
+        /// Play decoder traffic back from capture file instead of attached decoder.
+        #[arg(long)]
+        replay: Option<PathBuf>,
+        /// Play capture back as fast as possible instead of the original timing.
+        #[arg(long, requires = "replay")]
+        fast: bool,
+        /// Channel map in TOML or JSON used to print named values instead of raw words.
+        #[arg(long)]
+        map: Option<PathBuf>,
+        /// Make decoder verify checksum word appended by emulator and drop corrupted frames.
+        #[arg(long, conflicts_with = "replay")]
+        checksum: bool,
+        /// Make decoder send only the newest frame when the host falls behind.
+        #[arg(long, conflicts_with_all = ["replay", "max_fps"])]
+        latest: bool,
+        /// Make decoder send at most this many frames per second.
+        #[arg(long, conflicts_with = "replay", value_parser = clap::value_parser!(u8).range(1..))]
+        max_fps: Option<u8>,
+    },
+    /// Print decoder firmware counters of received words, frames and drops.
+    Stats {
+        /// Reset counters after printing them.
+        #[arg(long)]
+        reset: bool,
+    },
+    /// Configure emulator channel generators and producer.
+    #[command(subcommand)]
+    Emulator(EmulatorCommand),
+    /// Check data bus between emulator and decoder wired back-to-back.
+    Loopback {
+        /// Patterns to run, every pattern runs by default.
+        #[arg(long = "pattern", value_enum)]
+        patterns: Vec<PatternArg>,
+        /// Frames produced per second.
+        #[arg(long, default_value_t = 100)]
+        fps: u8,
+        /// Frames collected for every pattern stage.
+        #[arg(long, default_value_t = 100)]
+        frames: usize,
+        /// Seed of the random pattern.
+        #[arg(long, default_value_t = 0x2545_f491)]
+        seed: u32,
+    },
+    /// Exchange raw packets written in hex with a device.
+    #[command(subcommand)]
+    Raw(RawCommand),
+    /// Explain why attached devices can't be opened and print udev rules fixing it.
+    #[cfg(target_os = "linux")]
+    Doctor {
+        /// Print udev rules granting access to SM2M devices instead of diagnosing them.
+        #[arg(long)]
+        udev_rules: bool,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum BackendArg {
+    Libusb,
+    #[cfg(target_os = "linux")]
+    Tty,
+}
+
+impl From<BackendArg> for Backend {
+    fn from(backend: BackendArg) -> Self {
+        match backend {
+            BackendArg::Libusb => Backend::Libusb,
+            #[cfg(target_os = "linux")]
+            BackendArg::Tty => Backend::Tty,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum RoleArg {
+    Decoder,
+    Emulator,
+    Encoder,
+}
+
+fn main() {
+    let cli = Cli::parse();
+    let output = if cli.json {
+        Output::Json
+    } else {
+        Output::Human
+    };
+    if let Err(error) = run(cli, output) {
+        output.error(&error);
+        process::exit(1);
+    }
+}
+
+fn run(cli: Cli, output: Output) -> Result<(), CliError> {
+    let backend = cli.backend.into();
+    let timeout = time::Duration::from_millis(cli.timeout);
+    let serial = cli.serial.as_deref();
+    match cli.command {
+        Command::List => commands::list::run(&mut driver(backend)?, timeout, output),
+        Command::Version { role } => {
+            commands::version::run(&mut driver(backend)?, role, serial, timeout, output)
+        }
+        Command::Monitor {
+            count,
+            interval,
+            quiet,
+            record,
+            replay,
+            fast,
+            map,
+            checksum,
+            latest,
+            max_fps,
+        } => {
+            let delivery = match (latest, max_fps) {
+                (true, _) => Some(DeliveryPolicy::Latest),
+                (false, Some(fps)) => Some(DeliveryPolicy::Decimate(fps)),
+                (false, None) => None,
+            };
+            let options = commands::monitor::Options {
+                count,
+                interval: time::Duration::from_secs(interval),
+                quiet,
+                record,
+                map: map.map(ChannelMap::load).transpose()?,
+                checksum,
+                delivery,
+            };
+            match replay {
+                Some(path) => {
+                    let pace = if fast {
+                        Pace::AsFastAsPossible
+                    } else {
+                        Pace::Original
+                    };
+                    commands::monitor::replay(path, pace, timeout, options, output)
+                }
+                None => {
+                    commands::monitor::run(&mut driver(backend)?, serial, timeout, options, output)
+                }
+            }
+        }
+        Command::Stats { reset } => {
+            commands::stats::run(&mut driver(backend)?, reset, serial, timeout, output)
+        }
+        Command::Emulator(command) => {
+            commands::emulator::run(&mut driver(backend)?, command, serial, timeout, output)
+        }
+        Command::Loopback {
+            patterns,
+            fps,
+            frames,
+            seed,
+        } => commands::loopback::run(
+            &mut driver(backend)?,
+            patterns,
+            fps,
+            frames,
+            seed,
+            timeout,
+            output,
+        ),
+        Command::Raw(command) => {
+            commands::raw::run(&mut driver(backend)?, command, serial, timeout, output)
+        }
+        #[cfg(target_os = "linux")]
+        Command::Doctor { udev_rules } => commands::doctor::run(backend, udev_rules, output),
+    }
+}
+
+fn driver(backend: Backend) -> Result<UsbDriver, CliError> {
+    Ok(UsbDriver::with_backend(backend)?)
+}
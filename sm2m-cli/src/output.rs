@@ -0,0 +1,29 @@
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::error::CliError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Output {
+    Human,
+    /// One JSON document per line, so streamed output can be piped to `jq`.
+    Json,
+}
+
+impl Output {
+    pub fn print<T: Serialize + fmt::Display>(&self, value: &T) -> Result<(), CliError> {
+        match self {
+            Output::Human => println!("{}", value),
+            Output::Json => println!("{}", serde_json::to_string(value)?),
+        }
+        Ok(())
+    }
+
+    pub fn error(&self, error: &CliError) {
+        match self {
+            Output::Human => eprintln!("error: {}", error),
+            Output::Json => eprintln!("{}", serde_json::json!({ "error": error.to_string() })),
+        }
+    }
+}
@@ -0,0 +1,43 @@
+use sm2m_protocol::clock::{ExtendedCounter, TICKS_PER_SECOND};
+use stm32f4xx_hal::{pac, rcc::Clocks};
+
+/// Microseconds since power up counted by the 32-bit TIM2 and extended to
+/// 64 bits. TIM2 clock must be enabled in RCC beforehand.
+pub struct Clock {
+    timer: pac::TIM2,
+    counter: ExtendedCounter,
+}
+
+impl Clock {
+    pub fn new(timer: pac::TIM2, clocks: &Clocks) -> Self {
+        // APB1 timers run at twice the bus clock when the bus is divided.
+        let timer_clock = match clocks.ppre1() {
+            1 => clocks.pclk1().0,
+            _ => clocks.pclk1().0 * 2,
+        };
+        timer
+            .psc
+            .write(|w| w.psc().bits((timer_clock / TICKS_PER_SECOND - 1) as u16));
+        timer.arr.write(|w| w.arr().bits(u32::MAX));
+        // Load the prescaler and drop the update flag raised by loading it.
+        timer.egr.write(|w| w.ug().set_bit());
+        timer.sr.modify(|_, w| w.uif().clear_bit());
+        timer.dier.write(|w| w.uie().set_bit());
+        timer.cr1.modify(|_, w| w.cen().set_bit());
+        Self {
+            timer,
+            counter: ExtendedCounter::new(),
+        }
+    }
+
+    pub fn now(&mut self) -> u64 {
+        self.counter.extend(self.timer.cnt.read().bits())
+    }
+
+    /// Samples the counter on every wrap, so wraps are not lost while the
+    /// data bus is idle.
+    pub fn handle_overflow(&mut self) {
+        self.timer.sr.modify(|_, w| w.uif().clear_bit());
+        self.now();
+    }
+}
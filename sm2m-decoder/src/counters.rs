@@ -0,0 +1,61 @@
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use sm2m_protocol::{decoder::DecoderStats, sync::SyncStats};
+
+/// Counters of the firmware pipeline around the frame synchroniser, which
+/// counts frames itself.
+///
+/// Counters are atomic and shared without locking, so counting never masks
+/// the bus interrupt.
+#[derive(Default)]
+pub struct Counters {
+    words: AtomicU32,
+    dropped_words: AtomicU32,
+    dropped_frames: AtomicU32,
+    usb_write_errors: AtomicU32,
+}
+
+impl Counters {
+    /// Counts word read from the bus, `queued` is `false` when the word was
+    /// lost because the synchroniser queue was full.
+    pub fn word_read(&self, queued: bool) {
+        increase(&self.words);
+        if !queued {
+            increase(&self.dropped_words);
+        }
+    }
+
+    pub fn frame_dropped(&self) {
+        increase(&self.dropped_frames);
+    }
+
+    pub fn usb_write_failed(&self) {
+        increase(&self.usb_write_errors);
+    }
+
+    pub fn reset(&self) {
+        for counter in [
+            &self.words,
+            &self.dropped_words,
+            &self.dropped_frames,
+            &self.usb_write_errors,
+        ] {
+            counter.store(0, Ordering::Relaxed);
+        }
+    }
+
+    pub fn stats(&self, sync: SyncStats) -> DecoderStats {
+        let load = |counter: &AtomicU32| counter.load(Ordering::Relaxed);
+        DecoderStats {
+            words: load(&self.words),
+            dropped_words: load(&self.dropped_words),
+            dropped_frames: load(&self.dropped_frames),
+            usb_write_errors: load(&self.usb_write_errors),
+            ..DecoderStats::from(sync)
+        }
+    }
+}
+
+fn increase(counter: &AtomicU32) {
+    counter.fetch_add(1, Ordering::Relaxed);
+}
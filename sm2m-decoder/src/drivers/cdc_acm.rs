@@ -1,5 +1,9 @@
 use core::borrow::BorrowMut;
 
+use sm2m_protocol::{
+    deframer::{Deframer, FrameStats},
+    frame::{Frame, FrameWriter, MAX_FRAME_SIZE},
+};
 use stm32f4xx_hal::otg_fs;
 use usb_device::{
     class_prelude::UsbBusAllocator,
@@ -9,6 +13,7 @@ use usb_device::{
 use usbd_serial::{SerialPort, USB_CLASS_CDC};
 
 pub const MAX_PACKET_SIZE: u8 = 64;
+const RX_BUF_SIZE: usize = MAX_PACKET_SIZE as usize;
 
 #[derive(Default)]
 pub struct Descriptor {
@@ -22,6 +27,16 @@ pub struct Descriptor {
 pub struct Device {
     usb_dev: UsbDevice<'static, otg_fs::UsbBusType>,
     serial: SerialPort<'static, otg_fs::UsbBusType>,
+    writer: FrameWriter,
+    deframer: Deframer,
+    rx_buf: [u8; RX_BUF_SIZE],
+    rx_len: usize,
+    rx_pos: usize,
+    /// Frame accepted only partly by the serial port, the rest is sent before
+    /// anything else.
+    tx_buf: [u8; MAX_FRAME_SIZE],
+    tx_len: usize,
+    tx_pos: usize,
 }
 
 impl Device {
@@ -43,26 +58,96 @@ impl Device {
             .max_packet_size_0(MAX_PACKET_SIZE)
             .build();
 
-        Self { usb_dev, serial }
+        Self {
+            usb_dev,
+            serial,
+            writer: FrameWriter::new(),
+            deframer: Deframer::new(),
+            rx_buf: [0; RX_BUF_SIZE],
+            rx_len: 0,
+            rx_pos: 0,
+            tx_buf: [0; MAX_FRAME_SIZE],
+            tx_len: 0,
+            tx_pos: 0,
+        }
     }
 
     pub fn poll(&mut self) -> bool {
-        self.usb_dev.poll(&mut [&mut self.serial])
+        let polled = self.usb_dev.poll(&mut [&mut self.serial]);
+        // Finishes a frame interrupted by a full endpoint, the error is kept
+        // for the next write.
+        self.flush_frame().ok();
+        polled
     }
 
     pub fn read(&mut self, data: &mut [u8]) -> Result<usize, UsbError> {
         self.serial.read(data)
     }
 
-    pub fn write(&mut self, data: &[u8]) -> Result<usize, UsbError> {
-        self.serial.write(data)
+    /// Encodes `payload` into a frame and sends it, returns the payload size.
+    ///
+    /// Frames are sent whole or not at all. `WouldBlock` means that nothing of
+    /// the frame was sent and its sequence number is still free. When the
+    /// serial port accepts only part of the frame, the rest is sent by the
+    /// following `poll` or `write_frame`.
+    pub fn write_frame(&mut self, payload: &[u8]) -> Result<usize, UsbError> {
+        self.flush_frame()?;
+        let size = self
+            .writer
+            .prepare_into(payload, &mut self.tx_buf)
+            .map_err(|_| UsbError::BufferOverflow)?;
+        let sent = self.serial.write(&self.tx_buf[..size])?;
+        self.writer.commit();
+        self.tx_len = size;
+        self.tx_pos = sent;
+        match self.flush_frame() {
+            Ok(()) | Err(UsbError::WouldBlock) => Ok(payload.len()),
+            Err(error) => Err(error),
+        }
     }
 
-    pub fn write_all(&mut self, buf: &[u8]) -> Result<usize, UsbError> {
-        let mut sent = 0;
-        while sent < buf.len() {
-            sent += self.write(buf)?;
+    fn flush_frame(&mut self) -> Result<(), UsbError> {
+        while self.tx_pos < self.tx_len {
+            match self.serial.write(&self.tx_buf[self.tx_pos..self.tx_len]) {
+                Ok(sent) => self.tx_pos += sent,
+                Err(UsbError::WouldBlock) => return Err(UsbError::WouldBlock),
+                Err(error) => {
+                    // The frame is broken anyway, the host drops it by CRC.
+                    self.tx_pos = self.tx_len;
+                    return Err(error);
+                }
+            }
         }
-        Ok(sent)
+        Ok(())
+    }
+
+    /// Returns the next complete frame, bytes of an incomplete frame are kept
+    /// until the following USB packets arrive.
+    pub fn read_frame(&mut self) -> Result<Option<Frame>, UsbError> {
+        loop {
+            if let Some(frame) = self.deframer.pop() {
+                return Ok(Some(frame));
+            }
+            while self.rx_pos < self.rx_len {
+                let byte = self.rx_buf[self.rx_pos];
+                self.rx_pos += 1;
+                if let Some(frame) = self.deframer.push(byte) {
+                    return Ok(Some(frame));
+                }
+            }
+
+            match self.serial.read(&mut self.rx_buf) {
+                Ok(0) | Err(UsbError::WouldBlock) => return Ok(None),
+                Ok(size) => {
+                    self.rx_len = size;
+                    self.rx_pos = 0;
+                }
+                Err(error) => return Err(error),
+            }
+        }
+    }
+
+    pub fn frame_stats(&self) -> FrameStats {
+        self.deframer.stats()
     }
 }
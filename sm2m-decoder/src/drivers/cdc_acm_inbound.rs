@@ -1,25 +1,19 @@
+use sm2m_protocol::{decoder::Command, packet::Packet};
 use usb_device::UsbError;
 
 use super::cdc_acm::Device;
 
-pub enum Inbound {
-    FirmwareVersion,
-    Unknown,
-}
-
 pub trait Reader {
-    fn read_inbound(&mut self) -> Result<Inbound, UsbError>;
+    fn read_inbound(&mut self) -> Result<Option<Command>, UsbError>;
 }
 
 impl Reader for Device {
-    fn read_inbound(&mut self) -> Result<Inbound, UsbError> {
-        let mut buf = [0u8; 64];
-        self.read(&mut buf)?;
-        let opcode = buf[0];
-        let packet = match opcode {
-            1 => Inbound::FirmwareVersion,
-            _ => Inbound::Unknown,
-        };
-        Ok(packet)
+    fn read_inbound(&mut self) -> Result<Option<Command>, UsbError> {
+        while let Some(frame) = self.read_frame()? {
+            if let Ok(command) = Command::decode(frame.payload()) {
+                return Ok(Some(command));
+            }
+        }
+        Ok(None)
     }
 }
@@ -4,29 +4,39 @@
 use panic_halt as _;
 
 mod bus;
+mod clock;
+mod counters;
 mod device_id;
 mod drivers;
-mod params;
 mod tasks;
 
+/// Frames waiting for the USB transfer.
+type FrameQueue = sm2m_protocol::queue::FrameQueue<8>;
+
 #[rtic::app(device = stm32f4xx_hal::pac, peripherals = true, dispatchers = [TAMP_STAMP])]
 mod app {
     use stm32f4xx_hal::{gpio, otg_fs, prelude::*};
 
     use crate::bus;
+    use crate::clock::Clock;
+    use crate::counters::Counters;
     use crate::device_id;
     use crate::drivers::cdc_acm;
-    use crate::params::{SM2MParamsState, MAX_PARAMS_COUNT};
+    use crate::FrameQueue;
+    use sm2m_protocol::sync::FrameSync;
 
     #[shared]
     struct Shared {
         // btn: gpio::gpioa::PA0<gpio::Input<gpio::PullDown>>,
         usb: cdc_acm::Device,
+        sync: FrameSync,
+        queue: FrameQueue,
+        clock: Clock,
+        counters: Counters,
     }
 
     #[local]
     struct Local {
-        state: SM2MParamsState,
         led: gpio::gpioc::PC13<gpio::Output<gpio::PushPull>>,
         bus_interrupt: gpio::gpioa::PA9<gpio::Input<gpio::PullDown>>,
         bus: bus::DataBus,
@@ -40,6 +50,7 @@ mod app {
 
         // Configure peripherals
         let mut pac = cx.device;
+        pac.RCC.apb1enr.modify(|_, w| w.tim2en().set_bit());
         let clocks = pac
             .RCC
             .constrain()
@@ -49,6 +60,9 @@ mod app {
             .require_pll48clk()
             .freeze();
 
+        // Configure frame timestamps
+        let clock = Clock::new(pac.TIM2, &clocks);
+
         // Configure LED
         let gpioc = pac.GPIOC.split();
         let led = gpioc
@@ -109,9 +123,14 @@ mod app {
         bus_interrupt.clear_interrupt_pending_bit();
 
         (
-            Shared { /*btn, */ usb },
+            Shared {
+                /*btn, */ usb,
+                sync: FrameSync::default(),
+                queue: FrameQueue::default(),
+                clock,
+                counters: Counters::default(),
+            },
             Local {
-                state: SM2MParamsState::DetectMarker,
                 led,
                 bus_interrupt,
                 bus,
@@ -130,19 +149,17 @@ mod app {
     use crate::tasks::*;
 
     extern "Rust" {
-        #[task(local = [state, led])]
-        fn handle_param(cx: handle_param::Context, param: u16);
-        #[task(shared = [usb])]
-        fn transfer_params(
-            cx: transfer_params::Context,
-            params: [u16; MAX_PARAMS_COUNT],
-            count: usize,
-        );
-        #[task(priority = 2, binds = OTG_FS, shared = [usb])]
+        #[task(shared = [sync, queue, &counters], local = [led])]
+        fn handle_param(cx: handle_param::Context, param: u16, timestamp: u64);
+        #[task(shared = [usb, queue, &counters])]
+        fn transfer_params(cx: transfer_params::Context);
+        #[task(priority = 2, binds = OTG_FS, shared = [usb, sync, queue, &counters])]
         fn usb_global(cx: usb_global::Context);
-        #[task(priority = 2, binds = OTG_FS_WKUP, shared = [usb])]
+        #[task(priority = 2, binds = OTG_FS_WKUP, shared = [usb, sync, queue, &counters])]
         fn usb_wkup(cx: usb_wkup::Context);
-        #[task(priority = 3, binds = EXTI9_5, local = [bus_interrupt, bus])]
+        #[task(priority = 3, binds = EXTI9_5, shared = [clock, &counters], local = [bus_interrupt, bus])]
         fn bus_read_interrupt(cx: bus_read_interrupt::Context);
+        #[task(priority = 3, binds = TIM2, shared = [clock])]
+        fn clock_overflow(cx: clock_overflow::Context);
     }
 }
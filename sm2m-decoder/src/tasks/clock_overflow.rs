@@ -0,0 +1,7 @@
+use rtic::Mutex;
+
+use crate::app::clock_overflow;
+
+pub fn clock_overflow(mut cx: clock_overflow::Context) {
+    cx.shared.clock.lock(|clock| clock.handle_overflow());
+}
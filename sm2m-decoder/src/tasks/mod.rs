@@ -1,9 +1,11 @@
 mod bus_read;
+mod clock_overflow;
 mod handle_param;
 mod transfer_params;
 mod usb_read;
 
 pub use bus_read::bus_read_interrupt;
+pub use clock_overflow::clock_overflow;
 pub use handle_param::handle_param;
 pub use transfer_params::transfer_params;
 pub use usb_read::{usb_global, usb_wkup};
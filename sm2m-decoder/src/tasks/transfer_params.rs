@@ -1,18 +1,28 @@
 use rtic::Mutex;
+use sm2m_protocol::queue::Push;
+use usb_device::UsbError;
 
-use crate::{
-    app::transfer_params,
-    drivers::cdc_acm_outbound::{Outbound, Writer},
-    params::MAX_PARAMS_COUNT,
-};
+use crate::{app::transfer_params, drivers::cdc_acm_outbound::Writer};
 
-pub fn transfer_params(
-    mut cx: transfer_params::Context,
-    params: [u16; MAX_PARAMS_COUNT],
-    count: usize,
-) {
-    cx.shared
-        .usb
-        .lock(|device| device.write_outbound(Outbound::Params(params, count)))
-        .ok();
+/// Sends queued events until the queue is empty or the USB endpoint is busy,
+/// the transfer is then spawned again by the next USB interrupt.
+pub fn transfer_params(mut cx: transfer_params::Context) {
+    while let Some(event) = cx.shared.queue.lock(|queue| queue.pop()) {
+        let result = cx
+            .shared
+            .usb
+            .lock(|device| device.write_outbound(event.clone().into()));
+        match result {
+            Ok(_) => {}
+            // Frames are written whole or not at all, so a blocked event was
+            // not sent at all and goes out first on the next attempt.
+            Err(UsbError::WouldBlock) => {
+                if cx.shared.queue.lock(|queue| queue.requeue(event)) == Push::Dropped {
+                    cx.shared.counters.frame_dropped();
+                }
+                return;
+            }
+            Err(_) => cx.shared.counters.usb_write_failed(),
+        }
+    }
 }
@@ -1,52 +1,80 @@
-use rtic::Mutex;
+use rtic::mutex_prelude::*;
+use sm2m_protocol::{
+    capabilities::{Capabilities, Features, Role},
+    decoder::{Command, Response, MAX_PARAMS_COUNT},
+    sync::FrameSync,
+};
 
 use crate::{
-    app::{usb_global, usb_wkup},
-    drivers::{
-        cdc_acm::Device,
-        cdc_acm_inbound::{Inbound, Reader},
-        cdc_acm_outbound::{Outbound, Writer},
-    },
+    app::{transfer_params, usb_global, usb_wkup},
+    counters::Counters,
+    drivers::{cdc_acm::Device, cdc_acm_inbound::Reader, cdc_acm_outbound::Writer},
+    FrameQueue,
 };
 
-pub fn usb_global(mut cx: usb_global::Context) {
-    if let Some(inbound) = cx.shared.usb.lock(poll) {
-        if let Some(outbound) = handle_inbound(inbound) {
-            cx.shared
-                .usb
-                .lock(|device| device.write_outbound(outbound))
-                .ok();
-        }
-    }
+pub fn usb_global(cx: usb_global::Context) {
+    let counters = cx.shared.counters;
+    (cx.shared.usb, cx.shared.sync, cx.shared.queue)
+        .lock(|device, sync, queue| poll(device, sync, queue, counters));
 }
 
-pub fn usb_wkup(mut cx: usb_wkup::Context) {
-    if let Some(inbound) = cx.shared.usb.lock(poll) {
-        if let Some(outbound) = handle_inbound(inbound) {
-            cx.shared
-                .usb
-                .lock(|device| device.write_outbound(outbound))
-                .ok();
-        }
-    }
+pub fn usb_wkup(cx: usb_wkup::Context) {
+    let counters = cx.shared.counters;
+    (cx.shared.usb, cx.shared.sync, cx.shared.queue)
+        .lock(|device, sync, queue| poll(device, sync, queue, counters));
 }
 
-fn poll(device: &mut Device) -> Option<Inbound> {
+fn poll(device: &mut Device, sync: &mut FrameSync, queue: &mut FrameQueue, counters: &Counters) {
     if device.poll() {
-        device.read_inbound().ok()
-    } else {
-        None
+        while let Ok(Some(inbound)) = device.read_inbound() {
+            if let Some(outbound) = handle_inbound(inbound, sync, queue, counters) {
+                if device.write_outbound(outbound).is_err() {
+                    counters.usb_write_failed();
+                }
+            }
+        }
+    }
+    // Resumes the transfer stopped by a busy endpoint.
+    if !queue.is_empty() {
+        transfer_params::spawn().ok();
     }
 }
 
-fn handle_inbound(inbound: Inbound) -> Option<Outbound> {
-    match inbound {
-        Inbound::FirmwareVersion => {
+fn handle_inbound(
+    command: Command,
+    sync: &mut FrameSync,
+    queue: &mut FrameQueue,
+    counters: &Counters,
+) -> Option<Response> {
+    match command {
+        Command::GetVersion => {
             let major = env!("CARGO_PKG_VERSION_MAJOR").parse::<u8>().unwrap_or(0);
             let minor = env!("CARGO_PKG_VERSION_MINOR").parse::<u8>().unwrap_or(0);
             let patch = env!("CARGO_PKG_VERSION_PATCH").parse::<u8>().unwrap_or(0);
-            Some(Outbound::FirmwareVersion(major, minor, patch))
+            Some(Response::Version(major, minor, patch))
+        }
+        Command::GetCapabilities => Some(Response::Capabilities(Capabilities::new(
+            Role::Decoder,
+            MAX_PARAMS_COUNT as u8,
+            Features::FRAMING
+                | Features::PARAMS_STREAM
+                | Features::CHECKSUM
+                | Features::STATS
+                | Features::DELIVERY,
+        ))),
+        Command::SetChecksum(enabled) => {
+            sync.set_checksum(enabled);
+            None
+        }
+        Command::SetDelivery(policy) => {
+            queue.set_policy(policy);
+            None
+        }
+        Command::GetStats => Some(Response::Stats(counters.stats(sync.stats()))),
+        Command::ResetStats => {
+            counters.reset();
+            sync.reset_stats();
+            None
         }
-        Inbound::Unknown => None,
     }
 }
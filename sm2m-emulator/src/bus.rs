@@ -3,6 +3,8 @@ use stm32f1xx_hal::{
     pac,
 };
 
+pub use sm2m_protocol::bus::{checksum, START_MARKER};
+
 pub struct Interface {
     pub line_activity: u32,
     pub interrupt: gpioa::PA0<Output<PushPull>>,
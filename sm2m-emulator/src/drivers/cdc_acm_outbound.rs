@@ -1,22 +1,21 @@
+use sm2m_protocol::{
+    emulator::Response,
+    packet::{Packet, MAX_PACKET_SIZE},
+};
 use usb_device::UsbError;
 
 use super::cdc_acm::Device;
 
-pub enum Outbound {
-    Version(u8, u8, u8),
-}
-
 pub trait Writer {
-    fn write_outbound(&mut self, packet: Outbound) -> Result<usize, UsbError>;
+    fn write_outbound(&mut self, packet: Response) -> Result<usize, UsbError>;
 }
 
 impl Writer for Device {
-    fn write_outbound(&mut self, packet: Outbound) -> Result<usize, UsbError> {
-        match packet {
-            Outbound::Version(major, minor, patch) => {
-                let buf = [1, major, minor, patch];
-                self.write_all(&buf)
-            }
-        }
+    fn write_outbound(&mut self, packet: Response) -> Result<usize, UsbError> {
+        let mut buf = [0u8; MAX_PACKET_SIZE];
+        let size = packet
+            .encode_into(&mut buf)
+            .map_err(|_| UsbError::BufferOverflow)?;
+        self.write_frame(&buf[..size])
     }
 }
@@ -4,24 +4,30 @@
 mod bus;
 mod device_id;
 mod drivers;
-mod generator;
 mod tasks;
 
 use panic_halt as _;
 
 #[rtic::app(device = stm32f1xx_hal::pac, peripherals = true, dispatchers = [TAMPER])]
 mod app {
-    use stm32f1xx_hal::{gpio, prelude::*, time, usb};
+    use sm2m_protocol::generator::channels::Channels;
+    use stm32f1xx_hal::{gpio, pac, prelude::*, time, timer, usb};
 
     use crate::{bus, device_id, drivers::cdc_acm};
 
     #[shared]
     struct Shared {
         usb: cdc_acm::Device,
+        channels: Channels,
+        /// Appends checksum word to every produced frame.
+        checksum: bool,
+        timer: timer::CountDownTimer<pac::TIM2>,
     }
 
     #[local]
-    struct Local {}
+    struct Local {
+        bus: bus::Interface,
+    }
 
     // struct Resources {
     //     sysclk: time::Hertz,
@@ -139,7 +145,21 @@ mod app {
                 .into_push_pull_output_with_state(&mut gpiob.crh, gpio::PinState::Low),
         };
 
-        (Shared { usb }, Local {}, init::Monotonics())
+        // Configure producer timer, it is started by host
+        let mut timer = timer::Timer::tim2(pac.TIM2, &clocks).start_count_down(1.hz());
+        timer.unlisten(timer::Event::Update);
+
+        let channels = Channels::default();
+        (
+            Shared {
+                usb,
+                channels,
+                checksum: false,
+                timer,
+            },
+            Local { bus },
+            init::Monotonics(),
+        )
     }
 
     #[idle]
@@ -154,7 +174,9 @@ mod app {
     extern "Rust" {
         #[task(binds = USB_HP_CAN_TX, shared = [usb])]
         fn usb_tx(cx: usb_tx::Context);
-        #[task(binds = USB_LP_CAN_RX0, shared = [usb])]
+        #[task(binds = USB_LP_CAN_RX0, shared = [usb, channels, checksum, timer])]
         fn usb_rx(cx: usb_rx::Context);
+        #[task(binds = TIM2, shared = [channels, checksum, timer], local = [bus])]
+        fn produce(cx: produce::Context);
     }
 }
@@ -1,5 +1,7 @@
+pub mod produce;
 pub mod usb_rx;
 pub mod usb_tx;
 
+pub use produce::produce;
 pub use usb_rx::usb_rx;
 pub use usb_tx::usb_tx;
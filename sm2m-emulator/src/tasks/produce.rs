@@ -0,0 +1,26 @@
+use rtic::Mutex;
+
+use crate::{app::produce, bus};
+
+pub fn produce(mut cx: produce::Context) {
+    cx.shared
+        .timer
+        .lock(|timer| timer.clear_update_interrupt_flag());
+    let values = cx.shared.channels.lock(|channels| {
+        let values = channels.values();
+        channels.generate();
+        values
+    });
+
+    let checksum = cx
+        .shared
+        .checksum
+        .lock(|checksum| checksum.then(|| bus::checksum(&values)));
+
+    let bus = cx.local.bus;
+    bus.write(bus::START_MARKER);
+    values.into_iter().for_each(|value| bus.write(value));
+    if let Some(checksum) = checksum {
+        bus.write(checksum);
+    }
+}
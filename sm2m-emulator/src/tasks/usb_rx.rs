@@ -1,50 +1,93 @@
 use rtic::Mutex;
+use sm2m_protocol::{
+    capabilities::{Capabilities, Features, Role},
+    emulator::{Command, Response, MAX_CHANNELS_COUNT},
+};
+use stm32f1xx_hal::{pac, prelude::*, timer};
 
 use crate::{
     app::usb_rx,
-    drivers::{
-        cdc_acm::Device,
-        cdc_acm_inbound::{Inbound, Reader},
-        cdc_acm_outbound::{Outbound, Writer},
-    },
+    drivers::{cdc_acm::Device, cdc_acm_inbound::Reader, cdc_acm_outbound::Writer},
 };
 
 pub fn usb_rx(mut cx: usb_rx::Context) {
-    if let Some(inbound) = cx.shared.usb.lock(poll) {
-        if let Some(outbound) = handle_inbound(&mut cx, inbound) {
-            send(&mut cx, outbound);
+    if cx.shared.usb.lock(Device::poll) {
+        while let Some(inbound) = cx.shared.usb.lock(read) {
+            if let Some(outbound) = handle_inbound(&mut cx, inbound) {
+                send(&mut cx, outbound);
+            }
         }
     }
 }
 
-fn poll(device: &mut Device) -> Option<Inbound> {
-    if device.poll() {
-        device.read_inbound().ok()
-    } else {
-        None
-    }
+fn read(device: &mut Device) -> Option<Command> {
+    device.read_inbound().ok().flatten()
 }
 
-fn send(cx: &mut usb_rx::Context, outbound: Outbound) {
+fn send(cx: &mut usb_rx::Context, outbound: Response) {
     cx.shared.usb.lock(|device| {
         device.write_outbound(outbound).ok();
     })
 }
 
-fn handle_inbound(cx: &mut usb_rx::Context, inbound: Inbound) -> Option<Outbound> {
-    match inbound {
-        Inbound::FirmwareVersion => firmware_version(),
-        Inbound::EnableGenerator(idx, val, period, step) => None,
-        Inbound::DisableGenerator(idx) => None,
-        Inbound::StartTimer(fps) => None,
-        Inbound::StopTimer => None,
-        Inbound::Unknown => None,
+fn handle_inbound(cx: &mut usb_rx::Context, command: Command) -> Option<Response> {
+    match command {
+        Command::GetVersion => firmware_version(),
+        Command::GetCapabilities => Some(Response::Capabilities(Capabilities::new(
+            Role::Emulator,
+            MAX_CHANNELS_COUNT as u8,
+            Features::FRAMING | Features::GENERATORS | Features::VALUES | Features::CHECKSUM,
+        ))),
+        Command::EnableGenerator {
+            index,
+            period,
+            value,
+            step,
+        } => {
+            cx.shared
+                .channels
+                .lock(|channels| channels.enable(index, period, value, step));
+            None
+        }
+        Command::DisableGenerator(index) => {
+            cx.shared.channels.lock(|channels| channels.disable(index));
+            None
+        }
+        Command::StartProducer(fps) => {
+            cx.shared.timer.lock(|timer| start_producer(timer, fps));
+            None
+        }
+        Command::StopProducer => {
+            cx.shared.timer.lock(stop_producer);
+            None
+        }
+        Command::GetValues => {
+            let values = cx.shared.channels.lock(|channels| channels.values());
+            Some(Response::Values(values))
+        }
+        Command::SetChecksum(enabled) => {
+            cx.shared.checksum.lock(|checksum| *checksum = enabled);
+            None
+        }
     }
 }
 
-fn firmware_version() -> Option<Outbound> {
+fn start_producer(timer: &mut timer::CountDownTimer<pac::TIM2>, fps: u8) {
+    if fps == 0 {
+        stop_producer(timer);
+    } else {
+        timer.start((fps as u32).hz());
+        timer.listen(timer::Event::Update);
+    }
+}
+
+fn stop_producer(timer: &mut timer::CountDownTimer<pac::TIM2>) {
+    timer.unlisten(timer::Event::Update);
+}
+
+fn firmware_version() -> Option<Response> {
     let major = env!("CARGO_PKG_VERSION_MAJOR").parse::<u8>().unwrap_or(0);
     let minor = env!("CARGO_PKG_VERSION_MINOR").parse::<u8>().unwrap_or(0);
     let patch = env!("CARGO_PKG_VERSION_PATCH").parse::<u8>().unwrap_or(0);
-    Some(Outbound::Version(major, minor, patch))
+    Some(Response::Version(major, minor, patch))
 }
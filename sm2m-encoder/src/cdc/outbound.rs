@@ -1,40 +1,29 @@
+use sm2m_protocol::{
+    encoder::Response,
+    packet::{Packet, MAX_PACKET_SIZE},
+};
 use usb_device::UsbError;
 
 use super::device::CdcDevice;
 
-pub enum UsbOutPacket {
-    Version(u8, u8),
-    Pong(u8, u8),
-    Param(u8, u16),
-}
-
 pub trait PacketWriter {
-    fn write_packet(&mut self, packet: UsbOutPacket) -> Result<usize, UsbError>;
+    fn write_packet(&mut self, packet: Response) -> Result<usize, UsbError>;
 }
 
 impl PacketWriter for CdcDevice {
-    fn write_packet(&mut self, packet: UsbOutPacket) -> Result<usize, UsbError> {
-        match packet {
-            UsbOutPacket::Version(major, minor) => {
-                let buf = [2 | major << 4, minor];
-                write_all(self, &buf)
-            }
-            UsbOutPacket::Pong(version, payload) => {
-                let buf = [3 | payload << 4, version];
-                write_all(self, &buf)
-            }
-            UsbOutPacket::Param(index, param) => {
-                let buf = [4 | index << 4, param as u8, (param >> 8) as u8];
-                write_all(self, &buf)
-            }
-        }
+    fn write_packet(&mut self, packet: Response) -> Result<usize, UsbError> {
+        let mut buf = [0u8; MAX_PACKET_SIZE];
+        let size = packet
+            .encode_into(&mut buf)
+            .map_err(|_| UsbError::BufferOverflow)?;
+        write_all(self, &buf[..size])
     }
 }
 
 fn write_all(device: &mut CdcDevice, buf: &[u8]) -> Result<usize, UsbError> {
     let mut sent = 0;
     while sent < buf.len() {
-        sent += device.write(buf)?;
+        sent += device.write(&buf[sent..])?;
     }
     Ok(sent)
 }
@@ -11,13 +11,13 @@ use cortex_m::asm;
 use embedded_hal::digital::v2::OutputPin;
 use panic_halt as _;
 use params_generator::ParamsGenerator;
+use sm2m_protocol::{
+    capabilities::{Capabilities, Features, Role},
+    encoder::{Command, Response},
+};
 use stm32f1xx_hal::gpio;
 
-use cdc::{
-    device::CdcDevice,
-    inbound::{PacketReader, UsbInPacket},
-    outbound::{PacketWriter, UsbOutPacket},
-};
+use cdc::{device::CdcDevice, inbound::PacketReader, outbound::PacketWriter};
 use led::Led;
 use params::SM2MParams;
 
@@ -77,46 +77,61 @@ const APP: () = {
     fn generate_param(cx: generate_param::Context) {}
 
     #[task(capacity = 5, resources = [led, params, params_generator, usb_device], schedule = [generate_param])]
-    fn handle_usb_inbound(cx: handle_usb_inbound::Context, inbound: UsbInPacket) {
+    fn handle_usb_inbound(cx: handle_usb_inbound::Context, inbound: Command) {
         let mut usb_device = cx.resources.usb_device;
         match inbound {
-            UsbInPacket::GetVersion => {
+            Command::GetVersion => {
                 let major = env!("CARGO_PKG_VERSION_MAJOR").parse::<u8>().unwrap_or(0);
                 let minor = env!("CARGO_PKG_VERSION_MINOR").parse::<u8>().unwrap_or(0);
-                let outbound = UsbOutPacket::Version(major, minor);
+                let outbound = Response::Version(major, minor);
                 usb_device.lock(|usb_device| {
                     write_usb_packet(usb_device, outbound);
                 });
             }
-            UsbInPacket::Ping(version, payload) => {
-                let response = UsbOutPacket::Pong(version.wrapping_add(1), payload);
+            Command::Ping { version, payload } => {
+                let response = Response::Pong {
+                    version: version.wrapping_add(1),
+                    payload,
+                };
                 usb_device.lock(|usb_device| {
                     write_usb_packet(usb_device, response);
                 });
             }
-            UsbInPacket::LedOn => cx.resources.led.on(),
-            UsbInPacket::LedOff => cx.resources.led.off(),
-            UsbInPacket::SetParam(index, param) => {
-                cx.resources.params.set(index as usize, param);
+            Command::LedOn => cx.resources.led.on(),
+            Command::LedOff => cx.resources.led.off(),
+            Command::SetParam { index, value } => {
+                cx.resources.params.set(index as usize, value);
             }
-            UsbInPacket::GetParam(index) => {
-                if let Some(param) = cx.resources.params.get(index as usize) {
-                    let response = UsbOutPacket::Param(index, param);
+            Command::GetParam(index) => {
+                if let Some(value) = cx.resources.params.get(index as usize) {
+                    let response = Response::Param { index, value };
                     usb_device.lock(|usb_device| write_usb_packet(usb_device, response))
                 }
             }
-            UsbInPacket::EnableParamGenerator(index, period, step) => {
+            Command::EnableParamGenerator {
+                index,
+                period,
+                step,
+            } => {
                 cx.resources
                     .params_generator
                     .enable(index as usize, period, step);
             }
-            UsbInPacket::DisableParamGenerator(index) => {
+            Command::DisableParamGenerator(index) => {
                 cx.resources.params_generator.disable(index as usize);
             }
-            UsbInPacket::EnableGlobalParamGenerator(freq) => {
+            Command::EnableGlobalParamGenerator(freq) => {
                 cx.schedule.generate_param(cx.scheduled).ok();
             }
-            UsbInPacket::DisableGlobalParamGenerator => {}
+            Command::DisableGlobalParamGenerator => {}
+            Command::GetCapabilities => {
+                let response = Response::Capabilities(Capabilities::new(
+                    Role::Encoder,
+                    params::PARAMS_COUNT as u8,
+                    Features::GENERATORS | Features::PING,
+                ));
+                usb_device.lock(|usb_device| write_usb_packet(usb_device, response));
+            }
         };
     }
 
@@ -138,10 +153,10 @@ const APP: () = {
     }
 };
 
-fn read_usb_packet(device: &mut CdcDevice) -> Option<UsbInPacket> {
+fn read_usb_packet(device: &mut CdcDevice) -> Option<Command> {
     device.read_packet().unwrap_or(None)
 }
 
-fn write_usb_packet(device: &mut CdcDevice, packet: UsbOutPacket) {
+fn write_usb_packet(device: &mut CdcDevice, packet: Response) {
     device.write_packet(packet).ok();
 }
@@ -1,4 +1,4 @@
-pub const PARAMS_COUNT: usize = 12;
+pub const PARAMS_COUNT: usize = sm2m_protocol::encoder::MAX_PARAMS_COUNT;
 
 #[derive(Default)]
 pub struct SM2MParams {
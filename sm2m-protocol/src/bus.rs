@@ -0,0 +1,49 @@
+use crate::crc;
+
+/// Separates parameter frames on the SM2M data bus, decoder counts parameters
+/// between two markers.
+pub const START_MARKER: u16 = 0x5555;
+
+/// Number of data lines of the SM2M data bus.
+pub const BUS_WIDTH: usize = 16;
+
+/// Returns checksum word trailing parameters when checksum mode is enabled,
+/// CRC-16/CCITT-FALSE of the parameters in little-endian byte order.
+pub fn checksum(params: &[u16]) -> u16 {
+    params.iter().fold(crc::INITIAL, |crc, param| {
+        crc::update(crc, &param.to_le_bytes())
+    })
+}
+
+/// Returns parameters of `frame` when its trailing checksum word matches.
+pub fn verify(frame: &[u16]) -> Option<&[u16]> {
+    let (checksum_word, params) = frame.split_last()?;
+    (checksum(params) == *checksum_word).then_some(params)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verify_trailing_checksum() {
+        let params = [0x1234, START_MARKER, 0];
+        let mut frame = params.to_vec();
+        frame.push(checksum(&params));
+
+        assert_eq!(verify(&frame), Some(&params[..]));
+        assert_eq!(verify(&[checksum(&[])]), Some(&[][..]));
+        assert_eq!(verify(&[]), None);
+    }
+
+    #[test]
+    fn reject_shifted_and_corrupted_frames() {
+        let params = [1, 2, 3, 4];
+        let mut frame = params.to_vec();
+        frame.push(checksum(&params));
+
+        assert_eq!(verify(&frame[1..]), None);
+        frame[2] ^= 0x0100;
+        assert_eq!(verify(&frame), None);
+    }
+}
@@ -0,0 +1,184 @@
+use core::ops::BitOr;
+
+use crate::packet::{self, PacketError};
+
+/// Revision of the protocol implemented by this crate. It is increased on
+/// every change which makes host and firmware unable to understand each other.
+pub const PROTOCOL_REVISION: u8 = 2;
+
+/// Opcode of capabilities command and response shared by every device role.
+pub const CAPABILITIES: u8 = 0x0f;
+
+pub const CAPABILITIES_SIZE: usize = 6;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    Decoder,
+    Emulator,
+    Encoder,
+}
+
+impl Role {
+    fn to_u8(self) -> u8 {
+        match self {
+            Role::Decoder => 1,
+            Role::Emulator => 2,
+            Role::Encoder => 3,
+        }
+    }
+
+    fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            1 => Some(Role::Decoder),
+            2 => Some(Role::Emulator),
+            3 => Some(Role::Encoder),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Features(u16);
+
+impl Features {
+    /// Packets are wrapped into checksummed frames.
+    pub const FRAMING: Features = Features(1);
+    /// Device streams parameters received from SM2M.
+    pub const PARAMS_STREAM: Features = Features(1 << 1);
+    /// Device generates parameters by itself.
+    pub const GENERATORS: Features = Features(1 << 2);
+    /// Device reports current values of its channels.
+    pub const VALUES: Features = Features(1 << 3);
+    /// Device answers ping requests.
+    pub const PING: Features = Features(1 << 4);
+    /// Device produces or verifies a checksum word trailing every bus frame.
+    pub const CHECKSUM: Features = Features(1 << 5);
+    /// Device reports diagnostics counters.
+    pub const STATS: Features = Features(1 << 6);
+    /// Device delivers frames according to a selectable policy.
+    pub const DELIVERY: Features = Features(1 << 7);
+
+    pub const fn empty() -> Self {
+        Features(0)
+    }
+
+    pub const fn from_bits(bits: u16) -> Self {
+        Features(bits)
+    }
+
+    pub const fn bits(self) -> u16 {
+        self.0
+    }
+
+    pub const fn contains(self, other: Features) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitOr for Features {
+    type Output = Features;
+
+    fn bitor(self, rhs: Features) -> Features {
+        Features(self.0 | rhs.0)
+    }
+}
+
+/// Answer to capabilities command, the same for every device role:
+///
+/// |Opcode 8 bits|Revision 8 bits|Role 8 bits|Max params 8 bits|Features 16 bits LE|
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities {
+    pub revision: u8,
+    pub role: Role,
+    pub max_params: u8,
+    pub features: Features,
+}
+
+impl Capabilities {
+    pub fn new(role: Role, max_params: u8, features: Features) -> Self {
+        Self {
+            revision: PROTOCOL_REVISION,
+            role,
+            max_params,
+            features,
+        }
+    }
+
+    pub fn encode_into(&self, buf: &mut [u8]) -> Result<usize, PacketError> {
+        let features = self.features.bits();
+        packet::write_bytes(
+            buf,
+            &[
+                CAPABILITIES,
+                self.revision,
+                self.role.to_u8(),
+                self.max_params,
+                features as u8,
+                (features >> 8) as u8,
+            ],
+        )
+    }
+
+    pub fn decode(buf: &[u8]) -> Result<Self, PacketError> {
+        packet::expect_len(buf, CAPABILITIES_SIZE)?;
+        let role = Role::from_u8(buf[2]).ok_or(PacketError::InvalidValue(CAPABILITIES))?;
+        Ok(Self {
+            revision: buf[1],
+            role,
+            max_params: buf[3],
+            features: Features::from_bits(packet::read_u16(buf, 4)),
+        })
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    fn role() -> impl Strategy<Value = Role> {
+        prop_oneof![
+            Just(Role::Decoder),
+            Just(Role::Emulator),
+            Just(Role::Encoder)
+        ]
+    }
+
+    pub(crate) fn capabilities() -> impl Strategy<Value = Capabilities> {
+        (any::<u8>(), role(), any::<u8>(), any::<u16>()).prop_map(
+            |(revision, role, max_params, features)| Capabilities {
+                revision,
+                role,
+                max_params,
+                features: Features::from_bits(features),
+            },
+        )
+    }
+
+    proptest! {
+        #[test]
+        fn capabilities_round_trip(capabilities in capabilities()) {
+            let mut buf = [0u8; CAPABILITIES_SIZE];
+            let size = capabilities.encode_into(&mut buf).unwrap();
+            prop_assert_eq!(Capabilities::decode(&buf[..size]), Ok(capabilities));
+        }
+    }
+
+    #[test]
+    fn combine_features() {
+        let features = Features::FRAMING | Features::VALUES;
+
+        assert!(features.contains(Features::FRAMING));
+        assert!(!features.contains(Features::PING));
+        assert_eq!(features.bits(), 0b1001);
+    }
+
+    #[test]
+    fn reject_unknown_role() {
+        assert_eq!(
+            Capabilities::decode(&[CAPABILITIES, 1, 9, 30, 0, 0]),
+            Err(PacketError::InvalidValue(CAPABILITIES))
+        );
+    }
+}
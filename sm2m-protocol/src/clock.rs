@@ -0,0 +1,44 @@
+/// Frequency of the decoder timestamp counter, timestamps are in microseconds.
+pub const TICKS_PER_SECOND: u32 = 1_000_000;
+
+/// Extends a wrapping 32-bit counter to 64 bits.
+///
+/// The counter must be sampled at least once per wrap, about every 71 minutes
+/// at `TICKS_PER_SECOND`, otherwise whole wraps are lost. The decoder samples
+/// it on every timer overflow.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ExtendedCounter {
+    wraps: u32,
+    last: u32,
+}
+
+impl ExtendedCounter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns `count` extended with the number of wraps seen so far.
+    pub fn extend(&mut self, count: u32) -> u64 {
+        if count < self.last {
+            self.wraps = self.wraps.wrapping_add(1);
+        }
+        self.last = count;
+        (self.wraps as u64) << 32 | count as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extend_across_wraps() {
+        let mut counter = ExtendedCounter::new();
+
+        assert_eq!(counter.extend(10), 10);
+        assert_eq!(counter.extend(u32::MAX), u32::MAX as u64);
+        assert_eq!(counter.extend(5), (1 << 32) + 5);
+        assert_eq!(counter.extend(5), (1 << 32) + 5);
+        assert_eq!(counter.extend(3), (2 << 32) + 3);
+    }
+}
@@ -0,0 +1,38 @@
+const POLYNOMIAL: u16 = 0x1021;
+pub const INITIAL: u16 = 0xffff;
+
+/// CRC-16/CCITT-FALSE checksum.
+pub fn crc16(data: &[u8]) -> u16 {
+    update(INITIAL, data)
+}
+
+pub fn update(mut crc: u16, data: &[u8]) -> u16 {
+    for byte in data {
+        crc ^= (*byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ POLYNOMIAL
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn calculate_check_value() {
+        assert_eq!(crc16(b"123456789"), 0x29b1);
+    }
+
+    #[test]
+    fn calculate_incrementally() {
+        let crc = update(update(INITIAL, b"1234"), b"56789");
+
+        assert_eq!(crc, crc16(b"123456789"));
+    }
+}
@@ -0,0 +1,445 @@
+use arrayvec::ArrayVec;
+
+use crate::{
+    capabilities::{Capabilities, CAPABILITIES},
+    packet::{self, Packet, PacketError},
+    queue::DeliveryPolicy,
+};
+
+pub const MAX_PARAMS_COUNT: usize = 30;
+
+const VERSION: u8 = 1;
+const PARAMS: u8 = 2;
+const CHECKSUM: u8 = 3;
+const STATS: u8 = 4;
+const RESET_STATS: u8 = 5;
+const DELIVERY: u8 = 6;
+
+const DELIVER_EVERY: u8 = 0;
+const DELIVER_LATEST: u8 = 1;
+const DELIVER_DECIMATED: u8 = 2;
+
+const PARAMS_OK: u8 = 0;
+const PARAMS_OVERFLOW: u8 = 1;
+const PARAMS_CORRUPTED: u8 = 2;
+
+pub type Params = ArrayVec<u16, MAX_PARAMS_COUNT>;
+
+/// Packets sent by host to SM2M decoder.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    GetVersion,
+    GetCapabilities,
+    /// Expects checksum word trailing every frame on the data bus, frames
+    /// with mismatching checksum are dropped. Disabled on power up, the SM2M
+    /// computer itself does not send checksums.
+    SetChecksum(bool),
+    GetStats,
+    ResetStats,
+    /// Selects frames delivered to host, every frame is delivered on power up.
+    SetDelivery(DeliveryPolicy),
+}
+
+/// Packets sent by SM2M decoder to host.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Response {
+    Version(u8, u8, u8),
+    Capabilities(Capabilities),
+    /// Frame received from SM2M, `timestamp` is the decoder clock in
+    /// microseconds when the first word of the frame was captured.
+    Params {
+        timestamp: u64,
+        params: Params,
+    },
+    /// SM2M sent more parameters than the decoder is capable to receive.
+    ParamsOverflow {
+        expected: u8,
+        received: u8,
+    },
+    /// Frame was dropped because of checksum mismatch, holds the number of
+    /// frames dropped since power up.
+    ParamsCorrupted(u32),
+    Stats(DecoderStats),
+}
+
+/// Diagnostics counters of the decoder firmware since power up or the last
+/// `Command::ResetStats`. Every counter wraps around.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct DecoderStats {
+    /// Words read from the data bus.
+    pub words: u32,
+    /// Words lost because the synchroniser queue was full.
+    pub dropped_words: u32,
+    /// Markers taken as frame boundaries.
+    pub markers: u32,
+    pub frames: u32,
+    pub overflows: u32,
+    /// Frames dropped because of checksum mismatch.
+    pub corrupted: u32,
+    /// Lock lost because a marker did not follow a frame.
+    pub resyncs: u32,
+    pub length_changes: u32,
+    /// Words received while not locked.
+    pub skipped_words: u32,
+    /// Frames lost because the USB transfer queue was full.
+    pub dropped_frames: u32,
+    pub usb_write_errors: u32,
+}
+
+const STATS_COUNT: usize = 11;
+
+impl DecoderStats {
+    fn to_array(self) -> [u32; STATS_COUNT] {
+        [
+            self.words,
+            self.dropped_words,
+            self.markers,
+            self.frames,
+            self.overflows,
+            self.corrupted,
+            self.resyncs,
+            self.length_changes,
+            self.skipped_words,
+            self.dropped_frames,
+            self.usb_write_errors,
+        ]
+    }
+
+    fn from_array(counters: [u32; STATS_COUNT]) -> Self {
+        let [words, dropped_words, markers, frames, overflows, corrupted, resyncs, length_changes, skipped_words, dropped_frames, usb_write_errors] =
+            counters;
+        Self {
+            words,
+            dropped_words,
+            markers,
+            frames,
+            overflows,
+            corrupted,
+            resyncs,
+            length_changes,
+            skipped_words,
+            dropped_frames,
+            usb_write_errors,
+        }
+    }
+}
+
+impl Packet for Command {
+    fn encode_into(&self, buf: &mut [u8]) -> Result<usize, PacketError> {
+        match self {
+            Command::GetVersion => packet::write_bytes(buf, &[VERSION]),
+            Command::GetCapabilities => packet::write_bytes(buf, &[CAPABILITIES]),
+            Command::SetChecksum(enabled) => packet::write_bytes(buf, &[CHECKSUM, *enabled as u8]),
+            Command::GetStats => packet::write_bytes(buf, &[STATS]),
+            Command::ResetStats => packet::write_bytes(buf, &[RESET_STATS]),
+            Command::SetDelivery(policy) => {
+                let (mode, fps) = match policy {
+                    DeliveryPolicy::Every => (DELIVER_EVERY, 0),
+                    DeliveryPolicy::Latest => (DELIVER_LATEST, 0),
+                    DeliveryPolicy::Decimate(fps) => (DELIVER_DECIMATED, *fps),
+                };
+                packet::write_bytes(buf, &[DELIVERY, mode, fps])
+            }
+        }
+    }
+
+    fn decode(buf: &[u8]) -> Result<Self, PacketError> {
+        match packet::opcode(buf)? {
+            VERSION => Ok(Command::GetVersion),
+            CAPABILITIES => Ok(Command::GetCapabilities),
+            CHECKSUM => {
+                packet::expect_len(buf, 2)?;
+                Ok(Command::SetChecksum(buf[1] != 0))
+            }
+            STATS => Ok(Command::GetStats),
+            RESET_STATS => Ok(Command::ResetStats),
+            DELIVERY => {
+                packet::expect_len(buf, 3)?;
+                let policy = match (buf[1], buf[2]) {
+                    (DELIVER_EVERY, _) => DeliveryPolicy::Every,
+                    (DELIVER_LATEST, _) => DeliveryPolicy::Latest,
+                    (DELIVER_DECIMATED, fps) if fps > 0 => DeliveryPolicy::Decimate(fps),
+                    _ => return Err(PacketError::InvalidValue(DELIVERY)),
+                };
+                Ok(Command::SetDelivery(policy))
+            }
+            opcode => Err(PacketError::UnknownOpcode(opcode)),
+        }
+    }
+}
+
+impl Packet for Response {
+    fn encode_into(&self, buf: &mut [u8]) -> Result<usize, PacketError> {
+        match self {
+            Response::Version(major, minor, patch) => {
+                packet::write_bytes(buf, &[VERSION, *major, *minor, *patch])
+            }
+            Response::Capabilities(capabilities) => capabilities.encode_into(buf),
+            Response::Params { timestamp, params } => encode_params(*timestamp, params, buf),
+            Response::ParamsOverflow { expected, received } => {
+                packet::write_bytes(buf, &[PARAMS, PARAMS_OVERFLOW, *expected, *received])
+            }
+            Response::ParamsCorrupted(dropped) => {
+                let [b0, b1, b2, b3] = dropped.to_le_bytes();
+                packet::write_bytes(buf, &[PARAMS, PARAMS_CORRUPTED, b0, b1, b2, b3])
+            }
+            Response::Stats(stats) => encode_stats(stats, buf),
+        }
+    }
+
+    fn decode(buf: &[u8]) -> Result<Self, PacketError> {
+        match packet::opcode(buf)? {
+            VERSION => {
+                packet::expect_len(buf, 4)?;
+                Ok(Response::Version(buf[1], buf[2], buf[3]))
+            }
+            CAPABILITIES => Ok(Response::Capabilities(Capabilities::decode(buf)?)),
+            PARAMS => decode_params(buf),
+            STATS => decode_stats(buf),
+            opcode => Err(PacketError::UnknownOpcode(opcode)),
+        }
+    }
+}
+
+/// Status, count and timestamp precede parameters.
+const PARAMS_HEADER_SIZE: usize = 11;
+
+fn encode_params(timestamp: u64, params: &Params, buf: &mut [u8]) -> Result<usize, PacketError> {
+    let size = PARAMS_HEADER_SIZE + params.len() * 2;
+    if buf.len() < size {
+        return Err(PacketError::BufferTooSmall(size));
+    }
+
+    buf[0] = PARAMS;
+    buf[1] = PARAMS_OK;
+    buf[2] = params.len() as u8;
+    buf[3..PARAMS_HEADER_SIZE].copy_from_slice(&timestamp.to_le_bytes());
+    for (chunk, param) in buf[PARAMS_HEADER_SIZE..size]
+        .chunks_exact_mut(2)
+        .zip(params)
+    {
+        chunk[0] = *param as u8;
+        chunk[1] = (*param >> 8) as u8;
+    }
+    Ok(size)
+}
+
+fn decode_params(buf: &[u8]) -> Result<Response, PacketError> {
+    packet::expect_len(buf, 3)?;
+    match buf[1] {
+        PARAMS_OK => {
+            let count = buf[2] as usize;
+            if count > MAX_PARAMS_COUNT {
+                return Err(PacketError::InvalidValue(PARAMS));
+            }
+
+            packet::expect_len(buf, PARAMS_HEADER_SIZE + count * 2)?;
+            let params = (0..count)
+                .map(|index| packet::read_u16(buf, PARAMS_HEADER_SIZE + index * 2))
+                .collect();
+            Ok(Response::Params {
+                timestamp: packet::read_u64(buf, 3),
+                params,
+            })
+        }
+        PARAMS_OVERFLOW => {
+            packet::expect_len(buf, 4)?;
+            Ok(Response::ParamsOverflow {
+                expected: buf[2],
+                received: buf[3],
+            })
+        }
+        PARAMS_CORRUPTED => {
+            packet::expect_len(buf, 6)?;
+            Ok(Response::ParamsCorrupted(packet::read_u32(buf, 2)))
+        }
+        _ => Err(PacketError::InvalidValue(PARAMS)),
+    }
+}
+
+fn encode_stats(stats: &DecoderStats, buf: &mut [u8]) -> Result<usize, PacketError> {
+    let size = 1 + STATS_COUNT * 4;
+    if buf.len() < size {
+        return Err(PacketError::BufferTooSmall(size));
+    }
+
+    buf[0] = STATS;
+    for (chunk, counter) in buf[1..size].chunks_exact_mut(4).zip(stats.to_array()) {
+        chunk.copy_from_slice(&counter.to_le_bytes());
+    }
+    Ok(size)
+}
+
+fn decode_stats(buf: &[u8]) -> Result<Response, PacketError> {
+    packet::expect_len(buf, 1 + STATS_COUNT * 4)?;
+    let mut counters = [0u32; STATS_COUNT];
+    for (index, counter) in counters.iter_mut().enumerate() {
+        *counter = packet::read_u32(buf, 1 + index * 4);
+    }
+    Ok(Response::Stats(DecoderStats::from_array(counters)))
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::{collection, prelude::*};
+
+    use crate::{capabilities::tests::capabilities, packet::MAX_PACKET_SIZE};
+
+    use super::*;
+
+    fn command() -> impl Strategy<Value = Command> {
+        prop_oneof![
+            Just(Command::GetVersion),
+            Just(Command::GetCapabilities),
+            any::<bool>().prop_map(Command::SetChecksum),
+            Just(Command::GetStats),
+            Just(Command::ResetStats),
+            prop_oneof![
+                Just(DeliveryPolicy::Every),
+                Just(DeliveryPolicy::Latest),
+                (1..=u8::MAX).prop_map(DeliveryPolicy::Decimate),
+            ]
+            .prop_map(Command::SetDelivery),
+        ]
+    }
+
+    fn response() -> impl Strategy<Value = Response> {
+        prop_oneof![
+            any::<(u8, u8, u8)>()
+                .prop_map(|(major, minor, patch)| Response::Version(major, minor, patch)),
+            (
+                any::<u64>(),
+                collection::vec(any::<u16>(), 0..=MAX_PARAMS_COUNT)
+            )
+                .prop_map(|(timestamp, params)| Response::Params {
+                    timestamp,
+                    params: params.into_iter().collect()
+                }),
+            any::<(u8, u8)>()
+                .prop_map(|(expected, received)| Response::ParamsOverflow { expected, received }),
+            any::<u32>().prop_map(Response::ParamsCorrupted),
+            any::<[u32; STATS_COUNT]>()
+                .prop_map(|counters| Response::Stats(DecoderStats::from_array(counters))),
+            capabilities().prop_map(Response::Capabilities),
+        ]
+    }
+
+    proptest! {
+        #[test]
+        fn command_round_trip(command in command()) {
+            let mut buf = [0u8; MAX_PACKET_SIZE];
+            let size = command.encode_into(&mut buf).unwrap();
+            prop_assert_eq!(Command::decode(&buf[..size]), Ok(command));
+        }
+
+        #[test]
+        fn response_round_trip(response in response()) {
+            let mut buf = [0u8; MAX_PACKET_SIZE];
+            let size = response.encode_into(&mut buf).unwrap();
+            prop_assert_eq!(Response::decode(&buf[..size]), Ok(response));
+        }
+
+        #[test]
+        fn decode_arbitrary_bytes_without_panic(buf in collection::vec(any::<u8>(), 0..80)) {
+            let _ = Command::decode(&buf);
+            let _ = Response::decode(&buf);
+        }
+    }
+
+    #[test]
+    fn encode_commands() {
+        let mut buf = [0u8; MAX_PACKET_SIZE];
+
+        let size = Command::GetVersion.encode_into(&mut buf).unwrap();
+
+        assert_eq!(&buf[..size], &[1]);
+        assert_eq!(Command::decode(&buf[..size]), Ok(Command::GetVersion));
+
+        let size = Command::GetCapabilities.encode_into(&mut buf).unwrap();
+
+        assert_eq!(&buf[..size], &[0x0f]);
+        assert_eq!(Command::decode(&buf[..size]), Ok(Command::GetCapabilities));
+
+        let size = Command::SetChecksum(true).encode_into(&mut buf).unwrap();
+
+        assert_eq!(&buf[..size], &[3, 1]);
+        assert_eq!(
+            Command::decode(&buf[..size]),
+            Ok(Command::SetChecksum(true))
+        );
+
+        let size = Command::GetStats.encode_into(&mut buf).unwrap();
+
+        assert_eq!(&buf[..size], &[4]);
+        assert_eq!(Command::decode(&buf[..size]), Ok(Command::GetStats));
+
+        let size = Command::ResetStats.encode_into(&mut buf).unwrap();
+
+        assert_eq!(&buf[..size], &[5]);
+        assert_eq!(Command::decode(&buf[..size]), Ok(Command::ResetStats));
+
+        let command = Command::SetDelivery(DeliveryPolicy::Decimate(25));
+        let size = command.encode_into(&mut buf).unwrap();
+
+        assert_eq!(&buf[..size], &[6, 2, 25]);
+        assert_eq!(Command::decode(&buf[..size]), Ok(command));
+    }
+
+    #[test]
+    fn reject_decimation_to_zero_fps() {
+        assert_eq!(
+            Command::decode(&[6, 2, 0]),
+            Err(PacketError::InvalidValue(6))
+        );
+    }
+
+    #[test]
+    fn encode_stats() {
+        let mut buf = [0u8; MAX_PACKET_SIZE];
+        let stats = DecoderStats {
+            words: 0x0102_0304,
+            usb_write_errors: 7,
+            ..DecoderStats::default()
+        };
+
+        let size = Response::Stats(stats).encode_into(&mut buf).unwrap();
+
+        assert_eq!(size, 45);
+        assert_eq!(&buf[..5], &[4, 4, 3, 2, 1]);
+        assert_eq!(&buf[41..45], &[7, 0, 0, 0]);
+    }
+
+    #[test]
+    fn encode_params() {
+        let mut buf = [0u8; MAX_PACKET_SIZE];
+        let params = [0x5555, 0x0102].into_iter().collect();
+
+        let response = Response::Params {
+            timestamp: 0x0102_0304,
+            params,
+        };
+
+        let size = response.encode_into(&mut buf).unwrap();
+
+        assert_eq!(
+            &buf[..size],
+            &[2, 0, 2, 4, 3, 2, 1, 0, 0, 0, 0, 0x55, 0x55, 2, 1]
+        );
+    }
+
+    #[test]
+    fn reject_truncated_params() {
+        assert_eq!(
+            Response::decode(&[2, 0, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0x55, 0x55]),
+            Err(PacketError::Truncated(2))
+        );
+    }
+
+    #[test]
+    fn reject_too_many_params() {
+        let mut buf = [0u8; MAX_PACKET_SIZE];
+        buf[..3].copy_from_slice(&[2, 0, 31]);
+
+        assert_eq!(Response::decode(&buf), Err(PacketError::InvalidValue(2)));
+    }
+}
@@ -0,0 +1,302 @@
+use crate::{
+    crc,
+    frame::{Frame, CRC_SIZE, HEADER_SIZE, MAX_FRAME_SIZE, MAX_PAYLOAD_SIZE, START_BYTE},
+};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct FrameStats {
+    pub frames: u32,
+    pub crc_errors: u32,
+    pub length_errors: u32,
+    pub sequence_gaps: u32,
+    pub lost_frames: u32,
+    pub dropped_bytes: u32,
+}
+
+/// Streaming frame decoder which accepts bytes in arbitrary chunks.
+///
+/// After a CRC or length error the deframer discards the false start byte and
+/// rescans the already buffered bytes for the next start byte, so a valid frame
+/// directly following garbage is never lost. A rescan may find several complete
+/// frames at once, [`Deframer::pop`] returns the ones [`Deframer::push`] could not.
+pub struct Deframer {
+    buf: [u8; MAX_FRAME_SIZE],
+    len: usize,
+    expected_seq: Option<u8>,
+    stats: FrameStats,
+}
+
+impl Default for Deframer {
+    fn default() -> Self {
+        Self {
+            buf: [0; MAX_FRAME_SIZE],
+            len: 0,
+            expected_seq: None,
+            stats: FrameStats::default(),
+        }
+    }
+}
+
+impl Deframer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn stats(&self) -> FrameStats {
+        self.stats
+    }
+
+    pub fn reset_stats(&mut self) {
+        self.stats = FrameStats::default();
+    }
+
+    pub fn reset(&mut self) {
+        self.stats.dropped_bytes = self.stats.dropped_bytes.wrapping_add(self.len as u32);
+        self.len = 0;
+        self.expected_seq = None;
+    }
+
+    pub fn push(&mut self, byte: u8) -> Option<Frame> {
+        if self.len == 0 && byte != START_BYTE {
+            self.stats.dropped_bytes = self.stats.dropped_bytes.wrapping_add(1);
+            return None;
+        }
+
+        self.buf[self.len] = byte;
+        self.len += 1;
+        self.pop()
+    }
+
+    /// Returns the next frame already complete in the buffer. Call it after
+    /// `push` returned a frame until it returns `None`.
+    pub fn pop(&mut self) -> Option<Frame> {
+        loop {
+            if self.len < HEADER_SIZE {
+                return None;
+            }
+
+            let payload_len = self.buf[1] as usize;
+            if payload_len > MAX_PAYLOAD_SIZE {
+                self.stats.length_errors = self.stats.length_errors.wrapping_add(1);
+                self.resync();
+                continue;
+            }
+
+            let frame_len = HEADER_SIZE + payload_len + CRC_SIZE;
+            if self.len < frame_len {
+                return None;
+            }
+
+            let expected_crc = crc::crc16(&self.buf[1..HEADER_SIZE + payload_len]);
+            let actual_crc = self.buf[frame_len - 2] as u16 | (self.buf[frame_len - 1] as u16) << 8;
+            if expected_crc != actual_crc {
+                self.stats.crc_errors = self.stats.crc_errors.wrapping_add(1);
+                self.resync();
+                continue;
+            }
+
+            let seq = self.buf[2];
+            let frame = Frame::new(seq, &self.buf[HEADER_SIZE..HEADER_SIZE + payload_len]).ok();
+            self.buf.copy_within(frame_len..self.len, 0);
+            self.len -= frame_len;
+            self.skip_to_start(0);
+            self.register_frame(seq);
+            return frame;
+        }
+    }
+
+    fn register_frame(&mut self, seq: u8) {
+        self.stats.frames = self.stats.frames.wrapping_add(1);
+        if let Some(expected_seq) = self.expected_seq {
+            if seq != expected_seq {
+                let lost = seq.wrapping_sub(expected_seq) as u32;
+                self.stats.sequence_gaps = self.stats.sequence_gaps.wrapping_add(1);
+                self.stats.lost_frames = self.stats.lost_frames.wrapping_add(lost);
+            }
+        }
+        self.expected_seq = Some(seq.wrapping_add(1));
+    }
+
+    /// Drops the false start byte and the bytes up to the next start byte.
+    fn resync(&mut self) {
+        self.skip_to_start(1);
+    }
+
+    fn skip_to_start(&mut self, from: usize) {
+        let next_start = self.buf[from..self.len]
+            .iter()
+            .position(|byte| *byte == START_BYTE)
+            .map(|position| position + from)
+            .unwrap_or(self.len);
+        if next_start == 0 {
+            return;
+        }
+        self.buf.copy_within(next_start..self.len, 0);
+        self.len -= next_start;
+        self.stats.dropped_bytes = self.stats.dropped_bytes.wrapping_add(next_start as u32);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::frame::{encode_into, FrameWriter};
+
+    use super::*;
+
+    fn encode(seq: u8, payload: &[u8]) -> Vec<u8> {
+        let mut buf = [0u8; MAX_FRAME_SIZE];
+        let size = encode_into(seq, payload, &mut buf).unwrap();
+        buf[..size].to_vec()
+    }
+
+    fn push_all(deframer: &mut Deframer, bytes: &[u8]) -> Vec<Frame> {
+        let mut frames = Vec::new();
+        for byte in bytes {
+            frames.extend(deframer.push(*byte));
+            frames.extend(core::iter::from_fn(|| deframer.pop()));
+        }
+        frames
+    }
+
+    #[test]
+    fn decode_two_frames_from_one_chunk() {
+        let mut deframer = Deframer::new();
+        let mut bytes = encode(0, &[1, 2]);
+        bytes.extend(encode(1, &[3]));
+
+        let frames = push_all(&mut deframer, &bytes);
+
+        assert_eq!(
+            frames,
+            vec![
+                Frame::new(0, &[1, 2]).unwrap(),
+                Frame::new(1, &[3]).unwrap()
+            ]
+        );
+        assert_eq!(deframer.stats().frames, 2);
+    }
+
+    #[test]
+    fn decode_frame_split_across_chunks() {
+        let mut deframer = Deframer::new();
+        let bytes = encode(0, &[1, 2, 3, 4]);
+
+        let first = push_all(&mut deframer, &bytes[..3]);
+        let second = push_all(&mut deframer, &bytes[3..]);
+
+        assert!(first.is_empty());
+        assert_eq!(second, vec![Frame::new(0, &[1, 2, 3, 4]).unwrap()]);
+    }
+
+    #[test]
+    fn decode_empty_payload() {
+        let mut deframer = Deframer::new();
+
+        let frames = push_all(&mut deframer, &encode(0, &[]));
+
+        assert_eq!(frames, vec![Frame::new(0, &[]).unwrap()]);
+    }
+
+    #[test]
+    fn resynchronise_after_garbage() {
+        let mut deframer = Deframer::new();
+        let mut bytes = vec![0x00, 0x13, START_BYTE, 0x02, 0xff];
+        bytes.extend(encode(0, &[9]));
+
+        let frames = push_all(&mut deframer, &bytes);
+
+        assert_eq!(frames, vec![Frame::new(0, &[9]).unwrap()]);
+        assert_eq!(deframer.stats().dropped_bytes, 5);
+    }
+
+    #[test]
+    fn count_crc_failure_and_keep_following_frame() {
+        let mut deframer = Deframer::new();
+        let mut bytes = encode(0, &[1, 2, 3]);
+        bytes[4] ^= 0x10;
+        bytes.extend(encode(1, &[4]));
+
+        let frames = push_all(&mut deframer, &bytes);
+
+        assert_eq!(frames, vec![Frame::new(1, &[4]).unwrap()]);
+        assert_eq!(deframer.stats().crc_errors, 1);
+    }
+
+    #[test]
+    fn keep_back_to_back_frames_after_false_start() {
+        let mut deframer = Deframer::new();
+        let mut bytes = vec![START_BYTE, 12];
+        bytes.extend(encode(0, &[1, 2]));
+        bytes.extend(encode(1, &[3]));
+        let third = encode(2, &[4]);
+
+        // The false start completes inside the third frame, the rescan finds
+        // two complete frames.
+        let first = bytes
+            .iter()
+            .chain(&third[..2])
+            .filter_map(|byte| deframer.push(*byte))
+            .collect::<Vec<_>>();
+        let buffered = deframer.pop();
+        let rest = push_all(&mut deframer, &third[2..]);
+
+        assert_eq!(first, vec![Frame::new(0, &[1, 2]).unwrap()]);
+        assert_eq!(buffered, Some(Frame::new(1, &[3]).unwrap()));
+        assert_eq!(rest, vec![Frame::new(2, &[4]).unwrap()]);
+        assert_eq!(deframer.stats().lost_frames, 0);
+        assert_eq!(deframer.stats().dropped_bytes, 2);
+    }
+
+    #[test]
+    fn count_length_errors() {
+        let mut deframer = Deframer::new();
+        let mut bytes = vec![START_BYTE, 0xff, 0];
+        bytes.extend(encode(0, &[1]));
+
+        let frames = push_all(&mut deframer, &bytes);
+
+        assert_eq!(frames.len(), 1);
+        assert_eq!(deframer.stats().length_errors, 1);
+    }
+
+    #[test]
+    fn count_sequence_gaps() {
+        let mut deframer = Deframer::new();
+        let mut bytes = encode(254, &[1]);
+        bytes.extend(encode(255, &[2]));
+        bytes.extend(encode(2, &[3]));
+
+        let frames = push_all(&mut deframer, &bytes);
+
+        assert_eq!(frames.len(), 3);
+        assert_eq!(deframer.stats().sequence_gaps, 1);
+        assert_eq!(deframer.stats().lost_frames, 2);
+    }
+
+    #[test]
+    fn decode_frames_produced_by_writer() {
+        let mut writer = FrameWriter::new();
+        let mut deframer = Deframer::new();
+        let mut buf = [0u8; MAX_FRAME_SIZE];
+        let mut frames = Vec::new();
+
+        for payload in [[1u8, 2], [3, 4], [5, 6]] {
+            let size = writer.encode_into(&payload, &mut buf).unwrap();
+            frames.extend(push_all(&mut deframer, &buf[..size]));
+        }
+
+        assert_eq!(frames.len(), 3);
+        assert_eq!(deframer.stats().sequence_gaps, 0);
+    }
+
+    #[test]
+    fn forget_sequence_on_reset() {
+        let mut deframer = Deframer::new();
+        push_all(&mut deframer, &encode(10, &[1]));
+
+        deframer.reset();
+        push_all(&mut deframer, &encode(0, &[1]));
+
+        assert_eq!(deframer.stats().sequence_gaps, 0);
+    }
+}
@@ -0,0 +1,252 @@
+use arrayvec::ArrayVec;
+
+use crate::{
+    capabilities::{Capabilities, CAPABILITIES},
+    packet::{self, Packet, PacketError},
+};
+
+pub const MAX_CHANNELS_COUNT: usize = 12;
+
+const VERSION: u8 = 1;
+const ENABLE_GENERATOR: u8 = 2;
+const DISABLE_GENERATOR: u8 = 3;
+const START_PRODUCER: u8 = 4;
+const STOP_PRODUCER: u8 = 5;
+const VALUES: u8 = 6;
+const CHECKSUM: u8 = 7;
+
+pub type Values = ArrayVec<u16, MAX_CHANNELS_COUNT>;
+
+/// Packets sent by host to SM2M emulator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    GetVersion,
+    GetCapabilities,
+    /// Generates new parameter `value` every `period` frames changing it by `step`.
+    EnableGenerator {
+        index: u8,
+        period: u8,
+        value: u16,
+        step: u16,
+    },
+    DisableGenerator(u8),
+    /// Starts producing parameter frames with the given frames per second rate.
+    StartProducer(u8),
+    StopProducer,
+    /// Requests current value of every emulator channel.
+    GetValues,
+    /// Appends checksum word to every produced frame, see `bus::checksum`.
+    SetChecksum(bool),
+}
+
+/// Packets sent by SM2M emulator to host.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Response {
+    Version(u8, u8, u8),
+    Capabilities(Capabilities),
+    /// Current value of every emulator channel, one word per channel.
+    Values(Values),
+}
+
+impl Packet for Command {
+    fn encode_into(&self, buf: &mut [u8]) -> Result<usize, PacketError> {
+        match *self {
+            Command::GetVersion => packet::write_bytes(buf, &[VERSION]),
+            Command::GetCapabilities => packet::write_bytes(buf, &[CAPABILITIES]),
+            Command::EnableGenerator {
+                index,
+                period,
+                value,
+                step,
+            } => packet::write_bytes(
+                buf,
+                &[
+                    ENABLE_GENERATOR,
+                    index,
+                    period,
+                    value as u8,
+                    (value >> 8) as u8,
+                    step as u8,
+                    (step >> 8) as u8,
+                ],
+            ),
+            Command::DisableGenerator(index) => {
+                packet::write_bytes(buf, &[DISABLE_GENERATOR, index])
+            }
+            Command::StartProducer(fps) => packet::write_bytes(buf, &[START_PRODUCER, fps]),
+            Command::StopProducer => packet::write_bytes(buf, &[STOP_PRODUCER]),
+            Command::GetValues => packet::write_bytes(buf, &[VALUES]),
+            Command::SetChecksum(enabled) => packet::write_bytes(buf, &[CHECKSUM, enabled as u8]),
+        }
+    }
+
+    fn decode(buf: &[u8]) -> Result<Self, PacketError> {
+        match packet::opcode(buf)? {
+            VERSION => Ok(Command::GetVersion),
+            CAPABILITIES => Ok(Command::GetCapabilities),
+            ENABLE_GENERATOR => {
+                packet::expect_len(buf, 7)?;
+                Ok(Command::EnableGenerator {
+                    index: buf[1],
+                    period: buf[2],
+                    value: packet::read_u16(buf, 3),
+                    step: packet::read_u16(buf, 5),
+                })
+            }
+            DISABLE_GENERATOR => {
+                packet::expect_len(buf, 2)?;
+                Ok(Command::DisableGenerator(buf[1]))
+            }
+            START_PRODUCER => {
+                packet::expect_len(buf, 2)?;
+                Ok(Command::StartProducer(buf[1]))
+            }
+            STOP_PRODUCER => Ok(Command::StopProducer),
+            VALUES => Ok(Command::GetValues),
+            CHECKSUM => {
+                packet::expect_len(buf, 2)?;
+                Ok(Command::SetChecksum(buf[1] != 0))
+            }
+            opcode => Err(PacketError::UnknownOpcode(opcode)),
+        }
+    }
+}
+
+impl Packet for Response {
+    fn encode_into(&self, buf: &mut [u8]) -> Result<usize, PacketError> {
+        match self {
+            Response::Version(major, minor, patch) => {
+                packet::write_bytes(buf, &[VERSION, *major, *minor, *patch])
+            }
+            Response::Capabilities(capabilities) => capabilities.encode_into(buf),
+            Response::Values(values) => encode_values(values, buf),
+        }
+    }
+
+    fn decode(buf: &[u8]) -> Result<Self, PacketError> {
+        match packet::opcode(buf)? {
+            VERSION => {
+                packet::expect_len(buf, 4)?;
+                Ok(Response::Version(buf[1], buf[2], buf[3]))
+            }
+            CAPABILITIES => Ok(Response::Capabilities(Capabilities::decode(buf)?)),
+            VALUES => decode_values(buf),
+            opcode => Err(PacketError::UnknownOpcode(opcode)),
+        }
+    }
+}
+
+fn encode_values(values: &Values, buf: &mut [u8]) -> Result<usize, PacketError> {
+    let size = 2 + values.len() * 2;
+    if buf.len() < size {
+        return Err(PacketError::BufferTooSmall(size));
+    }
+
+    buf[0] = VALUES;
+    buf[1] = values.len() as u8;
+    for (chunk, value) in buf[2..size].chunks_exact_mut(2).zip(values) {
+        chunk[0] = *value as u8;
+        chunk[1] = (*value >> 8) as u8;
+    }
+    Ok(size)
+}
+
+fn decode_values(buf: &[u8]) -> Result<Response, PacketError> {
+    packet::expect_len(buf, 2)?;
+    let count = buf[1] as usize;
+    if count > MAX_CHANNELS_COUNT {
+        return Err(PacketError::InvalidValue(VALUES));
+    }
+
+    packet::expect_len(buf, 2 + count * 2)?;
+    let values = (0..count)
+        .map(|index| packet::read_u16(buf, 2 + index * 2))
+        .collect();
+    Ok(Response::Values(values))
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::{collection, prelude::*};
+
+    use crate::{capabilities::tests::capabilities, packet::MAX_PACKET_SIZE};
+
+    use super::*;
+
+    fn command() -> impl Strategy<Value = Command> {
+        prop_oneof![
+            Just(Command::GetVersion),
+            any::<(u8, u8, u16, u16)>().prop_map(|(index, period, value, step)| {
+                Command::EnableGenerator {
+                    index,
+                    period,
+                    value,
+                    step,
+                }
+            }),
+            any::<u8>().prop_map(Command::DisableGenerator),
+            any::<u8>().prop_map(Command::StartProducer),
+            Just(Command::StopProducer),
+            Just(Command::GetValues),
+            Just(Command::GetCapabilities),
+            any::<bool>().prop_map(Command::SetChecksum),
+        ]
+    }
+
+    fn response() -> impl Strategy<Value = Response> {
+        prop_oneof![
+            any::<(u8, u8, u8)>()
+                .prop_map(|(major, minor, patch)| Response::Version(major, minor, patch)),
+            collection::vec(any::<u16>(), 0..=MAX_CHANNELS_COUNT)
+                .prop_map(|values| Response::Values(values.into_iter().collect())),
+            capabilities().prop_map(Response::Capabilities),
+        ]
+    }
+
+    proptest! {
+        #[test]
+        fn command_round_trip(command in command()) {
+            let mut buf = [0u8; MAX_PACKET_SIZE];
+            let size = command.encode_into(&mut buf).unwrap();
+            prop_assert_eq!(Command::decode(&buf[..size]), Ok(command));
+        }
+
+        #[test]
+        fn response_round_trip(response in response()) {
+            let mut buf = [0u8; MAX_PACKET_SIZE];
+            let size = response.encode_into(&mut buf).unwrap();
+            prop_assert_eq!(Response::decode(&buf[..size]), Ok(response));
+        }
+
+        #[test]
+        fn decode_arbitrary_bytes_without_panic(buf in collection::vec(any::<u8>(), 0..32)) {
+            let _ = Command::decode(&buf);
+            let _ = Response::decode(&buf);
+        }
+    }
+
+    #[test]
+    fn encode_enable_generator() {
+        let mut buf = [0u8; MAX_PACKET_SIZE];
+        let command = Command::EnableGenerator {
+            index: 0,
+            period: 1,
+            value: 100,
+            step: 0x0201,
+        };
+
+        let size = command.encode_into(&mut buf).unwrap();
+
+        assert_eq!(&buf[..size], &[2, 0, 1, 100, 0, 1, 2]);
+    }
+
+    #[test]
+    fn encode_values() {
+        let mut buf = [0u8; MAX_PACKET_SIZE];
+        let values = [100, 0x0201].into_iter().collect();
+
+        let size = Response::Values(values).encode_into(&mut buf).unwrap();
+
+        assert_eq!(&buf[..size], &[6, 2, 100, 0, 1, 2]);
+    }
+}
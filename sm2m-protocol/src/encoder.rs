@@ -0,0 +1,282 @@
+use crate::{
+    capabilities::{Capabilities, CAPABILITIES},
+    packet::{self, Packet, PacketError},
+};
+
+pub const MAX_PARAMS_COUNT: usize = 12;
+
+const GET_VERSION: u8 = 1;
+const ERROR: u8 = 1;
+const VERSION: u8 = 2;
+const PING: u8 = 2;
+const PONG: u8 = 3;
+const SET_LED: u8 = 3;
+const SET_PARAM: u8 = 4;
+const PARAM: u8 = 4;
+const GET_PARAM: u8 = 5;
+const ENABLE_PARAM_GENERATOR: u8 = 6;
+const DISABLE_PARAM_GENERATOR: u8 = 7;
+const ENABLE_GLOBAL_PARAM_GENERATOR: u8 = 8;
+const DISABLE_GLOBAL_PARAM_GENERATOR: u8 = 9;
+
+const NIBBLE_MAX: u8 = 0x0f;
+
+/// Packets sent by host to legacy SM2M encoder.
+///
+/// The encoder packs the opcode into the low nibble of the first byte and
+/// uses the high nibble for a small argument, so these packets are not framed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    GetVersion,
+    Ping { version: u8, payload: u8 },
+    LedOn,
+    LedOff,
+    SetParam { index: u8, value: u16 },
+    GetParam(u8),
+    EnableParamGenerator { index: u8, period: u8, step: u16 },
+    DisableParamGenerator(u8),
+    EnableGlobalParamGenerator(u8),
+    DisableGlobalParamGenerator,
+    GetCapabilities,
+}
+
+/// Packets sent by legacy SM2M encoder to host.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Response {
+    Error(u8),
+    Version(u8, u8),
+    Pong { version: u8, payload: u8 },
+    Param { index: u8, value: u16 },
+    Capabilities(Capabilities),
+}
+
+impl Packet for Command {
+    fn encode_into(&self, buf: &mut [u8]) -> Result<usize, PacketError> {
+        match *self {
+            Command::GetVersion => packet::write_bytes(buf, &[GET_VERSION]),
+            Command::Ping { version, payload } => {
+                packet::write_bytes(buf, &[nibble(PING, payload)?, version])
+            }
+            Command::LedOn => packet::write_bytes(buf, &[nibble(SET_LED, 1)?]),
+            Command::LedOff => packet::write_bytes(buf, &[SET_LED]),
+            Command::SetParam { index, value } => packet::write_bytes(
+                buf,
+                &[nibble(SET_PARAM, index)?, value as u8, (value >> 8) as u8],
+            ),
+            Command::GetParam(index) => packet::write_bytes(buf, &[nibble(GET_PARAM, index)?]),
+            Command::EnableParamGenerator {
+                index,
+                period,
+                step,
+            } => packet::write_bytes(
+                buf,
+                &[
+                    nibble(ENABLE_PARAM_GENERATOR, index)?,
+                    step as u8,
+                    (step >> 8) as u8,
+                    nibble(0, period)?,
+                ],
+            ),
+            Command::DisableParamGenerator(index) => {
+                packet::write_bytes(buf, &[nibble(DISABLE_PARAM_GENERATOR, index)?])
+            }
+            Command::EnableGlobalParamGenerator(freq) => {
+                packet::write_bytes(buf, &[ENABLE_GLOBAL_PARAM_GENERATOR, freq])
+            }
+            Command::DisableGlobalParamGenerator => {
+                packet::write_bytes(buf, &[DISABLE_GLOBAL_PARAM_GENERATOR])
+            }
+            Command::GetCapabilities => packet::write_bytes(buf, &[CAPABILITIES]),
+        }
+    }
+
+    fn decode(buf: &[u8]) -> Result<Self, PacketError> {
+        let (opcode, arg) = split(packet::opcode(buf)?);
+        match opcode {
+            GET_VERSION => Ok(Command::GetVersion),
+            PING => {
+                packet::expect_len(buf, 2)?;
+                Ok(Command::Ping {
+                    version: buf[1],
+                    payload: arg,
+                })
+            }
+            SET_LED if arg & 1 == 0 => Ok(Command::LedOff),
+            SET_LED => Ok(Command::LedOn),
+            SET_PARAM => {
+                packet::expect_len(buf, 3)?;
+                Ok(Command::SetParam {
+                    index: arg,
+                    value: packet::read_u16(buf, 1),
+                })
+            }
+            GET_PARAM => Ok(Command::GetParam(arg)),
+            ENABLE_PARAM_GENERATOR => {
+                packet::expect_len(buf, 4)?;
+                Ok(Command::EnableParamGenerator {
+                    index: arg,
+                    period: buf[3] >> 4,
+                    step: packet::read_u16(buf, 1),
+                })
+            }
+            DISABLE_PARAM_GENERATOR => Ok(Command::DisableParamGenerator(arg)),
+            ENABLE_GLOBAL_PARAM_GENERATOR => {
+                packet::expect_len(buf, 2)?;
+                Ok(Command::EnableGlobalParamGenerator(buf[1]))
+            }
+            DISABLE_GLOBAL_PARAM_GENERATOR => Ok(Command::DisableGlobalParamGenerator),
+            CAPABILITIES if arg == 0 => Ok(Command::GetCapabilities),
+            _ => Err(PacketError::UnknownOpcode(opcode)),
+        }
+    }
+}
+
+impl Packet for Response {
+    fn encode_into(&self, buf: &mut [u8]) -> Result<usize, PacketError> {
+        match *self {
+            Response::Error(reason) => packet::write_bytes(buf, &[nibble(ERROR, reason)?]),
+            Response::Version(major, minor) => {
+                packet::write_bytes(buf, &[nibble(VERSION, major)?, minor])
+            }
+            Response::Pong { version, payload } => {
+                packet::write_bytes(buf, &[nibble(PONG, payload)?, version])
+            }
+            Response::Param { index, value } => packet::write_bytes(
+                buf,
+                &[nibble(PARAM, index)?, value as u8, (value >> 8) as u8],
+            ),
+            Response::Capabilities(capabilities) => capabilities.encode_into(buf),
+        }
+    }
+
+    fn decode(buf: &[u8]) -> Result<Self, PacketError> {
+        let (opcode, arg) = split(packet::opcode(buf)?);
+        match opcode {
+            ERROR => Ok(Response::Error(arg)),
+            VERSION => {
+                packet::expect_len(buf, 2)?;
+                Ok(Response::Version(arg, buf[1]))
+            }
+            PONG => {
+                packet::expect_len(buf, 2)?;
+                Ok(Response::Pong {
+                    version: buf[1],
+                    payload: arg,
+                })
+            }
+            PARAM => {
+                packet::expect_len(buf, 3)?;
+                Ok(Response::Param {
+                    index: arg,
+                    value: packet::read_u16(buf, 1),
+                })
+            }
+            CAPABILITIES if arg == 0 => Ok(Response::Capabilities(Capabilities::decode(buf)?)),
+            _ => Err(PacketError::UnknownOpcode(opcode)),
+        }
+    }
+}
+
+fn nibble(opcode: u8, arg: u8) -> Result<u8, PacketError> {
+    if arg > NIBBLE_MAX {
+        Err(PacketError::InvalidValue(opcode))
+    } else {
+        Ok(opcode | arg << 4)
+    }
+}
+
+fn split(byte: u8) -> (u8, u8) {
+    (byte & NIBBLE_MAX, byte >> 4)
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::{collection, prelude::*};
+
+    use crate::{capabilities::tests::capabilities, packet::MAX_PACKET_SIZE};
+
+    use super::*;
+
+    fn command() -> impl Strategy<Value = Command> {
+        let nibble = 0..=NIBBLE_MAX;
+        prop_oneof![
+            Just(Command::GetVersion),
+            (any::<u8>(), nibble.clone())
+                .prop_map(|(version, payload)| Command::Ping { version, payload }),
+            Just(Command::LedOn),
+            Just(Command::LedOff),
+            (nibble.clone(), any::<u16>())
+                .prop_map(|(index, value)| Command::SetParam { index, value }),
+            nibble.clone().prop_map(Command::GetParam),
+            (nibble.clone(), nibble.clone(), any::<u16>()).prop_map(|(index, period, step)| {
+                Command::EnableParamGenerator {
+                    index,
+                    period,
+                    step,
+                }
+            }),
+            nibble.prop_map(Command::DisableParamGenerator),
+            any::<u8>().prop_map(Command::EnableGlobalParamGenerator),
+            Just(Command::DisableGlobalParamGenerator),
+            Just(Command::GetCapabilities),
+        ]
+    }
+
+    fn response() -> impl Strategy<Value = Response> {
+        let nibble = 0..=NIBBLE_MAX;
+        prop_oneof![
+            nibble.clone().prop_map(Response::Error),
+            (nibble.clone(), any::<u8>())
+                .prop_map(|(major, minor)| Response::Version(major, minor)),
+            (any::<u8>(), nibble.clone())
+                .prop_map(|(version, payload)| Response::Pong { version, payload }),
+            (nibble, any::<u16>()).prop_map(|(index, value)| Response::Param { index, value }),
+            capabilities().prop_map(Response::Capabilities),
+        ]
+    }
+
+    proptest! {
+        #[test]
+        fn command_round_trip(command in command()) {
+            let mut buf = [0u8; MAX_PACKET_SIZE];
+            let size = command.encode_into(&mut buf).unwrap();
+            prop_assert_eq!(Command::decode(&buf[..size]), Ok(command));
+        }
+
+        #[test]
+        fn response_round_trip(response in response()) {
+            let mut buf = [0u8; MAX_PACKET_SIZE];
+            let size = response.encode_into(&mut buf).unwrap();
+            prop_assert_eq!(Response::decode(&buf[..size]), Ok(response));
+        }
+
+        #[test]
+        fn decode_arbitrary_bytes_without_panic(buf in collection::vec(any::<u8>(), 0..8)) {
+            let _ = Command::decode(&buf);
+            let _ = Response::decode(&buf);
+        }
+    }
+
+    #[test]
+    fn reject_argument_wider_than_nibble() {
+        let mut buf = [0u8; MAX_PACKET_SIZE];
+
+        let result = Command::GetParam(16).encode_into(&mut buf);
+
+        assert_eq!(result, Err(PacketError::InvalidValue(GET_PARAM)));
+    }
+
+    #[test]
+    fn encode_legacy_packets() {
+        let mut buf = [0u8; MAX_PACKET_SIZE];
+
+        let size = Command::Ping {
+            version: 1,
+            payload: 15,
+        }
+        .encode_into(&mut buf)
+        .unwrap();
+
+        assert_eq!(&buf[..size], &[0xf2, 1]);
+    }
+}
@@ -0,0 +1,186 @@
+use crate::crc;
+
+pub const START_BYTE: u8 = 0xa5;
+pub const HEADER_SIZE: usize = 3;
+pub const CRC_SIZE: usize = 2;
+pub const MAX_PAYLOAD_SIZE: usize = 128;
+pub const MAX_FRAME_SIZE: usize = HEADER_SIZE + MAX_PAYLOAD_SIZE + CRC_SIZE;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameError {
+    PayloadTooLarge(usize),
+    BufferTooSmall(usize),
+}
+
+/// Frame layout on the wire:
+///
+/// |Start 8 bits|Length 8 bits|Sequence 8 bits|Payload|CRC-16 little-endian|
+///
+/// The CRC covers length, sequence and payload bytes.
+#[derive(Clone, Copy)]
+pub struct Frame {
+    pub seq: u8,
+    len: u8,
+    payload: [u8; MAX_PAYLOAD_SIZE],
+}
+
+impl Frame {
+    pub fn new(seq: u8, payload: &[u8]) -> Result<Self, FrameError> {
+        if payload.len() > MAX_PAYLOAD_SIZE {
+            return Err(FrameError::PayloadTooLarge(payload.len()));
+        }
+
+        let mut frame = Self {
+            seq,
+            len: payload.len() as u8,
+            payload: [0; MAX_PAYLOAD_SIZE],
+        };
+        frame.payload[..payload.len()].copy_from_slice(payload);
+        Ok(frame)
+    }
+
+    pub fn payload(&self) -> &[u8] {
+        &self.payload[..self.len as usize]
+    }
+
+    pub fn encoded_len(&self) -> usize {
+        HEADER_SIZE + self.len as usize + CRC_SIZE
+    }
+
+    pub fn encode_into(&self, buf: &mut [u8]) -> Result<usize, FrameError> {
+        encode_into(self.seq, self.payload(), buf)
+    }
+}
+
+impl core::fmt::Debug for Frame {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Frame")
+            .field("seq", &self.seq)
+            .field("payload", &self.payload())
+            .finish()
+    }
+}
+
+impl PartialEq for Frame {
+    fn eq(&self, other: &Self) -> bool {
+        self.seq == other.seq && self.payload() == other.payload()
+    }
+}
+
+impl Eq for Frame {}
+
+pub fn encode_into(seq: u8, payload: &[u8], buf: &mut [u8]) -> Result<usize, FrameError> {
+    if payload.len() > MAX_PAYLOAD_SIZE {
+        return Err(FrameError::PayloadTooLarge(payload.len()));
+    }
+
+    let size = HEADER_SIZE + payload.len() + CRC_SIZE;
+    if buf.len() < size {
+        return Err(FrameError::BufferTooSmall(size));
+    }
+
+    buf[0] = START_BYTE;
+    buf[1] = payload.len() as u8;
+    buf[2] = seq;
+    buf[HEADER_SIZE..HEADER_SIZE + payload.len()].copy_from_slice(payload);
+    let crc = crc::crc16(&buf[1..HEADER_SIZE + payload.len()]);
+    buf[size - 2] = crc as u8;
+    buf[size - 1] = (crc >> 8) as u8;
+    Ok(size)
+}
+
+/// Assigns consecutive sequence numbers to outgoing frames.
+#[derive(Default)]
+pub struct FrameWriter {
+    seq: u8,
+}
+
+impl FrameWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn encode_into(&mut self, payload: &[u8], buf: &mut [u8]) -> Result<usize, FrameError> {
+        let size = self.prepare_into(payload, buf)?;
+        self.commit();
+        Ok(size)
+    }
+
+    /// Encodes a frame without using up its sequence number, call
+    /// [`FrameWriter::commit`] once the frame is sent.
+    pub fn prepare_into(&self, payload: &[u8], buf: &mut [u8]) -> Result<usize, FrameError> {
+        encode_into(self.seq, payload, buf)
+    }
+
+    pub fn commit(&mut self) {
+        self.seq = self.seq.wrapping_add(1);
+    }
+
+    pub fn reset(&mut self) {
+        self.seq = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_frame() {
+        let mut buf = [0u8; MAX_FRAME_SIZE];
+
+        let size = encode_into(7, &[1, 2, 3], &mut buf).unwrap();
+
+        let crc = crc::crc16(&[3, 7, 1, 2, 3]);
+        assert_eq!(
+            &buf[..size],
+            &[START_BYTE, 3, 7, 1, 2, 3, crc as u8, (crc >> 8) as u8]
+        );
+    }
+
+    #[test]
+    fn reject_large_payload() {
+        let mut buf = [0u8; 256];
+
+        let result = encode_into(0, &[0; MAX_PAYLOAD_SIZE + 1], &mut buf);
+
+        assert_eq!(
+            result,
+            Err(FrameError::PayloadTooLarge(MAX_PAYLOAD_SIZE + 1))
+        );
+    }
+
+    #[test]
+    fn reject_small_buffer() {
+        let mut buf = [0u8; 4];
+
+        let result = encode_into(0, &[1], &mut buf);
+
+        assert_eq!(result, Err(FrameError::BufferTooSmall(6)));
+    }
+
+    #[test]
+    fn increment_sequence_number() {
+        let mut writer = FrameWriter::new();
+        let mut buf = [0u8; MAX_FRAME_SIZE];
+
+        writer.encode_into(&[1], &mut buf).unwrap();
+        writer.encode_into(&[1], &mut buf).unwrap();
+
+        assert_eq!(buf[2], 1);
+    }
+
+    #[test]
+    fn keep_sequence_until_commit() {
+        let mut writer = FrameWriter::new();
+        let mut buf = [0u8; MAX_FRAME_SIZE];
+
+        writer.prepare_into(&[1], &mut buf).unwrap();
+        writer.prepare_into(&[1], &mut buf).unwrap();
+        assert_eq!(buf[2], 0);
+
+        writer.commit();
+        writer.prepare_into(&[1], &mut buf).unwrap();
+        assert_eq!(buf[2], 1);
+    }
+}
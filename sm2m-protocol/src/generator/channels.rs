@@ -0,0 +1,64 @@
+use crate::emulator::{Values, MAX_CHANNELS_COUNT};
+
+use super::sequential::SequentialGenerator;
+
+#[derive(Default)]
+pub struct Channels {
+    generators: [Option<SequentialGenerator>; MAX_CHANNELS_COUNT],
+}
+
+impl Channels {
+    pub fn enable(&mut self, index: u8, period: u8, value: u16, step: u16) {
+        if let Some(generator) = self.generators.get_mut(index as usize) {
+            *generator = Some(SequentialGenerator::new(value, step, period));
+        }
+    }
+
+    pub fn disable(&mut self, index: u8) {
+        if let Some(generator) = self.generators.get_mut(index as usize) {
+            *generator = None;
+        }
+    }
+
+    pub fn values(&self) -> Values {
+        self.generators
+            .iter()
+            .map(|generator| generator.as_ref().map_or(0, |generator| generator.value))
+            .collect()
+    }
+
+    pub fn generate(&mut self) {
+        self.generators
+            .iter_mut()
+            .flatten()
+            .for_each(SequentialGenerator::generate);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generate_enabled_channels_only() {
+        let mut channels = Channels::default();
+        channels.enable(0, 1, 10, 1);
+        channels.enable(2, 1, 20, 2);
+        channels.enable(MAX_CHANNELS_COUNT as u8, 1, 30, 3);
+
+        channels.generate();
+        let values = channels.values();
+
+        assert_eq!(values.len(), MAX_CHANNELS_COUNT);
+        assert_eq!(&values[..3], &[11, 0, 22]);
+    }
+
+    #[test]
+    fn report_zero_for_disabled_channel() {
+        let mut channels = Channels::default();
+        channels.enable(1, 0, 7, 0);
+        channels.disable(1);
+
+        assert_eq!(channels.values()[1], 0);
+    }
+}
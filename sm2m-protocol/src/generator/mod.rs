@@ -1,4 +1,5 @@
+pub mod channels;
 pub mod sequential;
 
-mod period;
 mod direction;
+mod period;
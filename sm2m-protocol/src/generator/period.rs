@@ -9,14 +9,15 @@ impl Period {
     }
 
     pub fn count(&mut self) {
-        self.count += 1;
+        self.count = self.count.saturating_add(1);
     }
 
     pub fn reset(&mut self) {
         self.count = 0;
     }
 
+    /// Zero limit never elapses which keeps generator value constant.
     pub fn elapsed(&mut self) -> bool {
-        self.count >= self.limit
+        self.limit > 0 && self.count >= self.limit
     }
 }
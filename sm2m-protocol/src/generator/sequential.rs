@@ -0,0 +1,93 @@
+use super::direction::Direction;
+use super::period::Period;
+
+pub struct SequentialGenerator {
+    pub min: u16,
+    pub max: u16,
+    pub step: u16,
+    pub value: u16,
+    pub period: Period,
+    pub direction: Direction,
+}
+
+impl SequentialGenerator {
+    pub fn new(value: u16, step: u16, period: u8) -> Self {
+        Self {
+            min: u16::MIN,
+            max: u16::MAX,
+            step,
+            value,
+            period: Period::new(period as usize),
+            direction: Direction::Increment,
+        }
+    }
+
+    pub fn generate(&mut self) {
+        if self.should_generate() {
+            self.period.reset();
+            match self.direction {
+                Direction::Increment => self.increment(),
+                Direction::Decrement => self.decrement(),
+            }
+        }
+    }
+
+    fn should_generate(&mut self) -> bool {
+        self.period.count();
+        self.period.elapsed()
+    }
+
+    fn increment(&mut self) {
+        self.value = self.value.saturating_add(self.step);
+        if self.value >= self.max {
+            self.value = self.max;
+            self.direction = self.direction.reverse();
+        }
+    }
+
+    fn decrement(&mut self) {
+        self.value = self.value.saturating_sub(self.step);
+        if self.value <= self.min {
+            self.value = self.min;
+            self.direction = self.direction.reverse();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn change_value_every_period() {
+        let mut generator = SequentialGenerator::new(100, 5, 2);
+
+        let values = (0..4)
+            .map(|_| {
+                generator.generate();
+                generator.value
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(values, [100, 105, 105, 110]);
+    }
+
+    #[test]
+    fn reverse_direction_at_bounds() {
+        let mut generator = SequentialGenerator::new(u16::MAX - 1, 2, 1);
+
+        generator.generate();
+        assert_eq!(generator.value, u16::MAX);
+        generator.generate();
+        assert_eq!(generator.value, u16::MAX - 2);
+    }
+
+    #[test]
+    fn keep_constant_value_with_zero_period() {
+        let mut generator = SequentialGenerator::new(0x1234, 1, 0);
+
+        (0..10).for_each(|_| generator.generate());
+
+        assert_eq!(generator.value, 0x1234);
+    }
+}
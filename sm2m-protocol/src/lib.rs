@@ -0,0 +1,15 @@
+#![cfg_attr(not(test), no_std)]
+
+pub mod bus;
+pub mod capabilities;
+pub mod clock;
+pub mod crc;
+pub mod decoder;
+pub mod deframer;
+pub mod emulator;
+pub mod encoder;
+pub mod frame;
+pub mod generator;
+pub mod packet;
+pub mod queue;
+pub mod sync;
@@ -0,0 +1,75 @@
+use crate::frame::MAX_PAYLOAD_SIZE;
+
+pub const MAX_PACKET_SIZE: usize = MAX_PAYLOAD_SIZE;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PacketError {
+    Empty,
+    UnknownOpcode(u8),
+    Truncated(u8),
+    InvalidValue(u8),
+    BufferTooSmall(usize),
+}
+
+/// Packet which can be encoded into and decoded from its wire representation.
+///
+/// The first byte of every packet is the opcode, the rest is the opcode
+/// specific payload in little-endian byte order.
+pub trait Packet: Sized {
+    fn encode_into(&self, buf: &mut [u8]) -> Result<usize, PacketError>;
+    fn decode(buf: &[u8]) -> Result<Self, PacketError>;
+}
+
+pub(crate) fn write_bytes(buf: &mut [u8], bytes: &[u8]) -> Result<usize, PacketError> {
+    if buf.len() < bytes.len() {
+        return Err(PacketError::BufferTooSmall(bytes.len()));
+    }
+
+    buf[..bytes.len()].copy_from_slice(bytes);
+    Ok(bytes.len())
+}
+
+pub(crate) fn opcode(buf: &[u8]) -> Result<u8, PacketError> {
+    buf.first().copied().ok_or(PacketError::Empty)
+}
+
+pub(crate) fn expect_len(buf: &[u8], len: usize) -> Result<(), PacketError> {
+    if buf.len() < len {
+        Err(PacketError::Truncated(buf[0]))
+    } else {
+        Ok(())
+    }
+}
+
+pub(crate) fn read_u16(buf: &[u8], offset: usize) -> u16 {
+    buf[offset] as u16 | (buf[offset + 1] as u16) << 8
+}
+
+pub(crate) fn read_u32(buf: &[u8], offset: usize) -> u32 {
+    read_u16(buf, offset) as u32 | (read_u16(buf, offset + 2) as u32) << 16
+}
+
+pub(crate) fn read_u64(buf: &[u8], offset: usize) -> u64 {
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&buf[offset..offset + 8]);
+    u64::from_le_bytes(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reject_small_buffer() {
+        let mut buf = [0u8; 2];
+
+        let result = write_bytes(&mut buf, &[1, 2, 3]);
+
+        assert_eq!(result, Err(PacketError::BufferTooSmall(3)));
+    }
+
+    #[test]
+    fn read_little_endian_word() {
+        assert_eq!(read_u16(&[0, 0x34, 0x12], 1), 0x1234);
+    }
+}
@@ -0,0 +1,297 @@
+use crate::{clock::TICKS_PER_SECOND, sync::SyncEvent};
+
+/// Selects which frames the decoder delivers to the host.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum DeliveryPolicy {
+    /// Every frame is delivered, frames arriving while the queue is full are dropped.
+    #[default]
+    Every,
+    /// Only the newest frame is kept, it replaces the one not sent yet.
+    Latest,
+    /// At most this many frames per second are delivered, measured by frame
+    /// timestamps. Frames arriving sooner are skipped.
+    Decimate(u8),
+}
+
+/// Outcome of [`FrameQueue::push`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Push {
+    Queued,
+    /// Event replaced the pending one in latest-only mode.
+    Replaced,
+    /// Frame arrived sooner than the decimation interval allows.
+    Skipped,
+    /// Queue was full, the event was dropped.
+    Dropped,
+}
+
+/// Ring buffer of synchroniser events waiting for the USB transfer.
+///
+/// Events are delivered in order according to the [`DeliveryPolicy`]. Only
+/// frames are decimated, overflow and checksum events are always queued.
+pub struct FrameQueue<const N: usize> {
+    slots: [Option<SyncEvent>; N],
+    head: usize,
+    len: usize,
+    policy: DeliveryPolicy,
+    last_delivered: Option<u64>,
+}
+
+impl<const N: usize> Default for FrameQueue<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> FrameQueue<N> {
+    pub fn new() -> Self {
+        Self {
+            slots: core::array::from_fn(|_| None),
+            head: 0,
+            len: 0,
+            policy: DeliveryPolicy::Every,
+            last_delivered: None,
+        }
+    }
+
+    pub fn policy(&self) -> DeliveryPolicy {
+        self.policy
+    }
+
+    /// Switches the policy, switching to latest-only keeps the newest event.
+    pub fn set_policy(&mut self, policy: DeliveryPolicy) {
+        if policy == DeliveryPolicy::Latest {
+            while self.len > 1 {
+                self.pop();
+            }
+        }
+        self.policy = policy;
+        self.last_delivered = None;
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.len == N
+    }
+
+    pub fn push(&mut self, event: SyncEvent) -> Push {
+        match self.policy {
+            DeliveryPolicy::Latest if !self.is_empty() => {
+                let tail = self.index(self.len - 1);
+                self.slots[tail] = Some(event);
+                return Push::Replaced;
+            }
+            DeliveryPolicy::Decimate(fps) => {
+                if let SyncEvent::Frame { timestamp, .. } = &event {
+                    let interval = (TICKS_PER_SECOND / fps.max(1) as u32) as u64;
+                    if self
+                        .last_delivered
+                        .is_some_and(|last| timestamp.wrapping_sub(last) < interval)
+                    {
+                        return Push::Skipped;
+                    }
+                    if self.is_full() {
+                        return Push::Dropped;
+                    }
+                    self.last_delivered = Some(*timestamp);
+                }
+            }
+            _ => {}
+        }
+        if self.is_full() {
+            return Push::Dropped;
+        }
+        let tail = self.index(self.len);
+        self.slots[tail] = Some(event);
+        self.len += 1;
+        Push::Queued
+    }
+
+    pub fn pop(&mut self) -> Option<SyncEvent> {
+        if self.is_empty() {
+            return None;
+        }
+        let event = self.slots[self.head].take();
+        self.head = self.index(1);
+        self.len -= 1;
+        event
+    }
+
+    /// Puts back event taken by [`FrameQueue::pop`] which could not be sent,
+    /// it is the next one popped. Only events of which nothing was sent may be
+    /// put back, otherwise the host receives a duplicate. The event is dropped
+    /// when the queue filled up meanwhile or, in latest-only mode, a newer
+    /// event arrived. Requeued frames are not decimated again.
+    pub fn requeue(&mut self, event: SyncEvent) -> Push {
+        if self.is_full() || (self.policy == DeliveryPolicy::Latest && !self.is_empty()) {
+            return Push::Dropped;
+        }
+        self.head = self.index(N - 1);
+        self.slots[self.head] = Some(event);
+        self.len += 1;
+        Push::Queued
+    }
+
+    fn index(&self, offset: usize) -> usize {
+        (self.head + offset) % N
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(timestamp: u64, word: u16) -> SyncEvent {
+        SyncEvent::Frame {
+            timestamp,
+            params: [word].into_iter().collect(),
+        }
+    }
+
+    fn drain<const N: usize>(queue: &mut FrameQueue<N>) -> Vec<SyncEvent> {
+        core::iter::from_fn(|| queue.pop()).collect()
+    }
+
+    #[test]
+    fn deliver_every_frame_in_order_across_wrap() {
+        let mut queue = FrameQueue::<3>::new();
+
+        for word in 0..2 {
+            assert_eq!(queue.push(frame(0, word)), Push::Queued);
+        }
+        assert_eq!(queue.pop(), Some(frame(0, 0)));
+        for word in 2..4 {
+            assert_eq!(queue.push(frame(0, word)), Push::Queued);
+        }
+
+        assert!(queue.is_full());
+        assert_eq!(queue.push(frame(0, 4)), Push::Dropped);
+        assert_eq!(
+            drain(&mut queue),
+            vec![frame(0, 1), frame(0, 2), frame(0, 3)]
+        );
+        assert_eq!(queue.pop(), None);
+    }
+
+    #[test]
+    fn keep_only_latest_event() {
+        let mut queue = FrameQueue::<4>::new();
+        queue.set_policy(DeliveryPolicy::Latest);
+
+        assert_eq!(queue.push(frame(0, 1)), Push::Queued);
+        assert_eq!(queue.push(frame(0, 2)), Push::Replaced);
+        assert_eq!(queue.push(SyncEvent::Corrupted(1)), Push::Replaced);
+
+        assert_eq!(drain(&mut queue), vec![SyncEvent::Corrupted(1)]);
+    }
+
+    #[test]
+    fn keep_newest_event_when_switching_to_latest() {
+        let mut queue = FrameQueue::<4>::new();
+        for word in 0..3 {
+            queue.push(frame(0, word));
+        }
+
+        queue.set_policy(DeliveryPolicy::Latest);
+
+        assert_eq!(drain(&mut queue), vec![frame(0, 2)]);
+    }
+
+    #[test]
+    fn decimate_frames_by_timestamp() {
+        let mut queue = FrameQueue::<8>::new();
+        queue.set_policy(DeliveryPolicy::Decimate(10));
+
+        let pushed = (0..6)
+            .map(|index| queue.push(frame(index * 40_000, index as u16)))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            pushed,
+            vec![
+                Push::Queued,
+                Push::Skipped,
+                Push::Skipped,
+                Push::Queued,
+                Push::Skipped,
+                Push::Skipped
+            ]
+        );
+        assert_eq!(queue.push(SyncEvent::Overflow(31)), Push::Queued);
+        assert_eq!(
+            drain(&mut queue),
+            vec![frame(0, 0), frame(120_000, 3), SyncEvent::Overflow(31)]
+        );
+    }
+
+    #[test]
+    fn not_count_dropped_frame_as_delivered() {
+        let mut queue = FrameQueue::<1>::new();
+        queue.set_policy(DeliveryPolicy::Decimate(10));
+        queue.push(frame(0, 0));
+
+        assert_eq!(queue.push(frame(100_000, 1)), Push::Dropped);
+        queue.pop();
+
+        assert_eq!(queue.push(frame(110_000, 2)), Push::Queued);
+    }
+
+    #[test]
+    fn requeue_unsent_event_first() {
+        let mut queue = FrameQueue::<2>::new();
+        queue.push(frame(0, 0));
+        queue.push(frame(0, 1));
+
+        let event = queue.pop().unwrap();
+
+        assert_eq!(queue.requeue(event), Push::Queued);
+        assert_eq!(drain(&mut queue), vec![frame(0, 0), frame(0, 1)]);
+    }
+
+    #[test]
+    fn deliver_requeued_event_once() {
+        let mut queue = FrameQueue::<4>::new();
+        queue.push(frame(0, 0));
+        queue.push(frame(0, 1));
+
+        // The first attempt is blocked, the second one sends the event.
+        let blocked = queue.pop().unwrap();
+        queue.requeue(blocked);
+        let sent = queue.pop().unwrap();
+        queue.push(frame(0, 2));
+
+        assert_eq!(sent, frame(0, 0));
+        assert_eq!(drain(&mut queue), vec![frame(0, 1), frame(0, 2)]);
+    }
+
+    #[test]
+    fn keep_requeued_frame_when_decimating() {
+        let mut queue = FrameQueue::<2>::new();
+        queue.set_policy(DeliveryPolicy::Decimate(10));
+        queue.push(frame(0, 0));
+        let blocked = queue.pop().unwrap();
+
+        assert_eq!(queue.push(frame(50_000, 1)), Push::Skipped);
+        assert_eq!(queue.requeue(blocked), Push::Queued);
+        assert_eq!(drain(&mut queue), vec![frame(0, 0)]);
+    }
+
+    #[test]
+    fn drop_requeued_event_superseded_in_latest_mode() {
+        let mut queue = FrameQueue::<2>::new();
+        queue.set_policy(DeliveryPolicy::Latest);
+        queue.push(frame(0, 0));
+        let event = queue.pop().unwrap();
+        queue.push(frame(0, 1));
+
+        assert_eq!(queue.requeue(event), Push::Dropped);
+        assert_eq!(drain(&mut queue), vec![frame(0, 1)]);
+    }
+}
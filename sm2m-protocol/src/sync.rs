@@ -0,0 +1,579 @@
+use arrayvec::ArrayVec;
+
+use crate::{
+    bus::{self, START_MARKER},
+    decoder::{DecoderStats, Params, Response, MAX_PARAMS_COUNT},
+};
+
+/// Number of equally spaced markers required before the synchroniser locks.
+pub const DEFAULT_CONFIRMATIONS: u8 = 2;
+
+/// Parameters followed by the checksum word.
+const MAX_FRAME_LEN: usize = MAX_PARAMS_COUNT + 1;
+
+type Words = ArrayVec<u16, MAX_FRAME_LEN>;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SyncStats {
+    /// Markers taken as frame boundaries.
+    pub markers: u32,
+    pub frames: u32,
+    /// Marker did not follow a frame, either the marker was lost or the frame
+    /// length changed. The frame is dropped.
+    pub lost_locks: u32,
+    /// Synchroniser locked on a length different from the previous lock.
+    pub length_changes: u32,
+    pub overflows: u32,
+    /// Frames dropped because of checksum mismatch.
+    pub corrupted: u32,
+    /// Words received while not locked.
+    pub skipped_words: u32,
+}
+
+impl From<SyncStats> for DecoderStats {
+    /// Firmware queue and USB counters are left zero.
+    fn from(stats: SyncStats) -> Self {
+        Self {
+            markers: stats.markers,
+            frames: stats.frames,
+            overflows: stats.overflows,
+            corrupted: stats.corrupted,
+            resyncs: stats.lost_locks,
+            length_changes: stats.length_changes,
+            skipped_words: stats.skipped_words,
+            ..DecoderStats::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SyncEvent {
+    /// Frame with the timestamp of its first word.
+    Frame { timestamp: u64, params: Params },
+    /// Frame holds this many parameters, which exceeds `MAX_PARAMS_COUNT`.
+    Overflow(usize),
+    /// Frame was dropped because of checksum mismatch, holds `SyncStats::corrupted`.
+    Corrupted(u32),
+}
+
+impl From<SyncEvent> for Response {
+    fn from(event: SyncEvent) -> Self {
+        match event {
+            SyncEvent::Frame { timestamp, params } => Response::Params { timestamp, params },
+            SyncEvent::Overflow(received) => Response::ParamsOverflow {
+                expected: MAX_PARAMS_COUNT as u8,
+                received: received.min(u8::MAX as usize) as u8,
+            },
+            SyncEvent::Corrupted(dropped) => Response::ParamsCorrupted(dropped),
+        }
+    }
+}
+
+enum State {
+    WaitForMarker,
+    /// Counts words since the last marker.
+    Measure(usize),
+    ReadParams(usize, Words),
+    /// Frame is complete, it is delivered when the next word is a marker.
+    ExpectMarker(usize, Words),
+}
+
+/// Splits SM2M data bus words into parameters frames.
+///
+/// Frame length is learned from the distance between markers and the
+/// synchroniser locks once `confirmations` consecutive frames have the same
+/// length. While locked words are taken by position, so data words equal to
+/// `START_MARKER` don't split frames. A frame is delivered only once the next
+/// marker confirms its end, when it is not followed by a marker the frame is
+/// dropped, the lock is lost and the length is learned again.
+///
+/// In checksum mode the last word of every frame is verified with
+/// [`bus::verify`] and frames with mismatching checksum are dropped.
+pub struct FrameSync {
+    confirmations: u8,
+    checksum: bool,
+    state: State,
+    candidate: usize,
+    matches: u8,
+    locked_len: Option<usize>,
+    started: u64,
+    stats: SyncStats,
+}
+
+impl Default for FrameSync {
+    fn default() -> Self {
+        Self::new(DEFAULT_CONFIRMATIONS)
+    }
+}
+
+impl FrameSync {
+    pub fn new(confirmations: u8) -> Self {
+        Self {
+            confirmations: confirmations.max(1),
+            checksum: false,
+            state: State::WaitForMarker,
+            candidate: 0,
+            matches: 0,
+            locked_len: None,
+            started: 0,
+            stats: SyncStats::default(),
+        }
+    }
+
+    pub fn set_checksum(&mut self, enabled: bool) {
+        self.checksum = enabled;
+    }
+
+    pub fn is_checksum_enabled(&self) -> bool {
+        self.checksum
+    }
+
+    pub fn stats(&self) -> SyncStats {
+        self.stats
+    }
+
+    pub fn reset_stats(&mut self) {
+        self.stats = SyncStats::default();
+    }
+
+    /// Drops the lock, the next frame length is learned from scratch.
+    pub fn reset(&mut self) {
+        self.state = State::WaitForMarker;
+        self.matches = 0;
+        self.locked_len = None;
+    }
+
+    pub fn is_locked(&self) -> bool {
+        matches!(self.state, State::ReadParams(..) | State::ExpectMarker(..))
+    }
+
+    /// Returns length of frames the synchroniser is locked on.
+    pub fn frame_len(&self) -> Option<usize> {
+        if self.is_locked() {
+            self.locked_len
+        } else {
+            None
+        }
+    }
+
+    /// Feeds the next bus word captured at `timestamp`, frames are stamped
+    /// with the timestamp of their first word.
+    pub fn push(&mut self, word: u16, timestamp: u64) -> Option<SyncEvent> {
+        let is_marker = word == START_MARKER;
+        match &mut self.state {
+            State::WaitForMarker => {
+                if is_marker {
+                    self.count_marker();
+                    self.state = State::Measure(0);
+                } else {
+                    self.skip();
+                }
+            }
+            State::Measure(count) => {
+                if is_marker {
+                    let len = *count;
+                    self.count_marker();
+                    return self.measured(len);
+                }
+                *count = count.saturating_add(1);
+                self.skip();
+            }
+            State::ReadParams(len, words) => {
+                if words.is_empty() {
+                    self.started = timestamp;
+                }
+                words.push(word);
+                if words.len() >= *len {
+                    let len = *len;
+                    let words = core::mem::take(words);
+                    self.state = State::ExpectMarker(len, words);
+                }
+            }
+            State::ExpectMarker(len, words) => {
+                if is_marker {
+                    let words = core::mem::take(words);
+                    self.state = State::ReadParams(*len, Words::new());
+                    self.count_marker();
+                    return Some(self.complete(&words));
+                } else {
+                    self.stats.lost_locks = self.stats.lost_locks.wrapping_add(1);
+                    self.matches = 0;
+                    self.state = State::WaitForMarker;
+                    self.skip();
+                }
+            }
+        }
+        None
+    }
+
+    fn measured(&mut self, len: usize) -> Option<SyncEvent> {
+        self.state = State::Measure(0);
+        let checksum_len = self.checksum as usize;
+        if len > MAX_PARAMS_COUNT + checksum_len {
+            self.matches = 0;
+            self.stats.overflows = self.stats.overflows.wrapping_add(1);
+            return Some(SyncEvent::Overflow(len - checksum_len));
+        }
+        if len == 0 {
+            self.matches = 0;
+            return None;
+        }
+
+        if len == self.candidate && self.matches > 0 {
+            self.matches = self.matches.saturating_add(1);
+        } else {
+            self.candidate = len;
+            self.matches = 1;
+        }
+        if self.matches >= self.confirmations {
+            if self.locked_len.is_some_and(|locked_len| locked_len != len) {
+                self.stats.length_changes = self.stats.length_changes.wrapping_add(1);
+            }
+            self.locked_len = Some(len);
+            self.state = State::ReadParams(len, Words::new());
+        }
+        None
+    }
+
+    fn complete(&mut self, words: &[u16]) -> SyncEvent {
+        let params = if self.checksum {
+            match bus::verify(words) {
+                Some(params) => params,
+                None => {
+                    self.stats.corrupted = self.stats.corrupted.wrapping_add(1);
+                    return SyncEvent::Corrupted(self.stats.corrupted);
+                }
+            }
+        } else {
+            words
+        };
+        self.stats.frames = self.stats.frames.wrapping_add(1);
+        SyncEvent::Frame {
+            timestamp: self.started,
+            params: params.iter().copied().collect(),
+        }
+    }
+
+    fn count_marker(&mut self) {
+        self.stats.markers = self.stats.markers.wrapping_add(1);
+    }
+
+    fn skip(&mut self) {
+        self.stats.skipped_words = self.stats.skipped_words.wrapping_add(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::{collection, prelude::*};
+
+    use super::*;
+
+    /// Frames preceded by markers, the last one is closed by a marker too.
+    fn stream(frames: &[&[u16]]) -> Vec<u16> {
+        frames
+            .iter()
+            .flat_map(|frame| core::iter::once(START_MARKER).chain(frame.iter().copied()))
+            .chain(core::iter::once(START_MARKER))
+            .collect()
+    }
+
+    fn push_all(sync: &mut FrameSync, words: &[u16]) -> Vec<SyncEvent> {
+        words
+            .iter()
+            .filter_map(|word| sync.push(*word, 0))
+            .collect()
+    }
+
+    fn frame(words: &[u16]) -> SyncEvent {
+        SyncEvent::Frame {
+            timestamp: 0,
+            params: words.iter().copied().collect(),
+        }
+    }
+
+    #[test]
+    fn lock_after_confirmations() {
+        let mut sync = FrameSync::new(2);
+        let words = stream(&[&[1, 2], &[3, 4], &[5, 6], &[7, 8]]);
+
+        let events = push_all(&mut sync, &words);
+
+        assert_eq!(events, vec![frame(&[5, 6]), frame(&[7, 8])]);
+        assert_eq!(sync.frame_len(), Some(2));
+        assert_eq!(sync.stats().skipped_words, 4);
+        assert_eq!(sync.stats().markers, 5);
+    }
+
+    #[test]
+    fn lock_on_first_frame_with_single_confirmation() {
+        let mut sync = FrameSync::new(1);
+        let words = stream(&[&[1, 2, 3], &[4, 5, 6]]);
+
+        let events = push_all(&mut sync, &words);
+
+        assert_eq!(events, vec![frame(&[4, 5, 6])]);
+    }
+
+    #[test]
+    fn keep_marker_valued_data_words_while_locked() {
+        let mut sync = FrameSync::new(2);
+        let words = stream(&[
+            &[1, 2, 3],
+            &[1, 2, 3],
+            &[START_MARKER, 2, START_MARKER],
+            &[4, 5, 6],
+        ]);
+
+        let events = push_all(&mut sync, &words);
+
+        assert_eq!(
+            events,
+            vec![frame(&[START_MARKER, 2, START_MARKER]), frame(&[4, 5, 6])]
+        );
+        assert_eq!(sync.stats().lost_locks, 0);
+    }
+
+    #[test]
+    fn not_lock_on_marker_collision() {
+        let mut sync = FrameSync::new(2);
+        let words = stream(&[&[1, START_MARKER, 2, 3], &[4, 5, 6, 7]]);
+
+        let events = push_all(&mut sync, &words);
+
+        assert!(events.is_empty());
+        assert!(!sync.is_locked());
+    }
+
+    #[test]
+    fn resync_after_lost_marker() {
+        let mut sync = FrameSync::new(2);
+        let mut words = stream(&[
+            &[1, 2],
+            &[1, 2],
+            &[3, 4],
+            &[5, 6],
+            &[7, 8],
+            &[9, 10],
+            &[11, 12],
+            &[13, 14],
+        ]);
+        words.remove(9);
+
+        let events = push_all(&mut sync, &words);
+
+        // The frame before the lost marker is not confirmed and is dropped.
+        assert_eq!(events, vec![frame(&[11, 12]), frame(&[13, 14])]);
+        assert_eq!(sync.stats().lost_locks, 1);
+        assert_eq!(sync.stats().length_changes, 0);
+    }
+
+    #[test]
+    fn detect_longer_frames() {
+        let mut sync = FrameSync::new(2);
+        let words = stream(&[
+            &[1, 2],
+            &[1, 2],
+            &[3, 4],
+            &[5, 6, 7],
+            &[5, 6, 7],
+            &[8, 9, 10],
+            &[11, 12, 13],
+        ]);
+
+        let events = push_all(&mut sync, &words);
+
+        // The first longer frame lacks the marker after two words and is dropped.
+        assert_eq!(events, vec![frame(&[3, 4]), frame(&[11, 12, 13])]);
+        assert_eq!(sync.frame_len(), Some(3));
+        assert_eq!(sync.stats().lost_locks, 1);
+        assert_eq!(sync.stats().length_changes, 1);
+    }
+
+    #[test]
+    fn detect_shorter_frames() {
+        let mut sync = FrameSync::new(2);
+        let words = stream(&[
+            &[1, 2, 3],
+            &[1, 2, 3],
+            &[4, 5],
+            &[6, 7],
+            &[8, 9],
+            &[10, 11],
+            &[12, 13],
+            &[14, 15],
+        ]);
+
+        let events = push_all(&mut sync, &words);
+
+        // The first shorter frame swallows the following marker and is dropped.
+        assert_eq!(events, vec![frame(&[12, 13]), frame(&[14, 15])]);
+        assert_eq!(sync.frame_len(), Some(2));
+        assert_eq!(sync.stats().lost_locks, 1);
+        assert_eq!(sync.stats().length_changes, 1);
+    }
+
+    #[test]
+    fn report_overflow() {
+        let mut sync = FrameSync::new(2);
+        let long = [1u16; MAX_PARAMS_COUNT + 1];
+        let words = stream(&[&long, &long, &[]]);
+
+        let events = push_all(&mut sync, &words);
+
+        assert_eq!(
+            events,
+            vec![
+                SyncEvent::Overflow(MAX_PARAMS_COUNT + 1),
+                SyncEvent::Overflow(MAX_PARAMS_COUNT + 1)
+            ]
+        );
+        assert_eq!(sync.stats().overflows, 2);
+    }
+
+    #[test]
+    fn ignore_consecutive_markers() {
+        let mut sync = FrameSync::new(1);
+        let words = [START_MARKER, START_MARKER, START_MARKER, 1];
+
+        let events = push_all(&mut sync, &words);
+
+        assert!(events.is_empty());
+        assert!(!sync.is_locked());
+    }
+
+    fn with_checksum(params: &[u16]) -> Vec<u16> {
+        let mut frame = params.to_vec();
+        frame.push(bus::checksum(params));
+        frame
+    }
+
+    #[test]
+    fn strip_verified_checksum() {
+        let mut sync = FrameSync::new(1);
+        sync.set_checksum(true);
+        let frames = [with_checksum(&[1, 2]), with_checksum(&[3, 4])];
+
+        let events = push_all(&mut sync, &stream(&[&frames[0], &frames[1]]));
+
+        assert_eq!(events, vec![frame(&[3, 4])]);
+        assert_eq!(sync.frame_len(), Some(3));
+    }
+
+    #[test]
+    fn drop_corrupted_frames_and_keep_lock() {
+        let mut sync = FrameSync::new(1);
+        sync.set_checksum(true);
+        let mut corrupted = with_checksum(&[3, 4]);
+        corrupted[1] ^= 0x0010;
+        let frames = [with_checksum(&[1, 2]), corrupted, with_checksum(&[5, 6])];
+
+        let events = push_all(&mut sync, &stream(&[&frames[0], &frames[1], &frames[2]]));
+
+        assert_eq!(events, vec![SyncEvent::Corrupted(1), frame(&[5, 6])]);
+        assert_eq!(sync.stats().corrupted, 1);
+        assert_eq!(sync.stats().lost_locks, 0);
+    }
+
+    #[test]
+    fn drop_frame_shifted_by_lost_word() {
+        let mut sync = FrameSync::new(1);
+        sync.set_checksum(true);
+        let frames = [
+            with_checksum(&[1, 2]),
+            with_checksum(&[3, 4]),
+            with_checksum(&[5, 6]),
+        ];
+        let mut words = stream(&[&frames[0], &frames[1], &frames[2]]);
+        words.remove(5);
+
+        let events = push_all(&mut sync, &words);
+
+        assert!(events.is_empty());
+        assert_eq!(sync.stats().lost_locks, 1);
+        assert_eq!(sync.stats().corrupted, 0);
+    }
+
+    #[test]
+    fn allow_checksum_after_max_params() {
+        let mut sync = FrameSync::new(1);
+        sync.set_checksum(true);
+        let params = [7u16; MAX_PARAMS_COUNT];
+        let frames = [with_checksum(&params), with_checksum(&params)];
+
+        let events = push_all(&mut sync, &stream(&[&frames[0], &frames[1]]));
+
+        assert_eq!(events, vec![frame(&params)]);
+    }
+
+    #[test]
+    fn pass_checksum_word_through_without_checksum_mode() {
+        let mut sync = FrameSync::new(1);
+        let frames = [with_checksum(&[1]), with_checksum(&[2])];
+
+        let events = push_all(&mut sync, &stream(&[&frames[0], &frames[1]]));
+
+        assert_eq!(events, vec![frame(&frames[1])]);
+    }
+
+    #[test]
+    fn stamp_frame_with_first_word_time() {
+        let mut sync = FrameSync::new(1);
+        let words = stream(&[&[1, 2], &[3, 4], &[5, 6]]);
+
+        let events = words
+            .iter()
+            .enumerate()
+            .filter_map(|(index, word)| sync.push(*word, 100 * index as u64))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            events,
+            vec![
+                SyncEvent::Frame {
+                    timestamp: 400,
+                    params: [3, 4].into_iter().collect()
+                },
+                SyncEvent::Frame {
+                    timestamp: 700,
+                    params: [5, 6].into_iter().collect()
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn forget_lock_on_reset() {
+        let mut sync = FrameSync::new(1);
+        push_all(&mut sync, &stream(&[&[1], &[2]]));
+
+        sync.reset();
+
+        assert!(!sync.is_locked());
+        assert_eq!(sync.frame_len(), None);
+    }
+
+    proptest! {
+        #[test]
+        fn deliver_every_frame_after_lock(
+            len in 1..=MAX_PARAMS_COUNT,
+            seeds in collection::vec(any::<u16>(), 3..20),
+        ) {
+            let frames = seeds
+                .iter()
+                .map(|seed| (0..len).map(|index| seed.wrapping_add(index as u16)).collect::<Vec<_>>())
+                .collect::<Vec<_>>();
+            prop_assume!(frames.iter().all(|frame| !frame.contains(&START_MARKER)));
+            let slices = frames.iter().map(Vec::as_slice).collect::<Vec<_>>();
+            let mut sync = FrameSync::default();
+
+            let events = push_all(&mut sync, &stream(&slices));
+
+            let expected = frames[DEFAULT_CONFIRMATIONS as usize..]
+                .iter()
+                .map(|words| frame(words))
+                .collect::<Vec<_>>();
+            prop_assert_eq!(events, expected);
+        }
+    }
+}
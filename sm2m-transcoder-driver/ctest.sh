@@ -0,0 +1,5 @@
+#!/bin/sh
+
+cargo build && \
+cc -Wall -Wextra -Werror -std=c99 -I ./include ./ctest/sm2m_test.c -L ./target/debug -lsm2m_transcoder_driver -o ./target/debug/sm2m_test && \
+LD_LIBRARY_PATH=./target/debug ./target/debug/sm2m_test
@@ -0,0 +1,89 @@
+#include <stdio.h>
+#include <string.h>
+
+#include "sm2m.h"
+
+#define FRAMES 50
+#define FPS 100
+#define TIMEOUT_MS 1000
+
+static int failures = 0;
+
+static void check(int condition, const char *description) {
+    if (!condition) {
+        const char *error = sm2m_last_error();
+        fprintf(stderr, "FAILED: %s (%s)\n", description, error ? error : "no error");
+        failures++;
+    }
+}
+
+static void read_counting_frames(void) {
+    Sm2mDecoder *decoder = NULL;
+    check(sm2m_open_virtual_decoder(FPS, &decoder) == SM2M_ERROR_OK, "open virtual decoder");
+    if (decoder == NULL) {
+        return;
+    }
+
+    uint16_t words[32];
+    for (uint16_t frame = 0; frame < FRAMES; frame++) {
+        int32_t count = sm2m_read_frame(decoder, words, 32, TIMEOUT_MS);
+        check(count == 8, "read frame of 8 words");
+        for (int32_t word = 0; word < count; word++) {
+            check(words[word] == (uint16_t)(frame + word), "frame counts up from its number");
+        }
+    }
+
+    sm2m_close(decoder);
+}
+
+static void report_small_buffer(void) {
+    Sm2mDecoder *decoder = NULL;
+    check(sm2m_open_virtual_decoder(0, &decoder) == SM2M_ERROR_OK, "open virtual decoder");
+
+    uint16_t words[4];
+    check(sm2m_read_frame(decoder, words, 4, TIMEOUT_MS) == SM2M_ERROR_BUFFER_TOO_SMALL,
+          "reject buffer smaller than frame");
+    check(sm2m_last_error() != NULL, "describe last error");
+
+    sm2m_close(decoder);
+}
+
+static void report_corrupted_frames(void) {
+    Sm2mDecoder *decoder = NULL;
+    check(sm2m_open_virtual_decoder(0, &decoder) == SM2M_ERROR_OK, "open virtual decoder");
+
+    uint16_t words[32];
+    check(sm2m_set_checksum(decoder, true) == SM2M_ERROR_OK, "enable checksum");
+    check(sm2m_read_frame(decoder, words, 32, TIMEOUT_MS) == SM2M_ERROR_CORRUPTED_FRAME,
+          "drop frame without checksum");
+
+    sm2m_close(decoder);
+}
+
+static void reject_invalid_arguments(void) {
+    uint16_t words[8];
+    check(sm2m_read_frame(NULL, words, 8, TIMEOUT_MS) == SM2M_ERROR_INVALID_ARGUMENT,
+          "reject NULL decoder");
+    check(sm2m_open_virtual_decoder(0, NULL) == SM2M_ERROR_INVALID_ARGUMENT,
+          "reject NULL handle");
+    check(sm2m_list(NULL, 1) == SM2M_ERROR_INVALID_ARGUMENT, "reject NULL device list");
+    check(sm2m_set_checksum(NULL, true) == SM2M_ERROR_INVALID_ARGUMENT, "reject NULL decoder checksum");
+    sm2m_close(NULL);
+}
+
+int main(void) {
+    printf("sm2m driver %s\n", sm2m_version());
+    check(strlen(sm2m_version()) > 0, "return version");
+
+    read_counting_frames();
+    report_small_buffer();
+    report_corrupted_frames();
+    reject_invalid_arguments();
+
+    if (failures > 0) {
+        fprintf(stderr, "%d checks failed\n", failures);
+        return 1;
+    }
+    printf("all checks passed\n");
+    return 0;
+}
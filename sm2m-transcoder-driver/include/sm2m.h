@@ -0,0 +1,136 @@
+#ifndef SM2M_H
+#define SM2M_H
+
+/* Generated by cbindgen from src/ffi.rs, regenerate with `cbindgen --config cbindgen.toml --output include/sm2m.h` */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+enum Sm2mError {
+  SM2M_ERROR_OK = 0,
+  SM2M_ERROR_INVALID_ARGUMENT = -1,
+  SM2M_ERROR_NOT_FOUND = -2,
+  SM2M_ERROR_TIMEOUT = -3,
+  SM2M_ERROR_DISCONNECTED = -4,
+  SM2M_ERROR_STALL = -5,
+  SM2M_ERROR_INCOMPATIBLE_FIRMWARE = -6,
+  SM2M_ERROR_PROTOCOL = -7,
+  SM2M_ERROR_BUFFER_TOO_SMALL = -8,
+  SM2M_ERROR_FRAME_OVERFLOW = -9,
+  SM2M_ERROR_IO = -10,
+  SM2M_ERROR_PANIC = -11,
+  SM2M_ERROR_CORRUPTED_FRAME = -12,
+};
+typedef int32_t Sm2mError;
+
+enum Sm2mRole {
+  SM2M_ROLE_UNKNOWN = 0,
+  SM2M_ROLE_DECODER = 1,
+  SM2M_ROLE_EMULATOR = 2,
+  SM2M_ROLE_ENCODER = 3,
+};
+typedef int32_t Sm2mRole;
+
+/**
+ * Opened decoder, owned by the caller until `sm2m_close`.
+ */
+typedef struct Sm2mDecoder Sm2mDecoder;
+
+/**
+ * Attached device, strings are NUL terminated and truncated to fit.
+ */
+typedef struct Sm2mDeviceInfo {
+  Sm2mRole role;
+  char serial_number[64];
+  char product[64];
+  uint8_t bus;
+  /**
+   * `false` when firmware protocol revision does not match the driver.
+   */
+  bool compatible;
+} Sm2mDeviceInfo;
+
+/**
+ * Returns driver version as a static NUL terminated string.
+ */
+const char *sm2m_version(void);
+
+/**
+ * Returns message of the last failure on the calling thread, `NULL` when
+ * nothing failed yet. The message stays valid until the next failure.
+ */
+const char *sm2m_last_error(void);
+
+/**
+ * Fills up to `capacity` entries of `devices` and returns number of attached
+ * devices, which may exceed `capacity`.
+ *
+ * # Safety
+ *
+ * `devices` must point to `capacity` writable entries or be `NULL` when
+ * `capacity` is zero.
+ */
+int32_t sm2m_list(struct Sm2mDeviceInfo *devices, size_t capacity);
+
+/**
+ * Opens decoder with `serial_number`, or the first attached decoder when it
+ * is `NULL`, and stores its handle into `decoder`.
+ *
+ * # Safety
+ *
+ * `serial_number` must be `NULL` or a NUL terminated string, `decoder` must
+ * point to writable handle.
+ */
+int32_t sm2m_open_decoder(const char *serial_number, Sm2mDecoder **decoder);
+
+/**
+ * Opens software decoder streaming `fps` frames per second, as fast as they
+ * are read when zero. Frame `n` holds 8 words counting up from `n`, so
+ * applications can be tested without hardware.
+ *
+ * # Safety
+ *
+ * `decoder` must point to writable handle.
+ */
+int32_t sm2m_open_virtual_decoder(uint32_t fps, Sm2mDecoder **decoder);
+
+/**
+ * Makes decoder verify and strip checksum word trailing every frame, the
+ * emulator has to produce it. Keep it disabled with a real SM2M computer.
+ *
+ * # Safety
+ *
+ * `decoder` must be a handle returned by one of the open functions and not
+ * closed yet.
+ */
+int32_t sm2m_set_checksum(Sm2mDecoder *decoder, bool enabled);
+
+/**
+ * Waits up to `timeout_ms` for the next parameters frame, copies its words
+ * into `words` and returns their count.
+ *
+ * A frame longer than `capacity` is dropped with `SM2M_ERROR_BUFFER_TOO_SMALL`,
+ * a frame the decoder could not hold is reported as `SM2M_ERROR_FRAME_OVERFLOW`
+ * and a frame dropped because of checksum mismatch as `SM2M_ERROR_CORRUPTED_FRAME`.
+ *
+ * # Safety
+ *
+ * `decoder` must be a handle returned by one of the open functions and not
+ * closed yet, `words` must point to `capacity` writable words.
+ */
+int32_t sm2m_read_frame(Sm2mDecoder *decoder, uint16_t *words, size_t capacity, uint32_t timeout_ms);
+
+/**
+ * Closes decoder and frees its handle, `NULL` is ignored.
+ *
+ * # Safety
+ *
+ * `decoder` must be a handle returned by one of the open functions and is
+ * invalid after the call.
+ */
+void sm2m_close(Sm2mDecoder *decoder);
+
+#endif /* SM2M_H */
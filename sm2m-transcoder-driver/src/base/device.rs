@@ -1,8 +1,8 @@
 use std::time;
 
-use crate::error::DriverError;
+use crate::{error::DriverError, hotplug::DeviceLocation};
 
-use super::{device_lookup::DeviceLookup, endpoint_lookup::EndpointLookup};
+use super::{device_lookup::DeviceLookup, endpoint_lookup::EndpointLookup, transport::Transport};
 
 pub struct Device<T: rusb::UsbContext> {
     device: DeviceLookup<T>,
@@ -10,14 +10,12 @@ pub struct Device<T: rusb::UsbContext> {
     writeable_endpoint: EndpointLookup,
 }
 
+/// Closing fails when the device was unplugged, there is nothing left to
+/// release then, so errors are ignored instead of panicking in the host application.
 impl<T: rusb::UsbContext> Drop for Device<T> {
     fn drop(&mut self) {
-        self.readable_endpoint
-            .close(&mut self.device.handle)
-            .expect("Unable to close read endpoint");
-        self.writeable_endpoint
-            .close(&mut self.device.handle)
-            .expect("Unable to close write endpoint");
+        self.readable_endpoint.close(&mut self.device.handle).ok();
+        self.writeable_endpoint.close(&mut self.device.handle).ok();
     }
 }
 
@@ -37,7 +35,23 @@ impl<T: rusb::UsbContext> Device<T> {
         })
     }
 
-    pub fn reset(&mut self) -> Result<(), DriverError> {
+    pub fn location(&self) -> DeviceLocation {
+        DeviceLocation::from(&self.device.device)
+    }
+}
+
+impl<T: rusb::UsbContext> Transport for Device<T> {
+    fn read(&mut self, buf: &mut [u8], timeout: time::Duration) -> Result<usize, DriverError> {
+        self.readable_endpoint
+            .read(&self.device.handle, buf, timeout)
+    }
+
+    fn write(&mut self, buf: &[u8], timeout: time::Duration) -> Result<usize, DriverError> {
+        self.writeable_endpoint
+            .write(&self.device.handle, buf, timeout)
+    }
+
+    fn reset(&mut self) -> Result<(), DriverError> {
         self.device.handle.reset().map_err(|error| {
             DriverError::Reset(
                 error,
@@ -47,52 +61,8 @@ impl<T: rusb::UsbContext> Device<T> {
         })
     }
 
-    pub fn write(&mut self, buf: &[u8], timeout: time::Duration) -> Result<usize, DriverError> {
-        self.writeable_endpoint
-            .write(&self.device.handle, buf, timeout)
-    }
-
-    pub fn write_all(&mut self, buf: &[u8]) -> Result<usize, DriverError> {
-        self.try_write_all(buf, time::Duration::MAX, usize::MAX)
-    }
-
-    pub fn try_write_all(
-        &mut self,
-        buf: &[u8],
-        timeout: time::Duration,
-        mut retries: usize,
-    ) -> Result<usize, DriverError> {
-        let bytes_total = buf.len();
-        let mut bytes_written = self.write(buf, timeout)?;
-        while bytes_written < bytes_total && retries > 0 {
-            bytes_written += self.write(&buf[bytes_written..], timeout)?;
-            retries -= 1;
-        }
-        Ok(bytes_written)
-    }
-
-    pub fn read(&mut self, buf: &mut [u8], timeout: time::Duration) -> Result<usize, DriverError> {
-        self.readable_endpoint
-            .read(&self.device.handle, buf, timeout)
-    }
-
-    pub fn read_all(&mut self, buf: &mut [u8]) -> Result<usize, DriverError> {
-        self.try_read_all(buf, time::Duration::MAX, usize::MAX)
-    }
-
-    pub fn try_read_all(
-        &mut self,
-        buf: &mut [u8],
-        timeout: time::Duration,
-        mut retries: usize,
-    ) -> Result<usize, DriverError> {
-        let bytes_total = buf.len();
-        let mut bytes_read = self.read(buf, timeout)?;
-        while bytes_read < bytes_total && retries > 0 {
-            println!("123");
-            bytes_read += self.read(&mut buf[bytes_read..], timeout)?;
-            retries -= 1;
-        }
-        Ok(bytes_read)
+    fn clear_halt(&mut self) -> Result<(), DriverError> {
+        self.readable_endpoint.clear_halt(&mut self.device.handle)?;
+        self.writeable_endpoint.clear_halt(&mut self.device.handle)
     }
 }
@@ -6,6 +6,8 @@ pub struct DeviceLookup<T: rusb::UsbContext> {
     pub device: rusb::Device<T>,
     pub handle: rusb::DeviceHandle<T>,
     pub descriptor: rusb::DeviceDescriptor,
+    pub product: String,
+    pub serial_number: String,
 }
 
 impl<T: rusb::UsbContext> DeviceLookup<T> {
@@ -13,14 +15,26 @@ impl<T: rusb::UsbContext> DeviceLookup<T> {
         device: rusb::Device<T>,
         handle: rusb::DeviceHandle<T>,
         descriptor: rusb::DeviceDescriptor,
+        product: String,
+        serial_number: String,
     ) -> Self {
         Self {
             device,
             handle,
             descriptor,
+            product,
+            serial_number,
         }
     }
 
+    pub fn bus_number(&self) -> u8 {
+        self.device.bus_number()
+    }
+
+    pub fn port_numbers(&self) -> Vec<u8> {
+        self.device.port_numbers().unwrap_or_default()
+    }
+
     pub fn find_readable_endpoint(&mut self) -> Result<EndpointLookup, DriverError> {
         self.find_readable_typed_endpoint(rusb::TransferType::Bulk)
             .or_else(|| self.find_readable_typed_endpoint(rusb::TransferType::Interrupt))
@@ -2,28 +2,104 @@ use std::time;
 
 use crate::error::DriverError;
 
+use crate::hotplug::DeviceLocation;
+
 use super::device_lookup::DeviceLookup;
 
-const VID: u16 = 1155;
-const PID: u16 = 22336;
+pub const VID: u16 = 1155;
+pub const PID: u16 = 22336;
 
-pub fn find_device<T: rusb::UsbContext>(
+/// Opens every SM2M device on the bus. Devices which can't be opened or
+/// described are logged and skipped, so they don't hide the other ones.
+pub fn list_devices<T: rusb::UsbContext>(
     context: &mut T,
-    serial_number: &str,
     timeout: time::Duration,
-) -> Result<Option<DeviceLookup<T>>, DriverError> {
+) -> Result<Vec<DeviceLookup<T>>, DriverError> {
     let devices = context.devices().map_err(DriverError::DeviceList)?;
+    let mut lookups = Vec::new();
     for device in devices.iter() {
-        let descriptor = read_device_descriptor(&device)?;
+        let descriptor = match read_device_descriptor(&device) {
+            Ok(descriptor) => descriptor,
+            Err(error) => {
+                warn!(
+                    "skipped device on bus {} address {}: {}",
+                    device.bus_number(),
+                    device.address(),
+                    error
+                );
+                continue;
+            }
+        };
         if is_expected_device(&descriptor) {
-            let handle = open_device(&device, &descriptor)?;
-            if has_serial_number(&handle, &descriptor, serial_number, timeout)? {
-                return Ok(Some(DeviceLookup::new(device, handle, descriptor)));
+            match lookup_device(&device, &descriptor, timeout) {
+                Ok((handle, product, serial_number)) => {
+                    debug!(
+                        "accepted {:04x}:{:04x} on bus {} address {}, product {:?}, serial {:?}",
+                        descriptor.vendor_id(),
+                        descriptor.product_id(),
+                        device.bus_number(),
+                        device.address(),
+                        product,
+                        serial_number
+                    );
+                    lookups.push(DeviceLookup::new(
+                        device,
+                        handle,
+                        descriptor,
+                        product,
+                        serial_number,
+                    ));
+                }
+                Err(error) => warn!(
+                    "skipped {:04x}:{:04x} on bus {} address {}: {}",
+                    descriptor.vendor_id(),
+                    descriptor.product_id(),
+                    device.bus_number(),
+                    device.address(),
+                    error
+                ),
             }
+        } else {
+            debug!(
+                "rejected {:04x}:{:04x} on bus {} address {}, expected {:04x}:{:04x}",
+                descriptor.vendor_id(),
+                descriptor.product_id(),
+                device.bus_number(),
+                device.address(),
+                VID,
+                PID
+            );
         }
     }
 
-    Ok(None)
+    Ok(lookups)
+}
+
+pub fn list_locations<T: rusb::UsbContext>(
+    context: &mut T,
+) -> Result<Vec<DeviceLocation>, DriverError> {
+    let devices = context.devices().map_err(DriverError::DeviceList)?;
+    let mut locations = Vec::new();
+    for device in devices.iter() {
+        let Ok(descriptor) = read_device_descriptor(&device) else {
+            continue;
+        };
+        if is_expected_device(&descriptor) {
+            locations.push(DeviceLocation::from(&device));
+        }
+    }
+
+    Ok(locations)
+}
+
+fn lookup_device<T: rusb::UsbContext>(
+    device: &rusb::Device<T>,
+    descriptor: &rusb::DeviceDescriptor,
+    timeout: time::Duration,
+) -> Result<(rusb::DeviceHandle<T>, String, String), DriverError> {
+    let handle = open_device(device, descriptor)?;
+    let (product, serial_number) = read_strings(&handle, descriptor, timeout)?;
+    Ok((handle, product, serial_number))
 }
 
 fn read_device_descriptor<T: rusb::UsbContext>(
@@ -47,21 +123,34 @@ fn open_device<T: rusb::UsbContext>(
     })
 }
 
-fn has_serial_number<T: rusb::UsbContext>(
+fn read_strings<T: rusb::UsbContext>(
     handle: &rusb::DeviceHandle<T>,
     descriptor: &rusb::DeviceDescriptor,
-    serial_number: &str,
     timeout: time::Duration,
-) -> Result<bool, DriverError> {
+) -> Result<(String, String), DriverError> {
     let language = read_first_language(handle, descriptor, timeout)?;
     if let Some(language) = language {
-        let device_serial = read_serial_number_string(handle, descriptor, language, timeout)?;
-        Ok(device_serial.eq(serial_number))
+        let product = read_product_string(handle, descriptor, language, timeout)?;
+        let serial_number = read_serial_number_string(handle, descriptor, language, timeout)?;
+        Ok((product, serial_number))
     } else {
-        Ok(false)
+        Ok((String::new(), String::new()))
     }
 }
 
+fn read_product_string<T: rusb::UsbContext>(
+    handle: &rusb::DeviceHandle<T>,
+    descriptor: &rusb::DeviceDescriptor,
+    language: rusb::Language,
+    timeout: time::Duration,
+) -> Result<String, DriverError> {
+    handle
+        .read_product_string(language, descriptor, timeout)
+        .map_err(|error| {
+            DriverError::ProductString(error, descriptor.vendor_id(), descriptor.product_id())
+        })
+}
+
 fn read_serial_number_string<T: rusb::UsbContext>(
     handle: &rusb::DeviceHandle<T>,
     descriptor: &rusb::DeviceDescriptor,
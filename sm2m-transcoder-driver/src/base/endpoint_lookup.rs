@@ -1,6 +1,6 @@
 use std::time;
 
-use crate::error::DriverError;
+use crate::{error::DriverError, logging};
 
 pub struct EndpointLookup {
     config: u8,
@@ -35,7 +35,7 @@ impl EndpointLookup {
         buf: &mut [u8],
         timeout: time::Duration,
     ) -> Result<usize, DriverError> {
-        match self.transfer_type {
+        let result = match self.transfer_type {
             rusb::TransferType::Bulk => handle
                 .read_bulk(self.address, buf, timeout)
                 .map_err(|error| DriverError::Read(error, self.address)),
@@ -46,7 +46,14 @@ impl EndpointLookup {
                 self.transfer_type,
                 self.address,
             )),
-        }
+        };
+        logging::transfer(
+            "read from",
+            format_args!("endpoint 0x{:02x}", self.address),
+            buf,
+            &result,
+        );
+        result
     }
 
     pub fn write<T: rusb::UsbContext>(
@@ -55,7 +62,7 @@ impl EndpointLookup {
         buf: &[u8],
         timeout: time::Duration,
     ) -> Result<usize, DriverError> {
-        match self.transfer_type {
+        let result = match self.transfer_type {
             rusb::TransferType::Bulk => handle
                 .write_bulk(self.address, buf, timeout)
                 .map_err(|error| DriverError::Write(error, self.address)),
@@ -66,7 +73,14 @@ impl EndpointLookup {
                 self.transfer_type,
                 self.address,
             )),
-        }
+        };
+        logging::transfer(
+            "wrote to",
+            format_args!("endpoint 0x{:02x}", self.address),
+            buf,
+            &result,
+        );
+        result
     }
 
     pub fn open<T: rusb::UsbContext>(
@@ -95,6 +109,15 @@ impl EndpointLookup {
         Ok(())
     }
 
+    pub fn clear_halt<T: rusb::UsbContext>(
+        &self,
+        handle: &mut rusb::DeviceHandle<T>,
+    ) -> Result<(), DriverError> {
+        handle
+            .clear_halt(self.address)
+            .map_err(|error| DriverError::ClearHalt(error, self.address))
+    }
+
     fn is_kernel_driver_active<T: rusb::UsbContext>(
         &self,
         handle: &rusb::DeviceHandle<T>,
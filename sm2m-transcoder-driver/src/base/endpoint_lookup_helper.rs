@@ -33,6 +33,15 @@ fn find_endpoint<T: rusb::UsbContext>(
                 for interface_descriptor in interface.descriptors() {
                     for endpoint_descriptor in interface_descriptor.endpoint_descriptors() {
                         if has_direction(&endpoint_descriptor, transfer_type, &direction) {
+                            debug!(
+                                "selected {:?} {:?} endpoint 0x{:02x} of configuration {} interface {} setting {}",
+                                endpoint_descriptor.direction(),
+                                transfer_type,
+                                endpoint_descriptor.address(),
+                                config_descriptor.number(),
+                                interface_descriptor.interface_number(),
+                                interface_descriptor.setting_number()
+                            );
                             return Some(EndpointLookup::new(
                                 config_descriptor.number(),
                                 interface_descriptor.interface_number(),
@@ -41,12 +50,25 @@ fn find_endpoint<T: rusb::UsbContext>(
                                 transfer_type,
                             ));
                         }
+                        trace!(
+                            "skipped {:?} {:?} endpoint 0x{:02x} of interface {}",
+                            endpoint_descriptor.direction(),
+                            endpoint_descriptor.transfer_type(),
+                            endpoint_descriptor.address(),
+                            interface_descriptor.interface_number()
+                        );
                     }
                 }
             }
         }
     }
 
+    debug!(
+        "no {:?} endpoint found in {:04x}:{:04x}",
+        transfer_type,
+        descriptor.vendor_id(),
+        descriptor.product_id()
+    );
     None
 }
 
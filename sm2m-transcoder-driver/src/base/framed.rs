@@ -0,0 +1,276 @@
+use std::{collections::VecDeque, fmt, time};
+
+use sm2m_protocol::{
+    deframer::{Deframer, FrameStats},
+    frame::{Frame, FrameWriter, MAX_FRAME_SIZE},
+    packet::{Packet, MAX_PACKET_SIZE},
+};
+
+use crate::error::DriverError;
+
+use super::transport::Transport;
+
+const READ_CHUNK_SIZE: usize = 64;
+const DEFAULT_READ_TIMEOUT: time::Duration = time::Duration::from_secs(1);
+/// libusb timeouts have millisecond resolution and zero means no timeout.
+const MIN_READ_TIMEOUT: time::Duration = time::Duration::from_millis(1);
+
+/// Frames every outgoing payload and reassembles incoming frames from a byte
+/// stream, regardless of how the transport splits or merges USB transfers.
+pub struct Framed<T: Transport> {
+    transport: T,
+    writer: FrameWriter,
+    deframer: Deframer,
+    frames: VecDeque<Frame>,
+    read_timeout: time::Duration,
+}
+
+impl<T: Transport> Framed<T> {
+    pub fn new(transport: T) -> Self {
+        Self {
+            transport,
+            writer: FrameWriter::new(),
+            deframer: Deframer::new(),
+            frames: VecDeque::new(),
+            read_timeout: DEFAULT_READ_TIMEOUT,
+        }
+    }
+
+    pub fn get_ref(&self) -> &T {
+        &self.transport
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.transport
+    }
+
+    pub fn into_inner(self) -> T {
+        self.transport
+    }
+
+    pub fn stats(&self) -> FrameStats {
+        self.deframer.stats()
+    }
+
+    /// Time device protocols wait for a single packet, one second by default.
+    pub fn read_timeout(&self) -> time::Duration {
+        self.read_timeout
+    }
+
+    pub fn set_read_timeout(&mut self, timeout: time::Duration) {
+        self.read_timeout = timeout;
+    }
+
+    /// Sends `payload` as a single frame and returns the payload size.
+    pub fn write_frame(&mut self, payload: &[u8]) -> Result<usize, DriverError> {
+        let mut buf = [0u8; MAX_FRAME_SIZE];
+        let size = self
+            .writer
+            .encode_into(payload, &mut buf)
+            .map_err(DriverError::EncodeFrame)?;
+        self.transport.write_all(&buf[..size])?;
+        if let Some(stats) = self.transport.stats() {
+            stats.record_packet_written();
+        }
+        Ok(payload.len())
+    }
+
+    pub fn read_frame(&mut self, timeout: time::Duration) -> Result<Frame, DriverError> {
+        let deadline = time::Instant::now().checked_add(timeout);
+        loop {
+            if let Some(frame) = self.frames.pop_front() {
+                if let Some(stats) = self.transport.stats() {
+                    stats.record_packet_read();
+                }
+                return Ok(frame);
+            }
+
+            let remaining = match deadline {
+                Some(deadline) => deadline.saturating_duration_since(time::Instant::now()),
+                None => timeout,
+            };
+            if remaining.is_zero() {
+                return Err(DriverError::FrameTimeout(timeout));
+            }
+
+            let mut buf = [0u8; READ_CHUNK_SIZE];
+            let size = self
+                .transport
+                .read(&mut buf, remaining.max(MIN_READ_TIMEOUT))?;
+            for byte in &buf[..size] {
+                if let Some(frame) = self.deframer.push(*byte) {
+                    self.frames.push_back(frame);
+                    while let Some(frame) = self.deframer.pop() {
+                        self.frames.push_back(frame);
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn write_packet<P: Packet + fmt::Debug>(
+        &mut self,
+        packet: &P,
+    ) -> Result<usize, DriverError> {
+        debug!("sending {:?}", packet);
+        let mut buf = [0u8; MAX_PACKET_SIZE];
+        let size = packet
+            .encode_into(&mut buf)
+            .map_err(DriverError::EncodePacket)?;
+        self.write_frame(&buf[..size])
+    }
+
+    pub fn read_packet<P: Packet + fmt::Debug>(
+        &mut self,
+        timeout: time::Duration,
+    ) -> Result<P, DriverError> {
+        let frame = self.read_frame(timeout)?;
+        let packet = P::decode(frame.payload()).map_err(DriverError::DecodePacket);
+        match &packet {
+            Ok(packet) => debug!("received {:?}", packet),
+            Err(error) => debug!("received undecodable frame: {}", error),
+        }
+        packet
+    }
+
+    /// Resets the transport and drops every partially received or queued frame.
+    pub fn reset(&mut self) -> Result<(), DriverError> {
+        self.frames.clear();
+        self.deframer.reset();
+        self.transport.reset()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::base::mock::{self, MockTransport};
+
+    use super::*;
+
+    const READ_TIMEOUT: time::Duration = time::Duration::from_millis(100);
+
+    #[test]
+    fn write_payload_as_frame_with_increasing_sequence() {
+        let transport = MockTransport::new();
+        let mut framed = Framed::new(transport.clone());
+
+        framed.write_frame(&[1]).unwrap();
+        framed.write_frame(&[4, 20]).unwrap();
+
+        assert_eq!(
+            transport.written(),
+            vec![mock::frame(0, &[1]), mock::frame(1, &[4, 20])]
+        );
+    }
+
+    #[test]
+    fn reject_oversized_payload() {
+        let mut framed = Framed::new(MockTransport::new());
+
+        let result = framed.write_frame(&[0; 200]);
+
+        assert!(matches!(result, Err(DriverError::EncodeFrame(_))));
+    }
+
+    #[test]
+    fn read_frame_split_across_transfers() {
+        let transport = MockTransport::new();
+        let bytes = mock::frame(0, &[1, 1, 2, 3]);
+        transport.push_read(&bytes[..2]).push_read(&bytes[2..]);
+        let mut framed = Framed::new(transport);
+
+        let frame = framed.read_frame(READ_TIMEOUT).unwrap();
+
+        assert_eq!(frame.payload(), &[1, 1, 2, 3]);
+    }
+
+    #[test]
+    fn read_two_frames_from_one_transfer() {
+        let transport = MockTransport::new();
+        let mut bytes = mock::frame(0, &[1]);
+        bytes.extend(mock::frame(1, &[2]));
+        transport.push_read(&bytes);
+        let mut framed = Framed::new(transport.clone());
+
+        assert_eq!(framed.read_frame(READ_TIMEOUT).unwrap().payload(), &[1]);
+        assert_eq!(framed.read_frame(READ_TIMEOUT).unwrap().payload(), &[2]);
+        assert_eq!(transport.pending_reads(), 0);
+    }
+
+    #[test]
+    fn skip_corrupted_frame() {
+        let transport = MockTransport::new();
+        let mut corrupted = mock::frame(0, &[1, 2]);
+        corrupted[3] ^= 0xff;
+        transport
+            .push_read(&corrupted)
+            .push_read(&mock::frame(2, &[3]));
+        let mut framed = Framed::new(transport);
+
+        let frame = framed.read_frame(READ_TIMEOUT).unwrap();
+
+        assert_eq!(frame.payload(), &[3]);
+        assert_eq!(framed.stats().crc_errors, 1);
+    }
+
+    #[test]
+    fn time_out_without_complete_frame() {
+        let transport = MockTransport::new();
+        transport.push_read(&mock::frame(0, &[1])[..3]);
+        let mut framed = Framed::new(transport);
+
+        let result = framed.read_frame(READ_TIMEOUT);
+
+        assert!(matches!(
+            result,
+            Err(DriverError::Read(rusb::Error::Timeout, _))
+        ));
+    }
+
+    #[test]
+    fn round_sub_millisecond_timeout_up() {
+        struct Timeouts(Vec<time::Duration>);
+
+        impl Transport for Timeouts {
+            fn read(
+                &mut self,
+                _: &mut [u8],
+                timeout: time::Duration,
+            ) -> Result<usize, DriverError> {
+                self.0.push(timeout);
+                Ok(0)
+            }
+
+            fn write(&mut self, buf: &[u8], _: time::Duration) -> Result<usize, DriverError> {
+                Ok(buf.len())
+            }
+
+            fn reset(&mut self) -> Result<(), DriverError> {
+                Ok(())
+            }
+        }
+        let mut framed = Framed::new(Timeouts(Vec::new()));
+
+        let result = framed.read_frame(time::Duration::from_micros(500));
+
+        assert!(matches!(result, Err(DriverError::FrameTimeout(_))));
+        let timeouts = framed.into_inner().0;
+        assert!(!timeouts.is_empty());
+        assert!(timeouts.iter().all(|timeout| *timeout >= MIN_READ_TIMEOUT));
+    }
+
+    #[test]
+    fn drop_queued_frames_on_reset() {
+        let transport = MockTransport::new();
+        let mut bytes = mock::frame(0, &[1]);
+        bytes.extend(mock::frame(1, &[2]));
+        transport.push_read(&bytes);
+        let mut framed = Framed::new(transport.clone());
+
+        framed.read_frame(READ_TIMEOUT).unwrap();
+        framed.reset().unwrap();
+
+        assert!(framed.read_frame(READ_TIMEOUT).is_err());
+        assert_eq!(transport.resets(), 1);
+    }
+}
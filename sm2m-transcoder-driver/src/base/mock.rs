@@ -0,0 +1,260 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex, MutexGuard},
+    time,
+};
+
+use sm2m_protocol::frame::{self, MAX_FRAME_SIZE};
+
+use crate::{
+    error::DriverError,
+    hotplug::{DeviceEnumerator, DeviceLocation},
+};
+
+use super::transport::Transport;
+
+const MOCK_READ_ADDRESS: u8 = 0x81;
+const MOCK_WRITE_ADDRESS: u8 = 0x01;
+
+/// Encodes `payload` the way the SM2M firmware frames it on the wire.
+pub fn frame(seq: u8, payload: &[u8]) -> Vec<u8> {
+    let mut buf = [0u8; MAX_FRAME_SIZE];
+    let size = frame::encode_into(seq, payload, &mut buf).expect("Mock frame payload is too large");
+    buf[..size].to_vec()
+}
+
+/// In-memory transport which replays scripted reads and records every write.
+///
+/// Clones share the same script, so a test can keep one handle for inspection
+/// while another one is owned by the device under test.
+#[derive(Clone, Default)]
+pub struct MockTransport {
+    state: Arc<Mutex<MockState>>,
+}
+
+#[derive(Default)]
+struct MockState {
+    reads: VecDeque<Result<Vec<u8>, rusb::Error>>,
+    writes: Vec<Vec<u8>>,
+    responders: Vec<(Vec<u8>, Vec<u8>)>,
+    write_error: Option<rusb::Error>,
+    reset_error: Option<rusb::Error>,
+    resets: usize,
+    halts_cleared: usize,
+}
+
+impl MockTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push_read(&self, buf: &[u8]) -> &Self {
+        self.state().reads.push_back(Ok(buf.to_vec()));
+        self
+    }
+
+    pub fn push_read_error(&self, error: rusb::Error) -> &Self {
+        self.state().reads.push_back(Err(error));
+        self
+    }
+
+    /// Queues `response` for reading every time exactly `request` is written.
+    pub fn respond(&self, request: &[u8], response: &[u8]) -> &Self {
+        self.state()
+            .responders
+            .push((request.to_vec(), response.to_vec()));
+        self
+    }
+
+    pub fn fail_writes(&self, error: Option<rusb::Error>) -> &Self {
+        self.state().write_error = error;
+        self
+    }
+
+    pub fn fail_resets(&self, error: Option<rusb::Error>) -> &Self {
+        self.state().reset_error = error;
+        self
+    }
+
+    pub fn written(&self) -> Vec<Vec<u8>> {
+        self.state().writes.clone()
+    }
+
+    pub fn take_written(&self) -> Vec<Vec<u8>> {
+        std::mem::take(&mut self.state().writes)
+    }
+
+    pub fn pending_reads(&self) -> usize {
+        self.state().reads.len()
+    }
+
+    pub fn resets(&self) -> usize {
+        self.state().resets
+    }
+
+    pub fn halts_cleared(&self) -> usize {
+        self.state().halts_cleared
+    }
+
+    fn state(&self) -> MutexGuard<'_, MockState> {
+        self.state.lock().expect("Mock transport state is poisoned")
+    }
+}
+
+impl Transport for MockTransport {
+    fn read(&mut self, buf: &mut [u8], _timeout: time::Duration) -> Result<usize, DriverError> {
+        let mut state = self.state();
+        match state.reads.pop_front() {
+            Some(Ok(mut chunk)) => {
+                let size = chunk.len().min(buf.len());
+                buf[..size].copy_from_slice(&chunk[..size]);
+                if size < chunk.len() {
+                    state.reads.push_front(Ok(chunk.split_off(size)));
+                }
+                Ok(size)
+            }
+            Some(Err(error)) => Err(DriverError::Read(error, MOCK_READ_ADDRESS)),
+            None => Err(DriverError::Read(rusb::Error::Timeout, MOCK_READ_ADDRESS)),
+        }
+    }
+
+    fn write(&mut self, buf: &[u8], _timeout: time::Duration) -> Result<usize, DriverError> {
+        let mut state = self.state();
+        if let Some(error) = state.write_error {
+            return Err(DriverError::Write(error, MOCK_WRITE_ADDRESS));
+        }
+
+        state.writes.push(buf.to_vec());
+        let responses = state
+            .responders
+            .iter()
+            .filter(|(request, _)| request == buf)
+            .map(|(_, response)| Ok(response.clone()))
+            .collect::<Vec<_>>();
+        state.reads.extend(responses);
+        Ok(buf.len())
+    }
+
+    fn reset(&mut self) -> Result<(), DriverError> {
+        let mut state = self.state();
+        state.resets += 1;
+        match state.reset_error {
+            Some(error) => Err(DriverError::Reset(error, 0, 0)),
+            None => Ok(()),
+        }
+    }
+
+    fn clear_halt(&mut self) -> Result<(), DriverError> {
+        self.state().halts_cleared += 1;
+        Ok(())
+    }
+}
+
+/// In-memory USB bus for hotplug tests, devices are attached and detached by hand.
+#[derive(Clone, Default)]
+pub struct MockBus {
+    devices: Arc<Mutex<Vec<DeviceLocation>>>,
+}
+
+impl MockBus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn attach(&self, location: DeviceLocation) {
+        self.devices().push(location);
+    }
+
+    pub fn detach(&self, location: &DeviceLocation) {
+        self.devices().retain(|device| device != location);
+    }
+
+    fn devices(&self) -> MutexGuard<'_, Vec<DeviceLocation>> {
+        self.devices.lock().expect("Mock bus state is poisoned")
+    }
+}
+
+impl DeviceEnumerator for MockBus {
+    fn enumerate(&mut self) -> Result<Vec<DeviceLocation>, DriverError> {
+        Ok(self.devices().clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IO_TIMEOUT: time::Duration = time::Duration::from_millis(10);
+
+    #[test]
+    fn read_scripted_chunks_in_order() {
+        let mut transport = MockTransport::new();
+        transport.push_read(&[1, 2]).push_read(&[3]);
+        let mut buf = [0u8; 64];
+
+        assert_eq!(transport.read(&mut buf, IO_TIMEOUT).unwrap(), 2);
+        assert_eq!(&buf[..2], &[1, 2]);
+        assert_eq!(transport.read(&mut buf, IO_TIMEOUT).unwrap(), 1);
+        assert_eq!(buf[0], 3);
+    }
+
+    #[test]
+    fn keep_chunk_remainder_for_next_read() {
+        let mut transport = MockTransport::new();
+        transport.push_read(&[1, 2, 3]);
+        let mut buf = [0u8; 2];
+
+        assert_eq!(transport.read(&mut buf, IO_TIMEOUT).unwrap(), 2);
+        assert_eq!(transport.read(&mut buf, IO_TIMEOUT).unwrap(), 1);
+        assert_eq!(buf[0], 3);
+    }
+
+    #[test]
+    fn time_out_when_script_is_empty() {
+        let mut transport = MockTransport::new();
+        let mut buf = [0u8; 64];
+
+        let error = transport.read(&mut buf, IO_TIMEOUT).unwrap_err();
+
+        assert!(matches!(error, DriverError::Read(rusb::Error::Timeout, _)));
+    }
+
+    #[test]
+    fn answer_matching_request() {
+        let mut transport = MockTransport::new();
+        transport.respond(&[1], &[1, 2, 3, 4]);
+        let mut buf = [0u8; 64];
+
+        transport.write_all(&[2]).unwrap();
+        assert_eq!(transport.pending_reads(), 0);
+        transport.write_all(&[1]).unwrap();
+        assert_eq!(transport.read(&mut buf, IO_TIMEOUT).unwrap(), 4);
+        assert_eq!(transport.take_written(), vec![vec![2], vec![1]]);
+        assert!(transport.written().is_empty());
+    }
+
+    #[test]
+    fn share_script_between_clones() {
+        let transport = MockTransport::new();
+        let mut device = transport.clone();
+
+        device.write_all(&[5]).unwrap();
+        device.reset().unwrap();
+
+        assert_eq!(transport.written(), vec![vec![5]]);
+        assert_eq!(transport.resets(), 1);
+    }
+
+    #[test]
+    fn fail_writes_on_demand() {
+        let mut transport = MockTransport::new();
+        transport.fail_writes(Some(rusb::Error::NoDevice));
+
+        let error = transport.write_all(&[1]).unwrap_err();
+
+        assert!(matches!(
+            error,
+            DriverError::Write(rusb::Error::NoDevice, _)
+        ));
+    }
+}
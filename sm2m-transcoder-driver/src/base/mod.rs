@@ -1,4 +1,9 @@
 pub mod device;
+pub mod framed;
+pub mod mock;
+pub mod reconnect;
+pub mod request;
+pub mod transport;
 
 pub(crate) mod device_lookup;
 pub(crate) mod device_lookup_helper;
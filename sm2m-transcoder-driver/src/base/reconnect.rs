@@ -0,0 +1,430 @@
+use std::{sync::mpsc, thread, time};
+
+use crate::{
+    driver::{UsbDevice, UsbDriver},
+    error::{DriverError, ErrorClass},
+    stats::StatsRecorder,
+};
+
+use super::transport::Transport;
+
+/// Delay between attempts to reopen a device, doubled after every failed
+/// attempt up to `max`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Backoff {
+    pub initial: time::Duration,
+    pub max: time::Duration,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self {
+            initial: time::Duration::from_millis(50),
+            max: time::Duration::from_secs(2),
+        }
+    }
+}
+
+impl Backoff {
+    fn next(&self, delay: time::Duration) -> time::Duration {
+        (delay * 2).min(self.max)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionState {
+    Connected,
+    /// Device failed and is recovered in place by clearing halt or reset.
+    Recovering(ErrorClass),
+    /// Device is gone, it is reopened with backoff by the following operations.
+    Disconnected,
+}
+
+type Opener<T> = Box<dyn FnMut() -> Result<Option<T>, DriverError> + Send>;
+
+/// Transport which survives device failures and unplugging.
+///
+/// Timeouts are returned as is. A stalled device gets its halt cleared, a
+/// failed one is reset and a device which is gone or can't be reset is
+/// reopened with exponential backoff. The failed operation still returns its
+/// error, so the caller can retry it. While the device is gone operations wait
+/// up to their timeout for it to come back and fail with `DeviceNotFound`.
+pub struct ReconnectingDevice<T: Transport> {
+    device: Option<T>,
+    opener: Opener<T>,
+    name: String,
+    backoff: Backoff,
+    delay: time::Duration,
+    next_attempt: time::Instant,
+    state: ConnectionState,
+    listeners: Vec<mpsc::Sender<ConnectionState>>,
+    reconnects: usize,
+    stats: StatsRecorder,
+}
+
+impl ReconnectingDevice<UsbDevice> {
+    /// Opens device with `serial_number` and reopens it by serial number after failures.
+    pub fn open(
+        mut driver: UsbDriver,
+        serial_number: &str,
+        timeout: time::Duration,
+    ) -> Result<Self, DriverError> {
+        let serial = serial_number.to_owned();
+        Self::with_opener(serial_number, move || {
+            driver.open_by_serial(&serial, timeout)
+        })
+    }
+}
+
+impl<T: Transport> ReconnectingDevice<T> {
+    /// Opens device with `opener` which returns `None` while device is not attached,
+    /// `name` identifies the device in errors.
+    pub fn with_opener<F>(name: &str, opener: F) -> Result<Self, DriverError>
+    where
+        F: FnMut() -> Result<Option<T>, DriverError> + Send + 'static,
+    {
+        let mut opener: Opener<T> = Box::new(opener);
+        let device = opener()?.ok_or_else(|| DriverError::DeviceNotFound(name.to_owned()))?;
+        let backoff = Backoff::default();
+        Ok(Self {
+            device: Some(device),
+            opener,
+            name: name.to_owned(),
+            backoff,
+            delay: backoff.initial,
+            next_attempt: time::Instant::now(),
+            state: ConnectionState::Connected,
+            listeners: Vec::new(),
+            reconnects: 0,
+            stats: StatsRecorder::new(),
+        })
+    }
+
+    pub fn with_backoff(mut self, backoff: Backoff) -> Self {
+        self.backoff = backoff;
+        self.delay = backoff.initial;
+        self
+    }
+
+    pub fn state(&self) -> ConnectionState {
+        self.state
+    }
+
+    /// Returns receiver of every following connection state change.
+    pub fn subscribe(&mut self) -> mpsc::Receiver<ConnectionState> {
+        let (tx, rx) = mpsc::channel();
+        self.listeners.push(tx);
+        rx
+    }
+
+    /// Returns number of times device was reopened.
+    pub fn reconnects(&self) -> usize {
+        self.reconnects
+    }
+
+    /// Returns recorder of the traffic, its counters survive reconnects.
+    pub fn recorder(&self) -> StatsRecorder {
+        self.stats.clone()
+    }
+
+    /// Returns currently open device, `None` while it is disconnected.
+    pub fn get_ref(&self) -> Option<&T> {
+        self.device.as_ref()
+    }
+
+    pub fn get_mut(&mut self) -> Option<&mut T> {
+        self.device.as_mut()
+    }
+
+    /// Closes device and reopens it by the following operations.
+    pub fn disconnect(&mut self) {
+        self.device = None;
+        self.next_attempt = time::Instant::now();
+        self.set_state(ConnectionState::Disconnected);
+    }
+
+    /// Waits for the device to come back, a timeout too large for an instant
+    /// waits forever.
+    fn connect(&mut self, timeout: time::Duration) -> Result<(), DriverError> {
+        let deadline = time::Instant::now().checked_add(timeout);
+        while self.device.is_none() {
+            let now = time::Instant::now();
+            if now >= self.next_attempt {
+                if let Ok(Some(device)) = (self.opener)() {
+                    self.device = Some(device);
+                    self.delay = self.backoff.initial;
+                    self.reconnects += 1;
+                    self.stats.record_reconnect();
+                    self.set_state(ConnectionState::Connected);
+                    break;
+                }
+                self.next_attempt = now + self.delay;
+                self.delay = self.backoff.next(self.delay);
+            }
+
+            let now = time::Instant::now();
+            if let Some(deadline) = deadline.filter(|deadline| self.next_attempt >= *deadline) {
+                thread::sleep(deadline.saturating_duration_since(now));
+                return Err(DriverError::DeviceNotFound(self.name.clone()));
+            }
+            thread::sleep(self.next_attempt.saturating_duration_since(now));
+        }
+        Ok(())
+    }
+
+    fn with_device<R, F>(&mut self, timeout: time::Duration, operation: F) -> Result<R, DriverError>
+    where
+        F: FnOnce(&mut T) -> Result<R, DriverError>,
+    {
+        self.connect(timeout)?;
+        let result = match self.device.as_mut() {
+            Some(device) => operation(device),
+            None => return Err(DriverError::DeviceNotFound(self.name.clone())),
+        };
+        result.inspect_err(|error| self.recover(error.class()))
+    }
+
+    fn recover(&mut self, class: ErrorClass) {
+        match class {
+            ErrorClass::Timeout => {}
+            ErrorClass::Stall => {
+                self.set_state(ConnectionState::Recovering(class));
+                match self.device.as_mut().map(Transport::clear_halt) {
+                    Some(Ok(())) => self.set_state(ConnectionState::Connected),
+                    _ => self.reset_device(),
+                }
+            }
+            ErrorClass::Failure => {
+                self.set_state(ConnectionState::Recovering(class));
+                self.reset_device();
+            }
+            ErrorClass::Disconnected => self.disconnect(),
+        }
+    }
+
+    fn reset_device(&mut self) {
+        match self.device.as_mut().map(Transport::reset) {
+            Some(Ok(())) => self.set_state(ConnectionState::Connected),
+            _ => self.disconnect(),
+        }
+    }
+
+    fn set_state(&mut self, state: ConnectionState) {
+        if self.state != state {
+            self.state = state;
+            self.listeners
+                .retain(|listener| listener.send(state).is_ok());
+        }
+    }
+}
+
+impl<T: Transport> Transport for ReconnectingDevice<T> {
+    fn read(&mut self, buf: &mut [u8], timeout: time::Duration) -> Result<usize, DriverError> {
+        let result = self.with_device(timeout, |device| device.read(buf, timeout));
+        self.stats.record_read(&result);
+        result
+    }
+
+    fn write(&mut self, buf: &[u8], timeout: time::Duration) -> Result<usize, DriverError> {
+        let result = self.with_device(timeout, |device| device.write(buf, timeout));
+        self.stats.record_write(&result);
+        result
+    }
+
+    fn reset(&mut self) -> Result<(), DriverError> {
+        self.with_device(time::Duration::ZERO, Transport::reset)
+    }
+
+    fn clear_halt(&mut self) -> Result<(), DriverError> {
+        self.with_device(time::Duration::ZERO, Transport::clear_halt)
+    }
+
+    fn stats(&self) -> Option<&StatsRecorder> {
+        Some(&self.stats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::VecDeque,
+        sync::{Arc, Mutex},
+    };
+
+    use crate::base::mock::MockTransport;
+
+    use super::*;
+
+    const IO_TIMEOUT: time::Duration = time::Duration::from_millis(100);
+    const BACKOFF: Backoff = Backoff {
+        initial: time::Duration::from_millis(5),
+        max: time::Duration::from_millis(20),
+    };
+
+    /// Returns device opened from the first queued attachment, `None` means unplugged.
+    fn open(attached: Vec<Option<MockTransport>>) -> ReconnectingDevice<MockTransport> {
+        let attached = Arc::new(Mutex::new(VecDeque::from(attached)));
+        ReconnectingDevice::with_opener("1234", move || {
+            Ok(attached.lock().unwrap().pop_front().flatten())
+        })
+        .unwrap()
+        .with_backoff(BACKOFF)
+    }
+
+    #[test]
+    fn pass_timeouts_through() {
+        let transport = MockTransport::new();
+        let mut device = open(vec![Some(transport.clone())]);
+        let mut buf = [0u8; 8];
+
+        let error = device.read(&mut buf, IO_TIMEOUT).unwrap_err();
+
+        assert_eq!(error.class(), ErrorClass::Timeout);
+        assert_eq!(device.state(), ConnectionState::Connected);
+        assert_eq!(transport.resets(), 0);
+    }
+
+    #[test]
+    fn clear_halt_after_stall() {
+        let transport = MockTransport::new();
+        transport.push_read_error(rusb::Error::Pipe).push_read(&[1]);
+        let mut device = open(vec![Some(transport.clone())]);
+        let states = device.subscribe();
+        let mut buf = [0u8; 8];
+
+        let error = device.read(&mut buf, IO_TIMEOUT).unwrap_err();
+        let size = device.read(&mut buf, IO_TIMEOUT).unwrap();
+
+        assert_eq!(error.class(), ErrorClass::Stall);
+        assert_eq!(size, 1);
+        assert_eq!(transport.halts_cleared(), 1);
+        assert_eq!(
+            states.try_iter().collect::<Vec<_>>(),
+            vec![
+                ConnectionState::Recovering(ErrorClass::Stall),
+                ConnectionState::Connected
+            ]
+        );
+    }
+
+    #[test]
+    fn reset_failed_device() {
+        let transport = MockTransport::new();
+        transport.push_read_error(rusb::Error::Io);
+        let mut device = open(vec![Some(transport.clone())]);
+        let mut buf = [0u8; 8];
+
+        device.read(&mut buf, IO_TIMEOUT).unwrap_err();
+
+        assert_eq!(transport.resets(), 1);
+        assert_eq!(device.state(), ConnectionState::Connected);
+        assert_eq!(device.reconnects(), 0);
+    }
+
+    #[test]
+    fn reopen_unplugged_device() {
+        let unplugged = MockTransport::new();
+        unplugged.push_read_error(rusb::Error::NoDevice);
+        let replugged = MockTransport::new();
+        replugged.push_read(&[7]);
+        let mut device = open(vec![Some(unplugged), None, None, Some(replugged)]);
+        let states = device.subscribe();
+        let mut buf = [0u8; 8];
+
+        let error = device.read(&mut buf, IO_TIMEOUT).unwrap_err();
+        assert_eq!(device.state(), ConnectionState::Disconnected);
+        let size = device.read(&mut buf, IO_TIMEOUT).unwrap();
+
+        assert_eq!(error.class(), ErrorClass::Disconnected);
+        assert_eq!((size, buf[0]), (1, 7));
+        assert_eq!(device.reconnects(), 1);
+        assert_eq!(
+            states.try_iter().collect::<Vec<_>>(),
+            vec![ConnectionState::Disconnected, ConnectionState::Connected]
+        );
+    }
+
+    #[test]
+    fn wait_for_device_without_deadline() {
+        let unplugged = MockTransport::new();
+        unplugged.push_read_error(rusb::Error::NoDevice);
+        let replugged = MockTransport::new();
+        replugged.push_read(&[7]);
+        let mut device = open(vec![Some(unplugged), None, Some(replugged)]);
+        let mut buf = [0u8; 8];
+
+        device.read(&mut buf, time::Duration::MAX).unwrap_err();
+        let size = device.read(&mut buf, time::Duration::MAX).unwrap();
+
+        assert_eq!((size, buf[0]), (1, 7));
+    }
+
+    #[test]
+    fn keep_stats_across_reconnects() {
+        let unplugged = MockTransport::new();
+        unplugged
+            .push_read(&[1, 2])
+            .push_read_error(rusb::Error::NoDevice);
+        let replugged = MockTransport::new();
+        replugged.push_read(&[3]);
+        let mut device = open(vec![Some(unplugged), Some(replugged)]);
+        let mut buf = [0u8; 8];
+
+        device.read(&mut buf, IO_TIMEOUT).unwrap();
+        device.read(&mut buf, IO_TIMEOUT).unwrap_err();
+        device.read(&mut buf, IO_TIMEOUT).unwrap();
+        let stats = device.recorder().snapshot();
+
+        assert_eq!(stats.bytes_read, 3);
+        assert_eq!(stats.disconnects, 1);
+        assert_eq!(stats.reconnects, 1);
+    }
+
+    #[test]
+    fn reopen_device_which_fails_to_reset() {
+        let broken = MockTransport::new();
+        broken
+            .push_read_error(rusb::Error::Io)
+            .fail_resets(Some(rusb::Error::NotFound));
+        let mut device = open(vec![Some(broken), Some(MockTransport::new())]);
+        let mut buf = [0u8; 8];
+
+        device.read(&mut buf, IO_TIMEOUT).unwrap_err();
+        assert_eq!(device.state(), ConnectionState::Disconnected);
+        device.read(&mut buf, IO_TIMEOUT).unwrap_err();
+
+        assert_eq!(device.state(), ConnectionState::Connected);
+        assert_eq!(device.reconnects(), 1);
+    }
+
+    #[test]
+    fn report_missing_device_after_timeout() {
+        let unplugged = MockTransport::new();
+        unplugged.fail_writes(Some(rusb::Error::NoDevice));
+        let mut device = open(vec![Some(unplugged)]);
+        device.write(&[1], IO_TIMEOUT).unwrap_err();
+        let started = time::Instant::now();
+
+        let error = device.write(&[1], IO_TIMEOUT).unwrap_err();
+
+        assert!(matches!(error, DriverError::DeviceNotFound(ref name) if name == "1234"));
+        assert!(started.elapsed() >= IO_TIMEOUT);
+        assert_eq!(device.state(), ConnectionState::Disconnected);
+    }
+
+    #[test]
+    fn fail_to_open_missing_device() {
+        let result = ReconnectingDevice::<MockTransport>::with_opener("1234", || Ok(None));
+
+        assert!(matches!(result, Err(DriverError::DeviceNotFound(_))));
+    }
+
+    #[test]
+    fn double_backoff_delay_up_to_max() {
+        assert_eq!(
+            BACKOFF.next(BACKOFF.initial),
+            time::Duration::from_millis(10)
+        );
+        assert_eq!(BACKOFF.next(time::Duration::from_millis(15)), BACKOFF.max);
+    }
+}
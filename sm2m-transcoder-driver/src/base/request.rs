@@ -0,0 +1,421 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt, time,
+};
+
+use sm2m_protocol::{deframer::FrameStats, packet::Packet};
+
+use crate::error::DriverError;
+
+use super::{framed::Framed, transport::Transport};
+
+const DEFAULT_TIMEOUT: time::Duration = time::Duration::from_secs(1);
+const DEFAULT_RETRIES: usize = 2;
+const MAX_UNSOLICITED: usize = 256;
+const MAX_ABANDONED: usize = 8;
+
+/// Command which links device responses to the request which caused them.
+pub trait Request: Packet + fmt::Debug {
+    type Response: Packet + fmt::Debug;
+
+    /// Returns `true` when `response` answers this command. Commands which
+    /// are never answered return `false` for every response.
+    fn is_answered_by(&self, response: &Self::Response) -> bool;
+
+    /// Returns `true` for responses device sends on its own, like streamed parameters.
+    fn is_unsolicited(response: &Self::Response) -> bool;
+}
+
+/// Identifies submitted request until its response is taken with [`Requester::wait`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Tag(u32);
+
+/// Time given to every request attempt and the number of times the command
+/// is sent again after an attempt times out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    pub timeout: time::Duration,
+    pub retries: usize,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            timeout: DEFAULT_TIMEOUT,
+            retries: DEFAULT_RETRIES,
+        }
+    }
+}
+
+struct Pending<C> {
+    tag: Tag,
+    command: C,
+    abandoned: bool,
+}
+
+/// Request layer on top of [`Framed`] transport.
+///
+/// Device answers commands in the order they were received, so every response
+/// is routed to the oldest pending command it answers. Abandoned commands get
+/// only responses no live command waits for, so a lost answer doesn't starve
+/// later requests of the same kind. Unsolicited responses
+/// are queued separately, only the last 256 of them are kept. Responses which
+/// answer no pending command are reported as `DriverError::UnexpectedResponse`.
+pub struct Requester<T: Transport, C: Request> {
+    device: Framed<T>,
+    policy: RetryPolicy,
+    next_tag: u32,
+    pending: VecDeque<Pending<C>>,
+    completed: HashMap<Tag, C::Response>,
+    unsolicited: VecDeque<C::Response>,
+}
+
+impl<T: Transport, C: Request> Requester<T, C> {
+    pub fn new(transport: T) -> Self {
+        Self::with_policy(transport, RetryPolicy::default())
+    }
+
+    pub fn with_policy(transport: T, policy: RetryPolicy) -> Self {
+        Self {
+            device: Framed::new(transport),
+            policy,
+            next_tag: 0,
+            pending: VecDeque::new(),
+            completed: HashMap::new(),
+            unsolicited: VecDeque::new(),
+        }
+    }
+
+    pub fn policy(&self) -> RetryPolicy {
+        self.policy
+    }
+
+    pub fn set_policy(&mut self, policy: RetryPolicy) {
+        self.policy = policy;
+    }
+
+    pub fn get_ref(&self) -> &Framed<T> {
+        &self.device
+    }
+
+    pub fn get_mut(&mut self) -> &mut Framed<T> {
+        &mut self.device
+    }
+
+    pub fn into_inner(self) -> Framed<T> {
+        self.device
+    }
+
+    pub fn stats(&self) -> FrameStats {
+        self.device.stats()
+    }
+
+    /// Sends command without waiting for the answer.
+    pub fn send(&mut self, command: &C) -> Result<(), DriverError> {
+        self.device.write_packet(command)?;
+        Ok(())
+    }
+
+    /// Sends command and registers it as pending, the answer is taken with [`Requester::wait`].
+    pub fn submit(&mut self, command: C) -> Result<Tag, DriverError> {
+        self.device.write_packet(&command)?;
+        let tag = Tag(self.next_tag);
+        self.next_tag = self.next_tag.wrapping_add(1);
+        self.pending.push_back(Pending {
+            tag,
+            command,
+            abandoned: false,
+        });
+        Ok(tag)
+    }
+
+    /// Waits for the answer to submitted command. When nothing answers within
+    /// `timeout` or reading fails the request is abandoned and its late answer
+    /// is dropped.
+    pub fn wait(&mut self, tag: Tag, timeout: time::Duration) -> Result<C::Response, DriverError> {
+        let deadline = time::Instant::now().checked_add(timeout);
+        loop {
+            if let Some(response) = self.completed.remove(&tag) {
+                return Ok(response);
+            }
+            if !self.is_pending(tag) {
+                return Err(DriverError::Timeout(timeout));
+            }
+
+            let remaining = match deadline {
+                Some(deadline) => deadline.saturating_duration_since(time::Instant::now()),
+                None => timeout,
+            };
+            match self.poll(remaining) {
+                Ok(()) | Err(DriverError::DecodePacket(_)) => continue,
+                Err(error) => {
+                    self.abandon(tag);
+                    if error.is_timeout() {
+                        return Err(DriverError::Timeout(timeout));
+                    }
+                    return Err(error);
+                }
+            }
+        }
+    }
+
+    /// Sends command and waits for the answer according to retry policy.
+    pub fn request(&mut self, command: C) -> Result<C::Response, DriverError>
+    where
+        C: Clone,
+    {
+        self.request_within(command, self.policy.timeout)
+    }
+
+    /// Same as [`Requester::request`] with `timeout` given to every attempt.
+    pub fn request_within(
+        &mut self,
+        command: C,
+        timeout: time::Duration,
+    ) -> Result<C::Response, DriverError>
+    where
+        C: Clone,
+    {
+        let mut retries = self.policy.retries;
+        loop {
+            let tag = self.submit(command.clone())?;
+            match self.wait(tag, timeout) {
+                Err(DriverError::Timeout(_)) if retries > 0 => retries -= 1,
+                result => return result,
+            }
+        }
+    }
+
+    /// Returns queued unsolicited response or waits for the next one.
+    pub fn next_unsolicited(
+        &mut self,
+        timeout: time::Duration,
+    ) -> Result<C::Response, DriverError> {
+        let deadline = time::Instant::now().checked_add(timeout);
+        loop {
+            if let Some(response) = self.unsolicited.pop_front() {
+                return Ok(response);
+            }
+
+            let remaining = match deadline {
+                Some(deadline) => deadline.saturating_duration_since(time::Instant::now()),
+                None => timeout,
+            };
+            match self.poll(remaining) {
+                Ok(()) => continue,
+                Err(error) if error.is_timeout() => return Err(DriverError::Timeout(timeout)),
+                Err(error) => return Err(error),
+            }
+        }
+    }
+
+    /// Takes queued unsolicited response without reading from device.
+    pub fn pop_unsolicited(&mut self) -> Option<C::Response> {
+        self.unsolicited.pop_front()
+    }
+
+    /// Drops every pending request and queued response and resets the transport.
+    pub fn reset(&mut self) -> Result<(), DriverError> {
+        self.pending.clear();
+        self.completed.clear();
+        self.unsolicited.clear();
+        self.device.reset()
+    }
+
+    fn poll(&mut self, timeout: time::Duration) -> Result<(), DriverError> {
+        let response = self.device.read_packet::<C::Response>(timeout)?;
+        self.route(response)
+    }
+
+    fn route(&mut self, response: C::Response) -> Result<(), DriverError> {
+        if C::is_unsolicited(&response) {
+            if self.unsolicited.len() == MAX_UNSOLICITED {
+                self.unsolicited.pop_front();
+            }
+            self.unsolicited.push_back(response);
+            return Ok(());
+        }
+
+        let answers = |abandoned: bool| {
+            self.pending.iter().position(|pending| {
+                pending.abandoned == abandoned && pending.command.is_answered_by(&response)
+            })
+        };
+        let position = answers(false).or_else(|| answers(true));
+        match position.and_then(|position| self.pending.remove(position)) {
+            Some(pending) if pending.abandoned => Ok(()),
+            Some(pending) => {
+                self.completed.insert(pending.tag, response);
+                Ok(())
+            }
+            None => Err(DriverError::UnexpectedResponse(format!("{:?}", response))),
+        }
+    }
+
+    fn is_pending(&self, tag: Tag) -> bool {
+        self.pending
+            .iter()
+            .any(|pending| pending.tag == tag && !pending.abandoned)
+    }
+
+    fn abandon(&mut self, tag: Tag) {
+        if let Some(pending) = self.pending.iter_mut().find(|pending| pending.tag == tag) {
+            pending.abandoned = true;
+        }
+        let abandoned = self
+            .pending
+            .iter()
+            .filter(|pending| pending.abandoned)
+            .count();
+        if abandoned > MAX_ABANDONED {
+            if let Some(position) = self.pending.iter().position(|pending| pending.abandoned) {
+                self.pending.remove(position);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sm2m_protocol::decoder::{Command, Response};
+
+    use crate::base::mock::{self, MockTransport};
+
+    use super::*;
+
+    const TIMEOUT: time::Duration = time::Duration::from_millis(10);
+
+    fn requester(transport: &MockTransport) -> Requester<MockTransport, Command> {
+        Requester::with_policy(
+            transport.clone(),
+            RetryPolicy {
+                timeout: TIMEOUT,
+                retries: 1,
+            },
+        )
+    }
+
+    #[test]
+    fn route_reply_interleaved_with_unsolicited_frames() {
+        let transport = MockTransport::new();
+        transport
+            .push_read(&mock::frame(0, &[2, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0]))
+            .push_read(&mock::frame(1, &[1, 1, 2, 3]))
+            .push_read(&mock::frame(2, &[2, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 2, 0]));
+        let mut requester = requester(&transport);
+
+        let response = requester.request(Command::GetVersion).unwrap();
+
+        assert_eq!(response, Response::Version(1, 2, 3));
+        assert_eq!(
+            requester.pop_unsolicited(),
+            Some(Response::Params {
+                timestamp: 0,
+                params: [1].iter().copied().collect()
+            })
+        );
+        assert_eq!(
+            requester.next_unsolicited(TIMEOUT).unwrap(),
+            Response::Params {
+                timestamp: 0,
+                params: [2].iter().copied().collect()
+            }
+        );
+    }
+
+    #[test]
+    fn route_replies_to_submitted_requests() {
+        let transport = MockTransport::new();
+        transport
+            .push_read(&mock::frame(0, &[0x0f, 1, 1, 30, 3, 0]))
+            .push_read(&mock::frame(1, &[1, 1, 0, 0]));
+        let mut requester = requester(&transport);
+
+        let version = requester.submit(Command::GetVersion).unwrap();
+        let capabilities = requester.submit(Command::GetCapabilities).unwrap();
+
+        assert_eq!(
+            requester.wait(version, TIMEOUT).unwrap(),
+            Response::Version(1, 0, 0)
+        );
+        assert!(matches!(
+            requester.wait(capabilities, TIMEOUT).unwrap(),
+            Response::Capabilities(_)
+        ));
+    }
+
+    #[test]
+    fn retry_request_after_timeout() {
+        let transport = MockTransport::new();
+        let mut requester = requester(&transport);
+
+        let result = requester.request(Command::GetVersion);
+
+        assert!(matches!(result, Err(DriverError::Timeout(TIMEOUT))));
+        assert_eq!(
+            transport.written(),
+            vec![mock::frame(0, &[1]), mock::frame(1, &[1])]
+        );
+    }
+
+    #[test]
+    fn drop_late_reply_to_abandoned_request() {
+        let transport = MockTransport::new();
+        let mut requester = requester(&transport);
+        requester.set_policy(RetryPolicy {
+            timeout: TIMEOUT,
+            retries: 0,
+        });
+        assert!(requester.request(Command::GetVersion).is_err());
+        transport
+            .push_read(&mock::frame(0, &[1, 1, 0, 0]))
+            .push_read(&mock::frame(1, &[0x0f, 1, 1, 30, 3, 0]));
+
+        let response = requester.request(Command::GetCapabilities).unwrap();
+
+        assert!(matches!(response, Response::Capabilities(_)));
+    }
+
+    #[test]
+    fn answer_later_requests_after_lost_reply() {
+        let transport = MockTransport::new();
+        let mut requester = requester(&transport);
+        requester.set_policy(RetryPolicy {
+            timeout: TIMEOUT,
+            retries: 0,
+        });
+        assert!(requester.request(Command::GetVersion).is_err());
+
+        for (seq, patch) in (0..4).enumerate() {
+            transport.push_read(&mock::frame(seq as u8, &[1, 1, 0, patch]));
+
+            let response = requester.request(Command::GetVersion).unwrap();
+
+            assert_eq!(response, Response::Version(1, 0, patch));
+        }
+    }
+
+    #[test]
+    fn answer_next_request_after_failed_one() {
+        let transport = MockTransport::new();
+        transport.push_read_error(rusb::Error::Pipe);
+        let mut requester = requester(&transport);
+        assert!(requester.request(Command::GetVersion).is_err());
+        transport.push_read(&mock::frame(0, &[1, 1, 0, 4]));
+
+        let response = requester.request(Command::GetVersion).unwrap();
+
+        assert_eq!(response, Response::Version(1, 0, 4));
+    }
+
+    #[test]
+    fn report_unexpected_response() {
+        let transport = MockTransport::new();
+        transport.push_read(&mock::frame(0, &[1, 1, 0, 0]));
+        let mut requester = requester(&transport);
+
+        let result = requester.request(Command::GetCapabilities);
+
+        assert!(matches!(result, Err(DriverError::UnexpectedResponse(_))));
+    }
+}
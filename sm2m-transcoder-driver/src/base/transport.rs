@@ -0,0 +1,117 @@
+use std::time;
+
+use crate::{error::DriverError, stats::StatsRecorder};
+
+/// Time given to every transfer made by `write_all` and `read_all`.
+pub const IO_TIMEOUT: time::Duration = time::Duration::from_secs(1);
+const IO_RETRIES: usize = 8;
+
+pub trait Transport {
+    fn read(&mut self, buf: &mut [u8], timeout: time::Duration) -> Result<usize, DriverError>;
+    fn write(&mut self, buf: &[u8], timeout: time::Duration) -> Result<usize, DriverError>;
+    fn reset(&mut self) -> Result<(), DriverError>;
+
+    /// Clears halt condition of the device endpoints after a stall, transports
+    /// without endpoints have nothing to clear.
+    fn clear_halt(&mut self) -> Result<(), DriverError> {
+        Ok(())
+    }
+
+    /// Returns recorder of the transport traffic, protocol layers record
+    /// packets into it.
+    fn stats(&self) -> Option<&StatsRecorder> {
+        None
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> Result<usize, DriverError> {
+        let size = self.try_write_all(buf, IO_TIMEOUT, IO_RETRIES)?;
+        if size < buf.len() {
+            Err(DriverError::Timeout(IO_TIMEOUT))
+        } else {
+            Ok(size)
+        }
+    }
+
+    fn try_write_all(
+        &mut self,
+        buf: &[u8],
+        timeout: time::Duration,
+        mut retries: usize,
+    ) -> Result<usize, DriverError> {
+        let bytes_total = buf.len();
+        let mut bytes_written = self.write(buf, timeout)?;
+        while bytes_written < bytes_total && retries > 0 {
+            bytes_written += self.write(&buf[bytes_written..], timeout)?;
+            retries -= 1;
+        }
+        Ok(bytes_written)
+    }
+
+    fn read_all(&mut self, buf: &mut [u8]) -> Result<usize, DriverError> {
+        let size = self.try_read_all(buf, IO_TIMEOUT, IO_RETRIES)?;
+        if size < buf.len() {
+            Err(DriverError::Timeout(IO_TIMEOUT))
+        } else {
+            Ok(size)
+        }
+    }
+
+    fn try_read_all(
+        &mut self,
+        buf: &mut [u8],
+        timeout: time::Duration,
+        mut retries: usize,
+    ) -> Result<usize, DriverError> {
+        let bytes_total = buf.len();
+        let mut bytes_read = self.read(buf, timeout)?;
+        while bytes_read < bytes_total && retries > 0 {
+            bytes_read += self.read(&mut buf[bytes_read..], timeout)?;
+            retries -= 1;
+        }
+        Ok(bytes_read)
+    }
+}
+
+impl<T: Transport + ?Sized> Transport for &mut T {
+    fn read(&mut self, buf: &mut [u8], timeout: time::Duration) -> Result<usize, DriverError> {
+        (**self).read(buf, timeout)
+    }
+
+    fn write(&mut self, buf: &[u8], timeout: time::Duration) -> Result<usize, DriverError> {
+        (**self).write(buf, timeout)
+    }
+
+    fn reset(&mut self) -> Result<(), DriverError> {
+        (**self).reset()
+    }
+
+    fn clear_halt(&mut self) -> Result<(), DriverError> {
+        (**self).clear_halt()
+    }
+
+    fn stats(&self) -> Option<&StatsRecorder> {
+        (**self).stats()
+    }
+}
+
+impl<T: Transport + ?Sized> Transport for Box<T> {
+    fn read(&mut self, buf: &mut [u8], timeout: time::Duration) -> Result<usize, DriverError> {
+        (**self).read(buf, timeout)
+    }
+
+    fn write(&mut self, buf: &[u8], timeout: time::Duration) -> Result<usize, DriverError> {
+        (**self).write(buf, timeout)
+    }
+
+    fn reset(&mut self) -> Result<(), DriverError> {
+        (**self).reset()
+    }
+
+    fn clear_halt(&mut self) -> Result<(), DriverError> {
+        (**self).clear_halt()
+    }
+
+    fn stats(&self) -> Option<&StatsRecorder> {
+        (**self).stats()
+    }
+}
@@ -0,0 +1,354 @@
+use std::{
+    io::{self, Read, Write},
+    time,
+};
+
+use crate::{
+    error::DriverError,
+    info::{DeviceInfo, DeviceRole, FirmwareVersion},
+};
+
+const MAGIC: &[u8; 8] = b"SM2MCAP\0";
+const FORMAT_VERSION: u16 = 1;
+const RECORD_HEADER_SIZE: usize = 13;
+
+/// Describes the device traffic was captured from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaptureHeader {
+    pub role: DeviceRole,
+    pub product: String,
+    pub serial_number: String,
+    pub firmware: Option<FirmwareVersion>,
+    /// Wall clock time of the capture start, record timestamps are relative to it.
+    pub started: time::SystemTime,
+}
+
+impl CaptureHeader {
+    pub fn new(info: &DeviceInfo) -> Self {
+        Self {
+            role: info.role,
+            product: info.product.clone(),
+            serial_number: info.serial_number.clone(),
+            firmware: info.firmware,
+            started: time::SystemTime::now(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// Bytes read by host from device.
+    Read,
+    /// Bytes written by host to device.
+    Write,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Record {
+    pub direction: Direction,
+    /// Monotonic time elapsed since the capture start.
+    pub timestamp: time::Duration,
+    pub bytes: Vec<u8>,
+}
+
+/// Writes capture file, all numbers are little-endian:
+///
+/// - header: magic `SM2MCAP\0`, format version (16 bits), role (8 bits),
+///   firmware present flag, major, minor and patch (8 bits each), start time
+///   in microseconds since Unix epoch (64 bits), product and serial number
+///   strings each prefixed with 16 bits of length;
+/// - records: direction (8 bits, `0` read and `1` write), timestamp in
+///   microseconds since the capture start (64 bits), bytes prefixed with 32
+///   bits of length.
+pub struct CaptureWriter<W: Write> {
+    writer: W,
+    started: time::Instant,
+}
+
+impl<W: Write> CaptureWriter<W> {
+    pub fn new(mut writer: W, header: &CaptureHeader) -> Result<Self, DriverError> {
+        write_header(&mut writer, header).map_err(DriverError::Capture)?;
+        Ok(Self {
+            writer,
+            started: time::Instant::now(),
+        })
+    }
+
+    /// Time elapsed since the header was written.
+    pub fn elapsed(&self) -> time::Duration {
+        self.started.elapsed()
+    }
+
+    pub fn write_record(&mut self, record: &Record) -> Result<(), DriverError> {
+        write_record(&mut self.writer, record).map_err(DriverError::Capture)
+    }
+
+    pub fn flush(&mut self) -> Result<(), DriverError> {
+        self.writer.flush().map_err(DriverError::Capture)
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+pub struct CaptureReader<R: Read> {
+    reader: R,
+    header: CaptureHeader,
+}
+
+impl<R: Read> CaptureReader<R> {
+    pub fn new(mut reader: R) -> Result<Self, DriverError> {
+        let header = read_header(&mut reader)?;
+        Ok(Self { reader, header })
+    }
+
+    pub fn header(&self) -> &CaptureHeader {
+        &self.header
+    }
+
+    /// Returns the next record or `None` at the end of capture.
+    pub fn read_record(&mut self) -> Result<Option<Record>, DriverError> {
+        let mut direction = [0u8; 1];
+        match self.reader.read_exact(&mut direction) {
+            Ok(()) => {}
+            Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(error) => return Err(DriverError::Capture(error)),
+        }
+        let direction = match direction[0] {
+            0 => Direction::Read,
+            1 => Direction::Write,
+            value => {
+                return Err(DriverError::InvalidCapture(format!(
+                    "unknown record direction {}",
+                    value
+                )))
+            }
+        };
+        let timestamp = time::Duration::from_micros(read_u64(&mut self.reader)?);
+        let len = read_u32(&mut self.reader)? as usize;
+        let bytes = read_bytes(&mut self.reader, len)?;
+        Ok(Some(Record {
+            direction,
+            timestamp,
+            bytes,
+        }))
+    }
+}
+
+impl<R: Read> Iterator for CaptureReader<R> {
+    type Item = Result<Record, DriverError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_record().transpose()
+    }
+}
+
+fn write_header<W: Write>(writer: &mut W, header: &CaptureHeader) -> io::Result<()> {
+    writer.write_all(MAGIC)?;
+    writer.write_all(&FORMAT_VERSION.to_le_bytes())?;
+    writer.write_all(&[role_to_u8(header.role)])?;
+    let firmware = match header.firmware {
+        Some(firmware) => [1, firmware.major, firmware.minor, firmware.patch],
+        None => [0; 4],
+    };
+    writer.write_all(&firmware)?;
+    let started = header
+        .started
+        .duration_since(time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_micros() as u64;
+    writer.write_all(&started.to_le_bytes())?;
+    write_string(writer, &header.product)?;
+    write_string(writer, &header.serial_number)
+}
+
+fn write_string<W: Write>(writer: &mut W, value: &str) -> io::Result<()> {
+    let len = value.len().min(u16::MAX as usize);
+    writer.write_all(&(len as u16).to_le_bytes())?;
+    writer.write_all(&value.as_bytes()[..len])
+}
+
+/// Writes the whole record at once, so unbuffered files never keep a partial record.
+fn write_record<W: Write>(writer: &mut W, record: &Record) -> io::Result<()> {
+    let direction = match record.direction {
+        Direction::Read => 0u8,
+        Direction::Write => 1u8,
+    };
+    let mut buf = Vec::with_capacity(RECORD_HEADER_SIZE + record.bytes.len());
+    buf.push(direction);
+    buf.extend_from_slice(&(record.timestamp.as_micros() as u64).to_le_bytes());
+    buf.extend_from_slice(&(record.bytes.len() as u32).to_le_bytes());
+    buf.extend_from_slice(&record.bytes);
+    writer.write_all(&buf)
+}
+
+fn read_header<R: Read>(reader: &mut R) -> Result<CaptureHeader, DriverError> {
+    let mut magic = [0u8; 8];
+    read_exact(reader, &mut magic)?;
+    if &magic != MAGIC {
+        return Err(DriverError::InvalidCapture(
+            "not a SM2M capture file".to_owned(),
+        ));
+    }
+    let version = read_u16(reader)?;
+    if version != FORMAT_VERSION {
+        return Err(DriverError::InvalidCapture(format!(
+            "unsupported format version {}",
+            version
+        )));
+    }
+    let mut fields = [0u8; 5];
+    read_exact(reader, &mut fields)?;
+    let firmware = match fields[1] {
+        0 => None,
+        _ => Some(FirmwareVersion::new(fields[2], fields[3], fields[4])),
+    };
+    let started = time::UNIX_EPOCH + time::Duration::from_micros(read_u64(reader)?);
+    let product = read_string(reader)?;
+    let serial_number = read_string(reader)?;
+    Ok(CaptureHeader {
+        role: role_from_u8(fields[0]),
+        product,
+        serial_number,
+        firmware,
+        started,
+    })
+}
+
+fn read_string<R: Read>(reader: &mut R) -> Result<String, DriverError> {
+    let len = read_u16(reader)? as usize;
+    let bytes = read_bytes(reader, len)?;
+    String::from_utf8(bytes)
+        .map_err(|_| DriverError::InvalidCapture("header string is not UTF-8".to_owned()))
+}
+
+fn read_bytes<R: Read>(reader: &mut R, len: usize) -> Result<Vec<u8>, DriverError> {
+    let mut bytes = Vec::new();
+    let size = reader
+        .take(len as u64)
+        .read_to_end(&mut bytes)
+        .map_err(DriverError::Capture)?;
+    if size < len {
+        Err(DriverError::InvalidCapture(
+            "capture is truncated".to_owned(),
+        ))
+    } else {
+        Ok(bytes)
+    }
+}
+
+fn read_u16<R: Read>(reader: &mut R) -> Result<u16, DriverError> {
+    let mut buf = [0u8; 2];
+    read_exact(reader, &mut buf)?;
+    Ok(u16::from_le_bytes(buf))
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> Result<u32, DriverError> {
+    let mut buf = [0u8; 4];
+    read_exact(reader, &mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> Result<u64, DriverError> {
+    let mut buf = [0u8; 8];
+    read_exact(reader, &mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+fn read_exact<R: Read>(reader: &mut R, buf: &mut [u8]) -> Result<(), DriverError> {
+    reader.read_exact(buf).map_err(|error| {
+        if error.kind() == io::ErrorKind::UnexpectedEof {
+            DriverError::InvalidCapture("capture is truncated".to_owned())
+        } else {
+            DriverError::Capture(error)
+        }
+    })
+}
+
+fn role_to_u8(role: DeviceRole) -> u8 {
+    match role {
+        DeviceRole::Unknown => 0,
+        DeviceRole::Decoder => 1,
+        DeviceRole::Emulator => 2,
+        DeviceRole::Encoder => 3,
+    }
+}
+
+fn role_from_u8(value: u8) -> DeviceRole {
+    match value {
+        1 => DeviceRole::Decoder,
+        2 => DeviceRole::Emulator,
+        3 => DeviceRole::Encoder,
+        _ => DeviceRole::Unknown,
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    pub(crate) fn header() -> CaptureHeader {
+        CaptureHeader {
+            role: DeviceRole::Decoder,
+            product: "An26 SM2M Decoder".to_owned(),
+            serial_number: "1A2B".to_owned(),
+            firmware: Some(FirmwareVersion::new(1, 2, 3)),
+            started: time::UNIX_EPOCH + time::Duration::from_micros(1_634_000_000_000_000),
+        }
+    }
+
+    #[test]
+    fn read_back_written_capture() {
+        let records = vec![
+            Record {
+                direction: Direction::Write,
+                timestamp: time::Duration::from_micros(10),
+                bytes: vec![1, 2],
+            },
+            Record {
+                direction: Direction::Read,
+                timestamp: time::Duration::from_millis(25),
+                bytes: vec![0xa5; 70],
+            },
+        ];
+        let mut writer = CaptureWriter::new(Vec::new(), &header()).unwrap();
+        for record in &records {
+            writer.write_record(record).unwrap();
+        }
+        let bytes = writer.into_inner();
+
+        let reader = CaptureReader::new(bytes.as_slice()).unwrap();
+
+        assert_eq!(reader.header(), &header());
+        assert_eq!(reader.collect::<Result<Vec<_>, _>>().unwrap(), records);
+    }
+
+    #[test]
+    fn reject_file_without_magic() {
+        let result = CaptureReader::new(&b"SM2MCAX\0\x01\x00"[..]);
+
+        assert!(matches!(result, Err(DriverError::InvalidCapture(_))));
+    }
+
+    #[test]
+    fn reject_truncated_record() {
+        let mut writer = CaptureWriter::new(Vec::new(), &header()).unwrap();
+        writer
+            .write_record(&Record {
+                direction: Direction::Read,
+                timestamp: time::Duration::ZERO,
+                bytes: vec![1, 2, 3],
+            })
+            .unwrap();
+        let mut bytes = writer.into_inner();
+        bytes.pop();
+
+        let mut reader = CaptureReader::new(bytes.as_slice()).unwrap();
+
+        assert!(matches!(
+            reader.read_record(),
+            Err(DriverError::InvalidCapture(_))
+        ));
+    }
+}
@@ -0,0 +1,126 @@
+use std::{fs, io::Write, path::Path, time};
+
+use crate::{base::transport::Transport, error::DriverError, stats::StatsRecorder};
+
+use super::format::{CaptureHeader, CaptureWriter, Direction, Record};
+
+/// Transport wrapper which tees every byte read from and written to the
+/// device into a capture.
+///
+/// Capture file created by [`Recorder::create`] is not buffered, so records
+/// survive the process being killed in the middle of a session.
+pub struct Recorder<T: Transport, W: Write> {
+    transport: T,
+    writer: CaptureWriter<W>,
+}
+
+impl<T: Transport> Recorder<T, fs::File> {
+    pub fn create<P: AsRef<Path>>(
+        transport: T,
+        path: P,
+        header: &CaptureHeader,
+    ) -> Result<Self, DriverError> {
+        let file = fs::File::create(path).map_err(DriverError::Capture)?;
+        Self::new(transport, file, header)
+    }
+}
+
+impl<T: Transport, W: Write> Recorder<T, W> {
+    pub fn new(transport: T, writer: W, header: &CaptureHeader) -> Result<Self, DriverError> {
+        Ok(Self {
+            transport,
+            writer: CaptureWriter::new(writer, header)?,
+        })
+    }
+
+    pub fn get_ref(&self) -> &T {
+        &self.transport
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.transport
+    }
+
+    pub fn flush(&mut self) -> Result<(), DriverError> {
+        self.writer.flush()
+    }
+
+    /// Flushes the capture and returns wrapped transport and capture writer.
+    pub fn into_parts(mut self) -> Result<(T, W), DriverError> {
+        self.writer.flush()?;
+        Ok((self.transport, self.writer.into_inner()))
+    }
+
+    fn record(&mut self, direction: Direction, bytes: &[u8]) -> Result<(), DriverError> {
+        if bytes.is_empty() {
+            return Ok(());
+        }
+        self.writer.write_record(&Record {
+            direction,
+            timestamp: self.writer.elapsed(),
+            bytes: bytes.to_vec(),
+        })
+    }
+}
+
+impl<T: Transport, W: Write> Transport for Recorder<T, W> {
+    fn read(&mut self, buf: &mut [u8], timeout: time::Duration) -> Result<usize, DriverError> {
+        let size = self.transport.read(buf, timeout)?;
+        self.record(Direction::Read, &buf[..size])?;
+        Ok(size)
+    }
+
+    fn write(&mut self, buf: &[u8], timeout: time::Duration) -> Result<usize, DriverError> {
+        let size = self.transport.write(buf, timeout)?;
+        self.record(Direction::Write, &buf[..size])?;
+        Ok(size)
+    }
+
+    fn reset(&mut self) -> Result<(), DriverError> {
+        self.transport.reset()
+    }
+
+    fn clear_halt(&mut self) -> Result<(), DriverError> {
+        self.transport.clear_halt()
+    }
+
+    fn stats(&self) -> Option<&StatsRecorder> {
+        self.transport.stats()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        base::mock::{self, MockTransport},
+        capture::format::{tests::header, CaptureReader},
+    };
+
+    use super::*;
+
+    const TIMEOUT: time::Duration = time::Duration::from_millis(10);
+
+    #[test]
+    fn tee_traffic_into_capture() {
+        let transport = MockTransport::new();
+        transport.respond(&mock::frame(0, &[1]), &mock::frame(0, &[1, 1, 2, 3]));
+        let mut recorder = Recorder::new(transport, Vec::new(), &header()).unwrap();
+        let mut buf = [0u8; 64];
+
+        recorder.write_all(&mock::frame(0, &[1])).unwrap();
+        let size = recorder.read(&mut buf, TIMEOUT).unwrap();
+        assert!(recorder.read(&mut buf, TIMEOUT).is_err());
+        let (_, bytes) = recorder.into_parts().unwrap();
+
+        let records = CaptureReader::new(bytes.as_slice())
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].direction, Direction::Write);
+        assert_eq!(records[0].bytes, mock::frame(0, &[1]));
+        assert_eq!(records[1].direction, Direction::Read);
+        assert_eq!(records[1].bytes, buf[..size].to_vec());
+        assert!(records[0].timestamp <= records[1].timestamp);
+    }
+}
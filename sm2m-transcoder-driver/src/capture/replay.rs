@@ -0,0 +1,174 @@
+use std::{collections::VecDeque, fs, io, io::Read, path::Path, thread, time};
+
+use crate::{base::transport::Transport, error::DriverError};
+
+use super::format::{CaptureHeader, CaptureReader, Direction};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pace {
+    /// Delivers recorded bytes at the moments they were originally read.
+    Original,
+    /// Delivers recorded bytes as soon as they are requested.
+    AsFastAsPossible,
+}
+
+/// Transport which plays device traffic back from a capture.
+///
+/// Only bytes read from device are replayed, writes are accepted and dropped.
+/// Reading past the end of capture fails with `DriverError::CaptureEnded`.
+pub struct ReplayTransport<R: Read> {
+    reader: CaptureReader<R>,
+    pace: Pace,
+    started: time::Instant,
+    pending: VecDeque<u8>,
+    due: time::Duration,
+}
+
+impl ReplayTransport<io::BufReader<fs::File>> {
+    pub fn open<P: AsRef<Path>>(path: P, pace: Pace) -> Result<Self, DriverError> {
+        let file = fs::File::open(path).map_err(DriverError::Capture)?;
+        Self::new(io::BufReader::new(file), pace)
+    }
+}
+
+impl<R: Read> ReplayTransport<R> {
+    pub fn new(reader: R, pace: Pace) -> Result<Self, DriverError> {
+        Ok(Self {
+            reader: CaptureReader::new(reader)?,
+            pace,
+            started: time::Instant::now(),
+            pending: VecDeque::new(),
+            due: time::Duration::ZERO,
+        })
+    }
+
+    pub fn header(&self) -> &CaptureHeader {
+        self.reader.header()
+    }
+
+    fn next_read_record(&mut self) -> Result<(), DriverError> {
+        loop {
+            match self.reader.read_record()? {
+                Some(record) if record.direction == Direction::Read => {
+                    self.pending.extend(record.bytes);
+                    self.due = record.timestamp;
+                    return Ok(());
+                }
+                Some(_) => continue,
+                None => return Err(DriverError::CaptureEnded),
+            }
+        }
+    }
+}
+
+impl<R: Read> Transport for ReplayTransport<R> {
+    fn read(&mut self, buf: &mut [u8], timeout: time::Duration) -> Result<usize, DriverError> {
+        if self.pending.is_empty() {
+            self.next_read_record()?;
+        }
+
+        if self.pace == Pace::Original {
+            let wait = self.due.saturating_sub(self.started.elapsed());
+            if wait > timeout {
+                thread::sleep(timeout);
+                return Err(DriverError::Timeout(timeout));
+            }
+            thread::sleep(wait);
+        }
+
+        let size = buf.len().min(self.pending.len());
+        for (byte, pending) in buf.iter_mut().zip(self.pending.drain(..size)) {
+            *byte = pending;
+        }
+        Ok(size)
+    }
+
+    fn write(&mut self, buf: &[u8], _timeout: time::Duration) -> Result<usize, DriverError> {
+        Ok(buf.len())
+    }
+
+    fn reset(&mut self) -> Result<(), DriverError> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        base::{framed::Framed, mock},
+        capture::format::{tests::header, CaptureWriter, Record},
+        devices::decoder::DecoderDevice,
+    };
+
+    use super::*;
+
+    fn capture(records: &[Record]) -> Vec<u8> {
+        let mut writer = CaptureWriter::new(Vec::new(), &header()).unwrap();
+        for record in records {
+            writer.write_record(record).unwrap();
+        }
+        writer.into_inner()
+    }
+
+    fn read_record(timestamp: time::Duration, bytes: Vec<u8>) -> Record {
+        Record {
+            direction: Direction::Read,
+            timestamp,
+            bytes,
+        }
+    }
+
+    #[test]
+    fn replay_decoder_frames_through_device_api() {
+        let bytes = capture(&[
+            Record {
+                direction: Direction::Write,
+                timestamp: time::Duration::ZERO,
+                bytes: mock::frame(0, &[1]),
+            },
+            read_record(
+                time::Duration::from_secs(5),
+                mock::frame(0, &[2, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0x55, 0x55]),
+            ),
+            read_record(
+                time::Duration::from_secs(10),
+                mock::frame(1, &[2, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0x01, 0x00]),
+            ),
+        ]);
+        let replay = ReplayTransport::new(bytes.as_slice(), Pace::AsFastAsPossible).unwrap();
+        let mut device = Framed::new(replay);
+
+        let frames = device.frames().collect::<Vec<_>>();
+
+        assert_eq!(frames.len(), 3);
+        assert_eq!(frames[0].as_ref().unwrap().words.as_slice(), &[0x5555]);
+        assert_eq!(frames[1].as_ref().unwrap().words.as_slice(), &[1]);
+        assert!(matches!(frames[2], Err(DriverError::CaptureEnded)));
+    }
+
+    #[test]
+    fn replay_with_original_timing() {
+        let bytes = capture(&[
+            read_record(time::Duration::from_millis(30), vec![1, 2]),
+            read_record(time::Duration::from_secs(60), vec![3]),
+        ]);
+        let mut replay = ReplayTransport::new(bytes.as_slice(), Pace::Original).unwrap();
+        let mut buf = [0u8; 1];
+        let started = time::Instant::now();
+
+        assert_eq!(
+            replay.read(&mut buf, time::Duration::from_secs(1)).unwrap(),
+            1
+        );
+        assert!(started.elapsed() >= time::Duration::from_millis(30));
+        assert_eq!(
+            replay.read(&mut buf, time::Duration::from_secs(1)).unwrap(),
+            1
+        );
+        assert_eq!(buf, [2]);
+        assert!(matches!(
+            replay.read(&mut buf, time::Duration::from_millis(10)),
+            Err(DriverError::Timeout(_))
+        ));
+    }
+}
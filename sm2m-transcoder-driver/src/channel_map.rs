@@ -0,0 +1,512 @@
+use std::{collections::HashSet, convert::TryFrom, fs, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{devices::decoder::ParamsFrame, error::DriverError};
+
+/// Words a channel is read from. A pair holds a 32-bit value with the high word first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Words {
+    Single(usize),
+    Pair(usize, usize),
+}
+
+impl Words {
+    fn last(&self) -> usize {
+        match *self {
+            Words::Single(index) => index,
+            Words::Pair(high, low) => high.max(low),
+        }
+    }
+
+    fn read(&self, words: &[u16]) -> u32 {
+        match *self {
+            Words::Single(index) => words[index] as u32,
+            Words::Pair(high, low) => (words[high] as u32) << 16 | words[low] as u32,
+        }
+    }
+
+    fn bits(&self) -> u32 {
+        match self {
+            Words::Single(_) => 16,
+            Words::Pair(..) => 32,
+        }
+    }
+}
+
+/// Conversion of raw channel value into engineering value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Conversion {
+    /// Linear mapping of raw range `from` onto engineering range `to`.
+    Scale { from: [f64; 2], to: [f64; 2] },
+    /// Calibration table of raw and engineering value points sorted by raw
+    /// value, values between points are interpolated and values outside are clamped.
+    Lookup(Vec<[f64; 2]>),
+}
+
+impl Conversion {
+    fn apply(&self, raw: f64) -> f64 {
+        match self {
+            Conversion::Scale { from, to } => {
+                to[0] + (raw - from[0]) * (to[1] - to[0]) / (from[1] - from[0])
+            }
+            Conversion::Lookup(points) => {
+                let upper = points.iter().position(|point| raw < point[0]);
+                match upper {
+                    Some(0) => points[0][1],
+                    Some(index) => {
+                        let (low, high) = (points[index - 1], points[index]);
+                        low[1] + (raw - low[0]) * (high[1] - low[1]) / (high[0] - low[0])
+                    }
+                    None => points[points.len() - 1][1],
+                }
+            }
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        match self {
+            Conversion::Scale { from, .. } if from[0] == from[1] => {
+                Err("scale range `from` is empty".to_owned())
+            }
+            Conversion::Lookup(points) if points.len() < 2 => {
+                Err("lookup table needs at least two points".to_owned())
+            }
+            Conversion::Lookup(points)
+                if points.windows(2).any(|pair| pair[0][0] >= pair[1][0]) =>
+            {
+                Err("lookup table points are not sorted by raw value".to_owned())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Named channel carried by one or two words of parameters frame.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "ChannelSpec", into = "ChannelSpec")]
+pub struct Channel {
+    pub name: String,
+    pub words: Words,
+    /// Reads raw value as two's complement number.
+    pub signed: bool,
+    /// Selects bits of raw value, the result is shifted down to bit zero.
+    pub mask: Option<u32>,
+    pub conversion: Option<Conversion>,
+    pub units: Option<String>,
+}
+
+impl Channel {
+    fn raw(&self, words: &[u16]) -> f64 {
+        let raw = self.words.read(words);
+        match self.mask {
+            Some(mask) => ((raw & mask) >> mask.trailing_zeros()) as f64,
+            None if self.signed => {
+                let shift = 32 - self.words.bits();
+                ((raw << shift) as i32 >> shift) as f64
+            }
+            None => raw as f64,
+        }
+    }
+}
+
+/// Channel as written in map files, `word` or `words` selects the words and
+/// at most one of `scale` and `lookup` converts the value.
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ChannelSpec {
+    name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    word: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    words: Option<[usize; 2]>,
+    #[serde(default)]
+    signed: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    mask: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    scale: Option<Scale>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    lookup: Option<Vec<[f64; 2]>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    units: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Scale {
+    from: [f64; 2],
+    to: [f64; 2],
+}
+
+impl TryFrom<ChannelSpec> for Channel {
+    type Error = String;
+
+    fn try_from(spec: ChannelSpec) -> Result<Self, Self::Error> {
+        let ChannelSpec {
+            name,
+            word,
+            words,
+            signed,
+            mask,
+            scale,
+            lookup,
+            units,
+        } = spec;
+        let words = match (word, words) {
+            (Some(index), None) => Words::Single(index),
+            (None, Some([high, low])) => Words::Pair(high, low),
+            _ => return Err(format!("channel {} needs either `word` or `words`", name)),
+        };
+        let conversion = match (scale, lookup) {
+            (None, None) => None,
+            (Some(Scale { from, to }), None) => Some(Conversion::Scale { from, to }),
+            (None, Some(points)) => Some(Conversion::Lookup(points)),
+            (Some(_), Some(_)) => {
+                return Err(format!("channel {} has both `scale` and `lookup`", name))
+            }
+        };
+        if let Some(conversion) = &conversion {
+            conversion
+                .validate()
+                .map_err(|reason| format!("channel {}: {}", name, reason))?;
+        }
+        match mask {
+            Some(0) => return Err(format!("channel {} has empty `mask`", name)),
+            Some(_) if signed => {
+                return Err(format!("channel {} can't be both masked and signed", name))
+            }
+            _ => {}
+        }
+
+        Ok(Self {
+            name,
+            words,
+            signed,
+            mask,
+            conversion,
+            units,
+        })
+    }
+}
+
+impl From<Channel> for ChannelSpec {
+    fn from(channel: Channel) -> Self {
+        let (word, words) = match channel.words {
+            Words::Single(index) => (Some(index), None),
+            Words::Pair(high, low) => (None, Some([high, low])),
+        };
+        let (scale, lookup) = match channel.conversion {
+            Some(Conversion::Scale { from, to }) => (Some(Scale { from, to }), None),
+            Some(Conversion::Lookup(points)) => (None, Some(points)),
+            None => (None, None),
+        };
+        Self {
+            name: channel.name,
+            word,
+            words,
+            signed: channel.signed,
+            mask: channel.mask,
+            scale,
+            lookup,
+            units: channel.units,
+        }
+    }
+}
+
+/// Engineering value of a channel decoded from parameters frame.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChannelValue<'a> {
+    pub name: &'a str,
+    pub value: f64,
+    pub units: Option<&'a str>,
+}
+
+/// Declarative layout of parameters frame, loaded from TOML or JSON so the
+/// layout can follow the SM2M computer program without rebuilding.
+///
+/// ```toml
+/// [[channel]]
+/// name = "latitude"
+/// words = [0, 1]
+/// scale = { from = [0, 4294967295], to = [0, 90] }
+/// units = "deg"
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChannelMap {
+    #[serde(rename = "channel")]
+    channels: Vec<Channel>,
+}
+
+impl ChannelMap {
+    pub fn new(channels: Vec<Channel>) -> Result<Self, DriverError> {
+        let mut names = HashSet::new();
+        if let Some(channel) = channels
+            .iter()
+            .find(|channel| !names.insert(channel.name.as_str()))
+        {
+            return Err(DriverError::InvalidChannelMap(format!(
+                "channel {} is defined twice",
+                channel.name
+            )));
+        }
+        Ok(Self { channels })
+    }
+
+    pub fn from_toml(map: &str) -> Result<Self, DriverError> {
+        let map: Self = toml::from_str(map)
+            .map_err(|error| DriverError::InvalidChannelMap(error.to_string()))?;
+        Self::new(map.channels)
+    }
+
+    pub fn from_json(map: &str) -> Result<Self, DriverError> {
+        let map: Self = serde_json::from_str(map)
+            .map_err(|error| DriverError::InvalidChannelMap(error.to_string()))?;
+        Self::new(map.channels)
+    }
+
+    /// Loads map from `.json` file, any other file is read as TOML.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, DriverError> {
+        let path = path.as_ref();
+        let map = fs::read_to_string(path).map_err(DriverError::ReadChannelMap)?;
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("json") => Self::from_json(&map),
+            _ => Self::from_toml(&map),
+        }
+    }
+
+    pub fn channels(&self) -> &[Channel] {
+        &self.channels
+    }
+
+    pub fn channel(&self, name: &str) -> Option<&Channel> {
+        self.channels.iter().find(|channel| channel.name == name)
+    }
+
+    /// Returns number of words a frame needs to hold every channel.
+    pub fn frame_len(&self) -> usize {
+        self.channels
+            .iter()
+            .map(|channel| channel.words.last() + 1)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Decodes every channel in map order, frames shorter than `frame_len`
+    /// and overflown frames are rejected.
+    pub fn decode(&self, frame: &ParamsFrame) -> Result<Vec<ChannelValue<'_>>, DriverError> {
+        if let Some(overflow) = frame.overflow {
+            return Err(DriverError::FrameOverflow(
+                overflow.received,
+                overflow.expected,
+            ));
+        }
+        if frame.words.len() < self.frame_len() {
+            return Err(DriverError::FrameTooShort(
+                frame.words.len(),
+                self.frame_len(),
+            ));
+        }
+
+        Ok(self
+            .channels
+            .iter()
+            .map(|channel| {
+                let raw = channel.raw(&frame.words);
+                ChannelValue {
+                    name: &channel.name,
+                    value: channel
+                        .conversion
+                        .as_ref()
+                        .map_or(raw, |conversion| conversion.apply(raw)),
+                    units: channel.units.as_deref(),
+                }
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::devices::decoder::ParamsOverflow;
+
+    use super::*;
+
+    const AN26_MAP: &str = include_str!("../maps/an26.toml");
+
+    fn frame(words: &[u16]) -> ParamsFrame {
+        ParamsFrame {
+            words: words.iter().copied().collect(),
+            ..ParamsFrame::default()
+        }
+    }
+
+    fn value(values: &[ChannelValue<'_>], name: &str) -> f64 {
+        values
+            .iter()
+            .find(|value| value.name == name)
+            .map(|value| value.value)
+            .unwrap()
+    }
+
+    fn assert_near(actual: f64, expected: f64, tolerance: f64) {
+        assert!(
+            (actual - expected).abs() <= tolerance,
+            "{} is not {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn decode_an26_layout() {
+        let map = ChannelMap::from_toml(AN26_MAP).unwrap();
+        let latitude = 2402881062u32;
+        let longitude = 368431135u32;
+        let words = [
+            (latitude >> 16) as u16,
+            latitude as u16,
+            (longitude >> 16) as u16,
+            longitude as u16,
+            150,
+            16384,
+            0x8001,
+            0x8001,
+            0x8001,
+            32767,
+            0,
+            32768,
+            0,
+            32768,
+            0,
+            32768,
+            65535,
+            7,
+            1,
+        ];
+
+        let values = map.decode(&frame(&words)).unwrap();
+
+        assert_eq!(map.frame_len(), 19);
+        assert_near(value(&values, "latitude"), 50.351791, 0.00001);
+        assert_near(value(&values, "longitude"), 30.881541, 0.00001);
+        assert_near(value(&values, "altitude"), 150.0, 0.001);
+        assert_near(value(&values, "heading"), 90.0, 0.01);
+        assert_near(value(&values, "pitch"), -45.0, 0.01);
+        assert_near(value(&values, "roll"), -90.0, 0.01);
+        assert_near(value(&values, "ailerons"), -1.0, 0.001);
+        assert_near(value(&values, "elevator"), 1.0, 0.001);
+        assert_near(value(&values, "flaps"), 0.5, 0.001);
+        assert_near(value(&values, "engine_right"), 83.0, 0.01);
+        assert_near(value(&values, "gear_right"), 1.0, 0.001);
+        assert_eq!(value(&values, "light_beacon"), 1.0);
+        assert_eq!(value(&values, "reset"), 1.0);
+        assert_eq!(values[0].units, Some("deg"));
+    }
+
+    #[test]
+    fn interpolate_lookup_table() {
+        let map = ChannelMap::from_json(
+            r#"{ "channel": [
+                { "name": "fuel", "word": 0, "lookup": [[100, 0], [200, 500], [400, 1500]], "units": "kg" }
+            ] }"#,
+        )
+        .unwrap();
+
+        let decode = |raw| map.decode(&frame(&[raw])).unwrap()[0].value;
+
+        assert_eq!(decode(50), 0.0);
+        assert_eq!(decode(150), 250.0);
+        assert_eq!(decode(300), 1000.0);
+        assert_eq!(decode(1000), 1500.0);
+    }
+
+    #[test]
+    fn read_signed_and_masked_values() {
+        let map = ChannelMap::from_toml(
+            r#"
+            [[channel]]
+            name = "offset"
+            word = 0
+            signed = true
+
+            [[channel]]
+            name = "wide"
+            words = [1, 2]
+            signed = true
+
+            [[channel]]
+            name = "mode"
+            word = 3
+            mask = 0x0030
+            "#,
+        )
+        .unwrap();
+
+        let values = map
+            .decode(&frame(&[0xfffe, 0xffff, 0xfff0, 0x0020]))
+            .unwrap();
+
+        assert_eq!(values[0].value, -2.0);
+        assert_eq!(values[1].value, -16.0);
+        assert_eq!(values[2].value, 2.0);
+    }
+
+    #[test]
+    fn reject_short_frame() {
+        let map = ChannelMap::from_toml(AN26_MAP).unwrap();
+
+        let result = map.decode(&frame(&[0; 4]));
+
+        assert!(matches!(result, Err(DriverError::FrameTooShort(4, 19))));
+    }
+
+    #[test]
+    fn reject_overflown_frame() {
+        let map = ChannelMap::from_toml("[[channel]]\nname = \"a\"\nword = 0").unwrap();
+        let frame = ParamsFrame {
+            overflow: Some(ParamsOverflow {
+                expected: 30,
+                received: 31,
+            }),
+            ..frame(&[1])
+        };
+
+        let result = map.decode(&frame);
+
+        assert!(matches!(result, Err(DriverError::FrameOverflow(31, 30))));
+    }
+
+    #[test]
+    fn reject_invalid_maps() {
+        let maps = [
+            "[[channel]]\nname = \"a\"",
+            "[[channel]]\nname = \"a\"\nword = 0\nwords = [1, 2]",
+            "[[channel]]\nname = \"a\"\nword = 0\nlookup = [[2, 0], [1, 1]]",
+            "[[channel]]\nname = \"a\"\nword = 0\nscale = { from = [1, 1], to = [0, 1] }",
+            "[[channel]]\nname = \"a\"\nword = 0\nmask = 1\nsigned = true",
+            "[[channel]]\nname = \"a\"\nword = 0\nunit = \"m\"",
+            "[[channel]]\nname = \"a\"\nword = 0\n[[channel]]\nname = \"a\"\nword = 1",
+        ];
+
+        for map in maps.iter() {
+            assert!(
+                matches!(
+                    ChannelMap::from_toml(map),
+                    Err(DriverError::InvalidChannelMap(_))
+                ),
+                "{}",
+                map
+            );
+        }
+    }
+
+    #[test]
+    fn convert_between_toml_and_json() {
+        let map = ChannelMap::from_toml(AN26_MAP).unwrap();
+
+        let json = serde_json::to_string(&map).unwrap();
+
+        assert_eq!(ChannelMap::from_json(&json).unwrap(), map);
+    }
+}
@@ -1,79 +1,433 @@
+pub use sm2m_protocol::{
+    decoder::{Command, DecoderStats, Params, Response, MAX_PARAMS_COUNT},
+    queue::DeliveryPolicy,
+};
+
 use std::time;
 
-use crate::{driver::UsbDevice, error::DriverError};
+use crate::{
+    base::{framed::Framed, request::Request, transport::Transport},
+    error::DriverError,
+};
+
+const STATS_TIMEOUT: time::Duration = time::Duration::from_secs(1);
 
-pub enum Inbound {
-    GetVersion,
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParamsOverflow {
+    pub expected: u8,
+    pub received: u8,
 }
 
-#[derive(Debug, PartialEq, Eq)]
-pub enum Outbound {
-    Version(u8, u8, u8),
-    Unknown,
+/// Parameters received by decoder from SM2M between two start markers.
+///
+/// When SM2M sends more parameters than decoder is capable to receive, `words`
+/// is empty and `overflow` holds the expected and received counts. When
+/// decoder dropped a frame with mismatching checksum, `words` is empty and
+/// `corrupted` holds the number of frames it dropped so far.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ParamsFrame {
+    pub words: Params,
+    /// Decoder clock in microseconds when the first word was captured.
+    pub timestamp: u64,
+    pub overflow: Option<ParamsOverflow>,
+    pub corrupted: Option<u32>,
 }
 
 pub trait DecoderDevice {
-    fn write_ex(&mut self, packet: Inbound) -> Result<usize, DriverError>;
-    fn read_ex(&mut self) -> Result<Outbound, DriverError>;
-}
+    fn write_ex(&mut self, command: Command) -> Result<usize, DriverError>;
+    fn read_ex(&mut self) -> Result<Response, DriverError>;
+
+    /// Same as [`DecoderDevice::read_ex`] waiting at most `timeout`.
+    fn read_ex_within(&mut self, timeout: time::Duration) -> Result<Response, DriverError>;
+
+    /// Makes decoder verify and strip checksum word trailing every frame, see
+    /// `EmulatorSession::set_checksum`. Keep it disabled with a real SM2M.
+    fn set_checksum(&mut self, enabled: bool) -> Result<(), DriverError> {
+        self.write_ex(Command::SetChecksum(enabled)).map(|_| ())
+    }
+
+    /// Selects frames the decoder streams, frames skipped by the policy are
+    /// not counted as dropped.
+    fn set_delivery(&mut self, policy: DeliveryPolicy) -> Result<(), DriverError> {
+        self.write_ex(Command::SetDelivery(policy)).map(|_| ())
+    }
 
-impl DecoderDevice for UsbDevice {
-    fn write_ex(&mut self, packet: Inbound) -> Result<usize, DriverError> {
-        match packet {
-            Inbound::GetVersion => {
-                let buf = [1];
-                self.write_all(&buf)
+    /// Requests diagnostics counters of the decoder firmware. Parameters
+    /// frames arriving before the answer are skipped for at most a second.
+    fn decoder_stats(&mut self) -> Result<DecoderStats, DriverError> {
+        self.decoder_stats_within(STATS_TIMEOUT)
+    }
+
+    /// Same as [`DecoderDevice::decoder_stats`] waiting up to `timeout` for
+    /// the answer, every read is bounded by the time left.
+    fn decoder_stats_within(
+        &mut self,
+        timeout: time::Duration,
+    ) -> Result<DecoderStats, DriverError> {
+        self.write_ex(Command::GetStats)?;
+        let deadline = time::Instant::now().checked_add(timeout);
+        loop {
+            let remaining = match deadline {
+                Some(deadline) => deadline.saturating_duration_since(time::Instant::now()),
+                None => timeout,
+            };
+            if remaining.is_zero() {
+                return Err(DriverError::Timeout(timeout));
+            }
+            match self.read_ex_within(remaining) {
+                Ok(Response::Stats(stats)) => return Ok(stats),
+                Ok(_) | Err(DriverError::DecodePacket(_)) => continue,
+                Err(error) if error.is_timeout() => return Err(DriverError::Timeout(timeout)),
+                Err(error) => return Err(error),
             }
         }
     }
 
-    fn read_ex(&mut self) -> Result<Outbound, DriverError> {
-        const READ_TIMEOUT: time::Duration = time::Duration::from_secs(1);
-        let mut buf = [0u8; 64];
-        self.read(&mut buf, READ_TIMEOUT)?;
-        let opcode = buf[0] & 0x0f;
-        let packet = match opcode {
-            1 => {
-                let major = buf[1];
-                let minor = buf[2];
-                let patch = buf[3];
-                Outbound::Version(major, minor, patch)
+    fn reset_decoder_stats(&mut self) -> Result<(), DriverError> {
+        self.write_ex(Command::ResetStats).map(|_| ())
+    }
+
+    /// Reads packets until a parameters frame arrives, other responses are skipped.
+    fn read_params(&mut self) -> Result<ParamsFrame, DriverError> {
+        loop {
+            match self.read_ex()? {
+                Response::Params { timestamp, params } => {
+                    return Ok(ParamsFrame {
+                        words: params,
+                        timestamp,
+                        ..ParamsFrame::default()
+                    })
+                }
+                Response::ParamsOverflow { expected, received } => {
+                    return Ok(ParamsFrame {
+                        overflow: Some(ParamsOverflow { expected, received }),
+                        ..ParamsFrame::default()
+                    })
+                }
+                Response::ParamsCorrupted(dropped) => {
+                    return Ok(ParamsFrame {
+                        corrupted: Some(dropped),
+                        ..ParamsFrame::default()
+                    })
+                }
+                Response::Version(..) | Response::Capabilities(_) | Response::Stats(_) => continue,
             }
-            _ => Outbound::Unknown,
-        };
-        Ok(packet)
+        }
+    }
+
+    /// Returns blocking iterator over parameters frames streamed by decoder.
+    fn frames(&mut self) -> Frames<'_, Self>
+    where
+        Self: Sized,
+    {
+        Frames {
+            device: self,
+            done: false,
+        }
+    }
+}
+
+/// Blocking iterator over parameters frames.
+///
+/// Read timeouts are expected while SM2M is idle and are retried, malformed
+/// packets are reported and skipped. The iterator ends after any other error.
+pub struct Frames<'a, D: DecoderDevice> {
+    device: &'a mut D,
+    done: bool,
+}
+
+impl<'a, D: DecoderDevice> Iterator for Frames<'a, D> {
+    type Item = Result<ParamsFrame, DriverError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            match self.device.read_params() {
+                Ok(frame) => return Some(Ok(frame)),
+                Err(error) if error.is_timeout() => continue,
+                Err(error @ DriverError::DecodePacket(_)) => return Some(Err(error)),
+                Err(error) => {
+                    self.done = true;
+                    return Some(Err(error));
+                }
+            }
+        }
+        None
+    }
+}
+
+impl Request for Command {
+    type Response = Response;
+
+    fn is_answered_by(&self, response: &Response) -> bool {
+        matches!(
+            (self, response),
+            (Command::GetVersion, Response::Version(..))
+                | (Command::GetCapabilities, Response::Capabilities(_))
+                | (Command::GetStats, Response::Stats(_))
+        )
+    }
+
+    fn is_unsolicited(response: &Response) -> bool {
+        matches!(
+            response,
+            Response::Params { .. }
+                | Response::ParamsOverflow { .. }
+                | Response::ParamsCorrupted(_)
+        )
+    }
+}
+
+impl<T: Transport> DecoderDevice for Framed<T> {
+    fn write_ex(&mut self, command: Command) -> Result<usize, DriverError> {
+        self.write_packet(&command)
+    }
+
+    fn read_ex(&mut self) -> Result<Response, DriverError> {
+        self.read_packet(self.read_timeout())
+    }
+
+    fn read_ex_within(&mut self, timeout: time::Duration) -> Result<Response, DriverError> {
+        self.read_packet(timeout)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::driver::UsbDriver;
+    use sm2m_protocol::packet::PacketError;
 
-    use super::*;
+    use crate::base::mock::{self, MockTransport};
 
-    const IO_TIMEOUT: time::Duration = time::Duration::from_secs(1);
+    use super::*;
 
     #[test]
     fn get_version() {
-        let mut device = find_device();
+        let transport = MockTransport::new();
+        transport.respond(&mock::frame(0, &[1]), &mock::frame(0, &[1, 1, 2, 3]));
+        let mut device = Framed::new(transport.clone());
 
         let size = device
-            .write_ex(Inbound::GetVersion)
+            .write_ex(Command::GetVersion)
             .expect("Error sending version request");
         assert_eq!(size, 1);
         let packet = device.read_ex().expect("Error reading packet from device");
-        assert_ne!(packet, Outbound::Unknown);
+        assert_eq!(packet, Response::Version(1, 2, 3));
+        assert_eq!(transport.written(), vec![mock::frame(0, &[1])]);
+    }
 
-        println!("{:?}", packet);
+    #[test]
+    fn read_params() {
+        let transport = MockTransport::new();
+        transport.push_read(&mock::frame(
+            0,
+            &[2, 0, 2, 0x40, 0x42, 0x0f, 0, 0, 0, 0, 0, 0x55, 0x55, 1, 0],
+        ));
+        let mut device = Framed::new(transport);
+
+        let packet = device.read_ex().expect("Error reading packet from device");
+
+        let params = [0x5555, 1].iter().copied().collect();
+        assert_eq!(
+            packet,
+            Response::Params {
+                timestamp: 1_000_000,
+                params
+            }
+        );
+    }
+
+    #[test]
+    fn read_params_skipping_version() {
+        let transport = MockTransport::new();
+        transport
+            .push_read(&mock::frame(0, &[1, 1, 0, 0]))
+            .push_read(&mock::frame(
+                1,
+                &[2, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0x34, 0x12],
+            ));
+        let mut device = Framed::new(transport);
+
+        let frame = device.read_params().expect("Error reading params frame");
+
+        assert_eq!(frame.words.as_slice(), &[0x1234]);
+        assert_eq!(frame.overflow, None);
     }
 
-    fn find_device() -> UsbDevice {
-        let mut driver = UsbDriver::new().expect("Error initializing driver");
-        let mut device = driver
-            .find_decoder(IO_TIMEOUT)
-            .expect("Error finding decoder")
-            .expect("No decoder found");
-        device.reset().expect("Error resetting device");
-        device
+    #[test]
+    fn read_params_overflow() {
+        let transport = MockTransport::new();
+        transport.push_read(&mock::frame(0, &[2, 1, 30, 42]));
+        let mut device = Framed::new(transport);
+
+        let frame = device.read_params().expect("Error reading params frame");
+
+        assert!(frame.words.is_empty());
+        assert_eq!(
+            frame.overflow,
+            Some(ParamsOverflow {
+                expected: 30,
+                received: 42
+            })
+        );
+    }
+
+    #[test]
+    fn read_params_corrupted() {
+        let transport = MockTransport::new();
+        transport.push_read(&mock::frame(0, &[2, 2, 3, 1, 0, 0]));
+        let mut device = Framed::new(transport);
+
+        let frame = device.read_params().expect("Error reading params frame");
+
+        assert!(frame.words.is_empty());
+        assert_eq!(frame.corrupted, Some(259));
+    }
+
+    #[test]
+    fn enable_checksum() {
+        let transport = MockTransport::new();
+        let mut device = Framed::new(transport.clone());
+
+        device.set_checksum(true).expect("Error enabling checksum");
+
+        assert_eq!(transport.written(), vec![mock::frame(0, &[3, 1])]);
+    }
+
+    #[test]
+    fn read_decoder_stats_skipping_params() {
+        let transport = MockTransport::new();
+        let mut stats = vec![4];
+        for counter in 1..=11u32 {
+            stats.extend(counter.to_le_bytes());
+        }
+        transport
+            .push_read(&mock::frame(0, &[2, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0]))
+            .push_read(&mock::frame(1, &stats));
+        let mut device = Framed::new(transport.clone());
+
+        let stats = device.decoder_stats().expect("Error reading decoder stats");
+
+        assert_eq!(stats.words, 1);
+        assert_eq!(stats.frames, 4);
+        assert_eq!(stats.usb_write_errors, 11);
+        assert_eq!(transport.written(), vec![mock::frame(0, &[4])]);
+    }
+
+    #[test]
+    fn time_out_stats_request_while_params_stream() {
+        struct Streaming;
+
+        impl DecoderDevice for Streaming {
+            fn write_ex(&mut self, _: Command) -> Result<usize, DriverError> {
+                Ok(1)
+            }
+
+            fn read_ex(&mut self) -> Result<Response, DriverError> {
+                Ok(Response::ParamsCorrupted(0))
+            }
+
+            fn read_ex_within(&mut self, _: time::Duration) -> Result<Response, DriverError> {
+                self.read_ex()
+            }
+        }
+
+        let timeout = time::Duration::from_millis(20);
+
+        let result = Streaming.decoder_stats_within(timeout);
+
+        assert!(matches!(result, Err(DriverError::Timeout(t)) if t == timeout));
+    }
+
+    #[test]
+    fn time_out_stats_request_before_read_timeout() {
+        struct Silent;
+
+        impl Transport for Silent {
+            fn read(
+                &mut self,
+                _: &mut [u8],
+                timeout: time::Duration,
+            ) -> Result<usize, DriverError> {
+                std::thread::sleep(timeout);
+                Err(DriverError::Read(rusb::Error::Timeout, 0x81))
+            }
+
+            fn write(&mut self, buf: &[u8], _: time::Duration) -> Result<usize, DriverError> {
+                Ok(buf.len())
+            }
+
+            fn reset(&mut self) -> Result<(), DriverError> {
+                Ok(())
+            }
+        }
+        let timeout = time::Duration::from_millis(20);
+        let started = time::Instant::now();
+
+        let result = Framed::new(Silent).decoder_stats_within(timeout);
+
+        assert!(matches!(result, Err(DriverError::Timeout(t)) if t == timeout));
+        assert!(started.elapsed() < time::Duration::from_millis(500));
+    }
+
+    #[test]
+    fn stream_frames_through_timeouts() {
+        let transport = MockTransport::new();
+        transport
+            .push_read(&mock::frame(0, &[2, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0]))
+            .push_read_error(rusb::Error::Timeout)
+            .push_read(&mock::frame(1, &[14]))
+            .push_read(&mock::frame(2, &[2, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 2, 0]))
+            .push_read_error(rusb::Error::NoDevice);
+        let mut device = Framed::new(transport);
+
+        let frames = device.frames().collect::<Vec<_>>();
+
+        assert_eq!(frames.len(), 4);
+        assert_eq!(frames[0].as_ref().unwrap().words.as_slice(), &[1]);
+        assert!(matches!(frames[1], Err(DriverError::DecodePacket(_))));
+        assert_eq!(frames[2].as_ref().unwrap().words.as_slice(), &[2]);
+        assert!(matches!(
+            frames[3],
+            Err(DriverError::Read(rusb::Error::NoDevice, _))
+        ));
+    }
+
+    #[test]
+    fn read_unknown_packet() {
+        let transport = MockTransport::new();
+        transport.push_read(&mock::frame(0, &[14, 1, 2]));
+        let mut device = Framed::new(transport);
+
+        let result = device.read_ex();
+
+        assert!(matches!(
+            result,
+            Err(DriverError::DecodePacket(PacketError::UnknownOpcode(14)))
+        ));
+    }
+
+    #[test]
+    fn read_truncated_version() {
+        let transport = MockTransport::new();
+        transport.push_read(&mock::frame(0, &[1, 1]));
+        let mut device = Framed::new(transport);
+
+        let result = device.read_ex();
+
+        assert!(matches!(
+            result,
+            Err(DriverError::DecodePacket(PacketError::Truncated(1)))
+        ));
+    }
+
+    #[test]
+    fn fail_reading_without_data() {
+        let mut device = Framed::new(MockTransport::new());
+
+        let result = device.read_ex();
+
+        assert!(matches!(
+            result,
+            Err(DriverError::Read(rusb::Error::Timeout, _))
+        ));
     }
 }
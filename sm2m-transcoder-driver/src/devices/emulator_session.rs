@@ -0,0 +1,283 @@
+use crate::{
+    base::{framed::Framed, request::Requester, transport::Transport},
+    error::DriverError,
+};
+
+use super::emulator::{Command, Response, Values};
+
+/// Parameters of a single emulator channel generator.
+///
+/// Channel starts at `value` and changes by `step` every `period` produced frames.
+/// Zero `period` keeps the channel at constant `value`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GeneratorConfig {
+    pub value: u16,
+    pub step: u16,
+    pub period: u8,
+}
+
+impl Default for GeneratorConfig {
+    fn default() -> Self {
+        Self {
+            value: 0,
+            step: 1,
+            period: 1,
+        }
+    }
+}
+
+/// High level emulator API which validates channel indices against the
+/// number of channels reported by emulator.
+pub struct EmulatorSession<T: Transport> {
+    device: Requester<T, Command>,
+    channels_count: usize,
+}
+
+impl<T: Transport> EmulatorSession<T> {
+    /// Wraps emulator transport and reads the number of channels it supports.
+    pub fn open(transport: T) -> Result<Self, DriverError> {
+        let mut session = Self {
+            device: Requester::new(transport),
+            channels_count: 0,
+        };
+        session.channels_count = session.values()?.len();
+        Ok(session)
+    }
+
+    pub fn channels_count(&self) -> usize {
+        self.channels_count
+    }
+
+    pub fn channel(&mut self, index: usize) -> ChannelBuilder<'_, T> {
+        ChannelBuilder {
+            session: self,
+            index,
+            config: GeneratorConfig::default(),
+        }
+    }
+
+    pub fn enable(&mut self, index: usize, config: GeneratorConfig) -> Result<(), DriverError> {
+        let index = self.validate_channel(index)?;
+        self.device.send(&Command::EnableGenerator {
+            index,
+            period: config.period,
+            value: config.value,
+            step: config.step,
+        })
+    }
+
+    pub fn disable(&mut self, index: usize) -> Result<(), DriverError> {
+        let index = self.validate_channel(index)?;
+        self.device.send(&Command::DisableGenerator(index))
+    }
+
+    /// Starts producing parameter frames with `fps` frames per second.
+    pub fn start(&mut self, fps: u8) -> Result<(), DriverError> {
+        self.device.send(&Command::StartProducer(fps))
+    }
+
+    pub fn stop(&mut self) -> Result<(), DriverError> {
+        self.device.send(&Command::StopProducer)
+    }
+
+    /// Appends checksum word to every produced frame, decoder verifies it when
+    /// its checksum mode is enabled too.
+    pub fn set_checksum(&mut self, enabled: bool) -> Result<(), DriverError> {
+        self.device.send(&Command::SetChecksum(enabled))
+    }
+
+    /// Reads back current value of every emulator channel.
+    pub fn values(&mut self) -> Result<Values, DriverError> {
+        match self.device.request(Command::GetValues)? {
+            Response::Values(values) => Ok(values),
+            response => Err(DriverError::UnexpectedResponse(format!("{:?}", response))),
+        }
+    }
+
+    pub fn value(&mut self, index: usize) -> Result<u16, DriverError> {
+        let index = self.validate_channel(index)?;
+        let values = self.values()?;
+        values
+            .get(index as usize)
+            .copied()
+            .ok_or(DriverError::InvalidChannel(index as usize, values.len()))
+    }
+
+    pub fn into_inner(self) -> Framed<T> {
+        self.device.into_inner()
+    }
+
+    fn validate_channel(&self, index: usize) -> Result<u8, DriverError> {
+        if index < self.channels_count {
+            Ok(index as u8)
+        } else {
+            Err(DriverError::InvalidChannel(index, self.channels_count))
+        }
+    }
+}
+
+/// Builder of a single channel generator, see [`EmulatorSession::channel`].
+pub struct ChannelBuilder<'a, T: Transport> {
+    session: &'a mut EmulatorSession<T>,
+    index: usize,
+    config: GeneratorConfig,
+}
+
+impl<'a, T: Transport> ChannelBuilder<'a, T> {
+    /// Changes channel value by `step` every `period` frames, this is the default.
+    pub fn sequential(mut self) -> Self {
+        if self.config.period == 0 {
+            self.config.period = 1;
+        }
+        self
+    }
+
+    /// Keeps channel at constant `value`.
+    pub fn constant(mut self, value: u16) -> Self {
+        self.config.value = value;
+        self.config.step = 0;
+        self.config.period = 0;
+        self
+    }
+
+    pub fn from(mut self, value: u16) -> Self {
+        self.config.value = value;
+        self
+    }
+
+    pub fn step(mut self, step: u16) -> Self {
+        self.config.step = step;
+        self
+    }
+
+    pub fn every(mut self, period: u8) -> Self {
+        self.config.period = period;
+        self
+    }
+
+    pub fn config(&self) -> GeneratorConfig {
+        self.config
+    }
+
+    pub fn enable(self) -> Result<(), DriverError> {
+        self.session.enable(self.index, self.config)
+    }
+
+    pub fn disable(self) -> Result<(), DriverError> {
+        self.session.disable(self.index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::base::mock::{self, MockTransport};
+
+    use super::*;
+
+    fn open_session(
+        transport: &MockTransport,
+        channels_count: u8,
+    ) -> EmulatorSession<MockTransport> {
+        let mut values = vec![6, channels_count];
+        values.extend((0..channels_count).flat_map(|_| [0, 0]));
+        transport.push_read(&mock::frame(0, &values));
+        EmulatorSession::open(transport.clone()).expect("Error opening emulator session")
+    }
+
+    #[test]
+    fn read_channels_count_on_open() {
+        let transport = MockTransport::new();
+
+        let session = open_session(&transport, 12);
+
+        assert_eq!(session.channels_count(), 12);
+        assert_eq!(transport.written(), vec![mock::frame(0, &[6])]);
+    }
+
+    #[test]
+    fn enable_sequential_generator() {
+        let transport = MockTransport::new();
+        let mut session = open_session(&transport, 12);
+        transport.take_written();
+
+        session
+            .channel(3)
+            .sequential()
+            .from(100)
+            .step(5)
+            .every(2)
+            .enable()
+            .expect("Error enabling generator");
+
+        assert_eq!(
+            transport.written(),
+            vec![mock::frame(1, &[2, 3, 2, 100, 0, 5, 0])]
+        );
+    }
+
+    #[test]
+    fn enable_constant_generator() {
+        let transport = MockTransport::new();
+        let mut session = open_session(&transport, 12);
+
+        let builder = session.channel(0).constant(0x5555);
+
+        assert_eq!(
+            builder.config(),
+            GeneratorConfig {
+                value: 0x5555,
+                step: 0,
+                period: 0
+            }
+        );
+    }
+
+    #[test]
+    fn reject_channel_out_of_range() {
+        let transport = MockTransport::new();
+        let mut session = open_session(&transport, 4);
+        transport.take_written();
+
+        let result = session.channel(4).sequential().enable();
+
+        assert!(matches!(result, Err(DriverError::InvalidChannel(4, 4))));
+        assert!(transport.written().is_empty());
+    }
+
+    #[test]
+    fn start_and_stop_producer() {
+        let transport = MockTransport::new();
+        let mut session = open_session(&transport, 12);
+        transport.take_written();
+
+        session.start(20).expect("Error starting producer");
+        session.stop().expect("Error stopping producer");
+
+        assert_eq!(
+            transport.written(),
+            vec![mock::frame(1, &[4, 20]), mock::frame(2, &[5])]
+        );
+    }
+
+    #[test]
+    fn read_back_channel_value() {
+        let transport = MockTransport::new();
+        let mut session = open_session(&transport, 2);
+        transport.push_read(&mock::frame(1, &[6, 2, 100, 0, 0x34, 0x12]));
+
+        let value = session.value(1).expect("Error reading channel value");
+
+        assert_eq!(value, 0x1234);
+    }
+
+    #[test]
+    fn reject_response_to_other_request() {
+        let transport = MockTransport::new();
+        let mut session = open_session(&transport, 2);
+        transport.push_read(&mock::frame(1, &[1, 1, 0, 0]));
+
+        let result = session.values();
+
+        assert!(matches!(result, Err(DriverError::UnexpectedResponse(_))));
+    }
+}
@@ -0,0 +1,575 @@
+use std::{fmt, time};
+
+use sm2m_protocol::{
+    bus::{BUS_WIDTH, START_MARKER},
+    generator::{channels::Channels, sequential::SequentialGenerator},
+};
+
+use crate::{
+    base::{framed::Framed, transport::Transport},
+    driver::{UsbDevice, UsbDriver},
+    error::DriverError,
+    info::DeviceRole,
+};
+
+use super::{
+    decoder::DecoderDevice,
+    emulator_session::{EmulatorSession, GeneratorConfig},
+};
+
+/// Frames looked ahead of the last matched one when matching received frames.
+const MAX_FRAME_GAP: usize = 64;
+/// Keeps counters far below `START_MARKER`, so they never split frames.
+const MAX_STAGE_FRAMES: usize = 5000;
+const DRAIN_TIMEOUT: time::Duration = time::Duration::from_millis(100);
+const MAX_DRAIN_TIME: time::Duration = time::Duration::from_secs(1);
+const READ_MARGIN: time::Duration = time::Duration::from_millis(100);
+const RANDOM_ATTEMPTS: usize = 16;
+
+/// Test pattern programmed into emulator channels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pattern {
+    /// Every channel drives a single line high, lines walk across channels.
+    WalkingOnes,
+    /// Every channel drives a single line low, lines walk across channels.
+    WalkingZeros,
+    /// Channels count up by increasing powers of two every frame.
+    Counters,
+    /// Channels run generators with pseudo-random values, steps and periods.
+    PseudoRandom,
+}
+
+impl Pattern {
+    pub const ALL: [Pattern; 4] = [
+        Pattern::WalkingOnes,
+        Pattern::WalkingZeros,
+        Pattern::Counters,
+        Pattern::PseudoRandom,
+    ];
+
+    /// Returns generators of every channel for each stage of the pattern,
+    /// walking patterns need several stages to cover all lines.
+    fn stages(
+        self,
+        channels: usize,
+        frames: usize,
+        random: &mut XorShift,
+    ) -> Vec<Vec<GeneratorConfig>> {
+        match self {
+            Pattern::WalkingOnes | Pattern::WalkingZeros => (0..BUS_WIDTH.div_ceil(channels))
+                .map(|stage| {
+                    (0..channels)
+                        .map(|channel| {
+                            let line = 1u16 << ((stage * channels + channel) % BUS_WIDTH);
+                            let value = if self == Pattern::WalkingOnes {
+                                line
+                            } else {
+                                !line
+                            };
+                            constant(value)
+                        })
+                        .collect()
+                })
+                .collect(),
+            Pattern::Counters => vec![(0..channels)
+                .map(|channel| GeneratorConfig {
+                    value: 0,
+                    step: 1 << (channel % BUS_WIDTH),
+                    period: 1,
+                })
+                .collect()],
+            Pattern::PseudoRandom => vec![(0..channels)
+                .map(|channel| random_config(random, frames).unwrap_or_else(|| walking(channel)))
+                .collect()],
+        }
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::WalkingOnes => "walking ones",
+            Self::WalkingZeros => "walking zeros",
+            Self::Counters => "counters",
+            Self::PseudoRandom => "pseudo-random",
+        };
+        f.write_str(name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoopbackOptions {
+    pub patterns: Vec<Pattern>,
+    /// Frames per second produced by emulator.
+    pub fps: u8,
+    /// Frames collected for every pattern stage.
+    pub frames: usize,
+    /// Seed of the pseudo-random pattern.
+    pub seed: u32,
+}
+
+impl Default for LoopbackOptions {
+    fn default() -> Self {
+        Self {
+            patterns: Pattern::ALL.to_vec(),
+            fps: 100,
+            frames: 100,
+            seed: 0x2545_f491,
+        }
+    }
+}
+
+/// State of a single bus line found by loopback test.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineStatus {
+    Ok,
+    StuckLow,
+    StuckHigh,
+    /// Line reads wrong values only sometimes, e.g. due to crosstalk.
+    Intermittent,
+    /// Line was never driven both low and high.
+    Untested,
+}
+
+impl fmt::Display for LineStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Ok => "ok",
+            Self::StuckLow => "stuck low",
+            Self::StuckHigh => "stuck high",
+            Self::Intermittent => "intermittent",
+            Self::Untested => "untested",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Samples of a single bus line, errors are counted by the expected level.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LineReport {
+    pub expected_high: usize,
+    pub expected_low: usize,
+    pub errors_high: usize,
+    pub errors_low: usize,
+}
+
+impl LineReport {
+    pub fn status(&self) -> LineStatus {
+        if self.errors_high == 0 && self.errors_low == 0 {
+            if self.expected_high > 0 && self.expected_low > 0 {
+                LineStatus::Ok
+            } else {
+                LineStatus::Untested
+            }
+        } else if self.errors_low == 0 && self.errors_high == self.expected_high {
+            LineStatus::StuckLow
+        } else if self.errors_high == 0 && self.errors_low == self.expected_low {
+            LineStatus::StuckHigh
+        } else {
+            LineStatus::Intermittent
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct LoopbackReport {
+    pub lines: [LineReport; BUS_WIDTH],
+    pub frames_received: usize,
+    pub words_checked: usize,
+    pub word_errors: usize,
+    /// Frames produced by emulator and never delivered by decoder.
+    pub dropped_frames: usize,
+    /// Frames with unexpected length or reported as overflow by decoder.
+    pub malformed_frames: usize,
+    /// Time spent collecting frames.
+    pub elapsed: time::Duration,
+}
+
+impl LoopbackReport {
+    pub fn is_passed(&self) -> bool {
+        self.frames_received > 0
+            && self.word_errors == 0
+            && self.dropped_frames == 0
+            && self.malformed_frames == 0
+    }
+
+    pub fn frame_rate(&self) -> f64 {
+        let seconds = self.elapsed.as_secs_f64();
+        if seconds > 0.0 {
+            self.frames_received as f64 / seconds
+        } else {
+            0.0
+        }
+    }
+
+    /// Returns every faulty line with its status.
+    pub fn faults(&self) -> Vec<(usize, LineStatus)> {
+        self.lines
+            .iter()
+            .map(LineReport::status)
+            .enumerate()
+            .filter(|(_, status)| !matches!(status, LineStatus::Ok | LineStatus::Untested))
+            .collect()
+    }
+
+    /// Returns line faults which corrupt `START_MARKER`, decoder never detects
+    /// a frame with any of them, so they are the suspects when no frame is received.
+    pub fn marker_faults() -> Vec<(usize, LineStatus)> {
+        (0..BUS_WIDTH)
+            .map(|line| {
+                if START_MARKER & (1 << line) != 0 {
+                    (line, LineStatus::StuckLow)
+                } else {
+                    (line, LineStatus::StuckHigh)
+                }
+            })
+            .collect()
+    }
+
+    fn check(&mut self, expected: &[u16], received: &[u16]) {
+        self.frames_received += 1;
+        for (expected, received) in expected.iter().zip(received) {
+            self.words_checked += 1;
+            if expected != received {
+                self.word_errors += 1;
+            }
+            for (line, report) in self.lines.iter_mut().enumerate() {
+                let mask = 1 << line;
+                let errors = (expected ^ received) & mask != 0;
+                if expected & mask != 0 {
+                    report.expected_high += 1;
+                    report.errors_high += errors as usize;
+                } else {
+                    report.expected_low += 1;
+                    report.errors_low += errors as usize;
+                }
+            }
+        }
+    }
+}
+
+/// End to end test of emulator and decoder wired back-to-back over the data bus.
+///
+/// Emulator is programmed with known patterns and frames detected by decoder
+/// are compared with the frames emulator is expected to produce.
+pub struct LoopbackTest<D: Transport, E: Transport> {
+    decoder: Framed<D>,
+    emulator: EmulatorSession<E>,
+    options: LoopbackOptions,
+}
+
+impl LoopbackTest<UsbDevice, UsbDevice> {
+    /// Opens the first attached decoder and emulator.
+    pub fn open(driver: &mut UsbDriver, timeout: time::Duration) -> Result<Self, DriverError> {
+        let decoder = driver
+            .find_decoder(timeout)?
+            .ok_or_else(|| DriverError::DeviceNotFound(DeviceRole::Decoder.to_string()))?;
+        let emulator = driver
+            .find_emulator(timeout)?
+            .ok_or_else(|| DriverError::DeviceNotFound(DeviceRole::Emulator.to_string()))?;
+        Self::new(decoder, emulator)
+    }
+}
+
+impl<D: Transport, E: Transport> LoopbackTest<D, E> {
+    pub fn new(decoder: D, emulator: E) -> Result<Self, DriverError> {
+        Ok(Self {
+            decoder: Framed::new(decoder),
+            emulator: EmulatorSession::open(emulator)?,
+            options: LoopbackOptions::default(),
+        })
+    }
+
+    pub fn with_options(mut self, options: LoopbackOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn options(&self) -> &LoopbackOptions {
+        &self.options
+    }
+
+    /// Runs every pattern and leaves emulator producer stopped, the test ends
+    /// early when a stage receives no frame at all. Checksum mode left enabled
+    /// by an earlier session is disabled on both devices.
+    pub fn run(&mut self) -> Result<LoopbackReport, DriverError> {
+        let channels = self.emulator.channels_count();
+        if channels == 0 {
+            return Err(DriverError::InvalidChannel(0, 0));
+        }
+        self.emulator.set_checksum(false)?;
+        self.decoder.set_checksum(false)?;
+
+        let frames = self.options.frames.clamp(1, MAX_STAGE_FRAMES);
+        let mut random = XorShift::new(self.options.seed);
+        let mut report = LoopbackReport::default();
+        for pattern in self.options.patterns.clone() {
+            for configs in pattern.stages(channels, frames, &mut random) {
+                let result = self.run_stage(&configs, frames, &mut report);
+                if result.is_err() {
+                    self.emulator.stop().ok();
+                }
+                if result? == 0 {
+                    // Link is down, other stages would not receive anything either.
+                    return Ok(report);
+                }
+            }
+        }
+        Ok(report)
+    }
+
+    fn run_stage(
+        &mut self,
+        configs: &[GeneratorConfig],
+        frames: usize,
+        report: &mut LoopbackReport,
+    ) -> Result<usize, DriverError> {
+        self.emulator.stop()?;
+        for (index, config) in configs.iter().enumerate() {
+            self.emulator.enable(index, *config)?;
+        }
+        self.drain()?;
+
+        let fps = self.options.fps.max(1);
+        let period = time::Duration::from_secs(1) / fps as u32;
+        let expected = expected_frames(configs, expected_len(frames));
+        self.decoder.set_read_timeout(period * 2 + READ_MARGIN);
+        self.emulator.start(fps)?;
+
+        let started = time::Instant::now();
+        let deadline = started + period * (frames + MAX_FRAME_GAP) as u32 + READ_MARGIN;
+        let mut last = None;
+        let mut received = 0;
+        let mut stale = true;
+        while received < frames && time::Instant::now() < deadline {
+            let frame = match self.decoder.read_params() {
+                Ok(frame) => frame,
+                Err(error) if error.is_timeout() => continue,
+                Err(error) => return Err(error),
+            };
+            // Decoder delivers a frame once the next marker confirms it, so
+            // the last frame of the previous stage arrives with the first marker.
+            if std::mem::take(&mut stale) {
+                continue;
+            }
+            received += 1;
+            if frame.overflow.is_some() || frame.words.len() != configs.len() {
+                report.malformed_frames += 1;
+                continue;
+            }
+
+            let first = last.map_or(0, |last| last + 1);
+            let index = (first..(first + MAX_FRAME_GAP).min(expected.len()))
+                .min_by_key(|index| bit_errors(&expected[*index], &frame.words));
+            let index = match index {
+                Some(index) => index,
+                None => break,
+            };
+            if last.is_some() {
+                report.dropped_frames += index - first;
+            }
+            last = Some(index);
+            report.check(&expected[index], &frame.words);
+        }
+        report.elapsed += started.elapsed();
+        self.emulator.stop()?;
+        Ok(received)
+    }
+
+    /// Skips frames still buffered from the previous stage.
+    fn drain(&mut self) -> Result<(), DriverError> {
+        self.decoder.set_read_timeout(DRAIN_TIMEOUT);
+        let started = time::Instant::now();
+        while started.elapsed() < MAX_DRAIN_TIME {
+            match self.decoder.read_params() {
+                Ok(_) => continue,
+                Err(error) if error.is_timeout() => break,
+                Err(error) => return Err(error),
+            }
+        }
+        Ok(())
+    }
+}
+
+fn constant(value: u16) -> GeneratorConfig {
+    GeneratorConfig {
+        value,
+        step: 0,
+        period: 0,
+    }
+}
+
+fn walking(channel: usize) -> GeneratorConfig {
+    constant(1 << (channel % BUS_WIDTH))
+}
+
+fn random_config(random: &mut XorShift, frames: usize) -> Option<GeneratorConfig> {
+    (0..RANDOM_ATTEMPTS)
+        .map(|_| GeneratorConfig {
+            value: random.next() as u16,
+            step: random.next() as u16 | 1,
+            period: (random.next() % 3 + 1) as u8,
+        })
+        .find(|config| !hits_marker(config, expected_len(frames)))
+}
+
+fn expected_len(frames: usize) -> usize {
+    frames * 2 + MAX_FRAME_GAP
+}
+
+fn hits_marker(config: &GeneratorConfig, frames: usize) -> bool {
+    let mut generator = SequentialGenerator::new(config.value, config.step, config.period);
+    (0..frames).any(|_| {
+        let value = generator.value;
+        generator.generate();
+        value == START_MARKER
+    })
+}
+
+/// Returns frames emulator produces with the given generators, frame `n`
+/// holds values after `n` generator steps.
+fn expected_frames(configs: &[GeneratorConfig], frames: usize) -> Vec<Vec<u16>> {
+    let mut channels = Channels::default();
+    for (index, config) in configs.iter().enumerate() {
+        channels.enable(index as u8, config.period, config.value, config.step);
+    }
+    (0..frames)
+        .map(|_| {
+            let values = channels.values().to_vec();
+            channels.generate();
+            values
+        })
+        .collect()
+}
+
+fn bit_errors(expected: &[u16], received: &[u16]) -> u32 {
+    expected
+        .iter()
+        .zip(received)
+        .map(|(expected, received)| (expected ^ received).count_ones())
+        .sum()
+}
+
+struct XorShift(u32);
+
+impl XorShift {
+    fn new(seed: u32) -> Self {
+        Self(seed.max(1))
+    }
+
+    fn next(&mut self) -> u32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::simulated::{bus::VirtualBus, decoder::VirtualDecoder, emulator::VirtualEmulator};
+
+    use super::*;
+
+    fn options() -> LoopbackOptions {
+        LoopbackOptions {
+            fps: 250,
+            frames: 20,
+            ..LoopbackOptions::default()
+        }
+    }
+
+    fn run(bus: impl FnOnce(&VirtualEmulator) -> VirtualBus) -> LoopbackReport {
+        let emulator = VirtualEmulator::new();
+        let decoder = VirtualDecoder::new(bus(&emulator));
+        let mut test = LoopbackTest::new(decoder, emulator)
+            .unwrap()
+            .with_options(options());
+        test.run().unwrap()
+    }
+
+    #[test]
+    fn pass_healthy_link() {
+        let report = run(|emulator| emulator.bus());
+
+        assert!(report.is_passed(), "{:?}", report);
+        assert!(report.frame_rate() > 0.0);
+        assert!(report
+            .lines
+            .iter()
+            .all(|line| line.status() == LineStatus::Ok));
+    }
+
+    #[test]
+    fn disable_checksum_left_enabled() {
+        let emulator = VirtualEmulator::new();
+        let decoder = VirtualDecoder::new(emulator.bus());
+        let mut test = LoopbackTest::new(decoder, emulator)
+            .unwrap()
+            .with_options(options());
+        test.emulator.set_checksum(true).unwrap();
+
+        let report = test.run().unwrap();
+
+        assert!(report.is_passed(), "{:?}", report);
+    }
+
+    #[test]
+    fn find_stuck_lines() {
+        let report = run(|emulator| emulator.bus().stuck_high(1 << 10).stuck_low(1 << 3));
+
+        assert!(!report.is_passed());
+        assert!(report.word_errors > 0);
+        assert_eq!(
+            report.faults(),
+            vec![(3, LineStatus::StuckLow), (10, LineStatus::StuckHigh)]
+        );
+    }
+
+    #[test]
+    fn receive_nothing_with_broken_marker_line() {
+        let report = run(|emulator| emulator.bus().stuck_low(1 << 10));
+
+        assert_eq!(report.frames_received, 0);
+        assert!(!report.is_passed());
+        assert!(LoopbackReport::marker_faults().contains(&(10, LineStatus::StuckLow)));
+    }
+
+    #[test]
+    fn cover_every_line_with_walking_patterns() {
+        let stages = Pattern::WalkingOnes.stages(12, 10, &mut XorShift::new(1));
+        let lines = stages
+            .iter()
+            .flatten()
+            .fold(0u16, |lines, config| lines | config.value);
+
+        assert_eq!(stages.len(), 2);
+        assert_eq!(lines, u16::MAX);
+    }
+
+    #[test]
+    fn avoid_start_marker_in_random_pattern() {
+        let stages = Pattern::PseudoRandom.stages(12, 100, &mut XorShift::new(7));
+
+        assert!(stages
+            .iter()
+            .flatten()
+            .all(|config| !hits_marker(config, expected_len(100))));
+    }
+
+    #[test]
+    fn classify_line_status() {
+        let line = |expected_high, expected_low, errors_high, errors_low| LineReport {
+            expected_high,
+            expected_low,
+            errors_high,
+            errors_low,
+        };
+
+        assert_eq!(line(5, 5, 0, 0).status(), LineStatus::Ok);
+        assert_eq!(line(5, 5, 5, 0).status(), LineStatus::StuckLow);
+        assert_eq!(line(5, 5, 0, 5).status(), LineStatus::StuckHigh);
+        assert_eq!(line(5, 5, 1, 0).status(), LineStatus::Intermittent);
+        assert_eq!(line(0, 5, 0, 0).status(), LineStatus::Untested);
+    }
+}
@@ -1,2 +1,4 @@
 pub mod decoder;
 pub mod emulator;
+pub mod emulator_session;
+pub mod loopback;
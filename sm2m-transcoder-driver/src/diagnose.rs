@@ -0,0 +1,452 @@
+use std::{
+    ffi::CString,
+    fmt, fs,
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    base::device_lookup_helper::{PID, VID},
+    driver::Backend,
+    error::DriverError,
+    hotplug::DeviceLocation,
+    tty::sysfs,
+};
+
+/// Location udev reads the rules generated by [`udev_rules`] from.
+pub const UDEV_RULES_PATH: &str = "/etc/udev/rules.d/70-sm2m.rules";
+
+const MODEM_MANAGER: &str = "ModemManager";
+
+/// Filesystem roots inspected by [`inspect`], the real system by default.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Roots {
+    pub sysfs: PathBuf,
+    pub dev: PathBuf,
+    pub proc: PathBuf,
+    pub udev_rules: PathBuf,
+}
+
+impl Default for Roots {
+    fn default() -> Self {
+        Self {
+            sysfs: PathBuf::from("/sys"),
+            dev: PathBuf::from("/dev"),
+            proc: PathBuf::from("/proc"),
+            udev_rules: PathBuf::from(UDEV_RULES_PATH),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PortReport {
+    pub path: PathBuf,
+    pub accessible: bool,
+}
+
+/// Attached SM2M device as seen by the kernel.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceReport {
+    pub product: String,
+    pub serial_number: String,
+    pub location: DeviceLocation,
+    /// usbfs node libusb opens the device through.
+    pub node: PathBuf,
+    pub node_accessible: bool,
+    /// Error of opening the device through libusb, filled by `UsbDriver::diagnose`.
+    pub open_error: Option<rusb::Error>,
+    /// Kernel drivers bound to device interfaces, `cdc_acm` provides the tty ports.
+    pub drivers: Vec<String>,
+    pub ports: Vec<PortReport>,
+}
+
+impl DeviceReport {
+    /// Formats location the same way as `DeviceInfo::path`.
+    pub fn path(&self) -> String {
+        let ports = self
+            .location
+            .ports
+            .iter()
+            .map(u8::to_string)
+            .collect::<Vec<_>>()
+            .join(".");
+        format!("{}-{}", self.location.bus, ports)
+    }
+
+    fn name(&self) -> String {
+        format!("{} {}", self.product, self.serial_number)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Problem {
+    NoDevice,
+    AccessDenied { device: String, node: PathBuf },
+    OpenFailed { device: String, error: rusb::Error },
+    NoPort { device: String },
+    PortAccessDenied { device: String, port: PathBuf },
+    ModemManager,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::NoDevice => write!(
+                f,
+                "no SM2M device with USB id {:04x}:{:04x} is attached, check the cable and that the board is not left in DFU mode",
+                VID, PID
+            ),
+            Problem::AccessDenied { device, node } => write!(
+                f,
+                "{} is attached but {} is not readable and writable by the current user, install the udev rules",
+                device,
+                node.display()
+            ),
+            Problem::OpenFailed { device, error } => {
+                write!(f, "{} is attached but libusb can't open it: {}", device, error)
+            }
+            Problem::NoPort { device } => write!(
+                f,
+                "{} has no serial port, the tty backend needs the cdc_acm kernel module, load it with `modprobe cdc_acm`",
+                device
+            ),
+            Problem::PortAccessDenied { device, port } => write!(
+                f,
+                "{} serial port {} is not readable and writable by the current user, install the udev rules",
+                device,
+                port.display()
+            ),
+            Problem::ModemManager => write!(
+                f,
+                "ModemManager is running and probes new serial ports with AT commands, which corrupts the first frames, the udev rules tell it to ignore SM2M devices"
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnosis {
+    pub backend: Backend,
+    pub devices: Vec<DeviceReport>,
+    pub modem_manager: bool,
+    pub udev_rules_installed: bool,
+}
+
+impl Diagnosis {
+    /// Explains why devices can't be used with the diagnosed backend.
+    pub fn problems(&self) -> Vec<Problem> {
+        let mut problems = Vec::new();
+        if self.devices.is_empty() {
+            problems.push(Problem::NoDevice);
+        }
+        for device in &self.devices {
+            match self.backend {
+                Backend::Libusb => match device.open_error {
+                    Some(rusb::Error::Access) => problems.push(Problem::AccessDenied {
+                        device: device.name(),
+                        node: device.node.clone(),
+                    }),
+                    Some(error) => problems.push(Problem::OpenFailed {
+                        device: device.name(),
+                        error,
+                    }),
+                    None if !device.node_accessible => problems.push(Problem::AccessDenied {
+                        device: device.name(),
+                        node: device.node.clone(),
+                    }),
+                    None => {}
+                },
+                Backend::Tty if device.ports.is_empty() => problems.push(Problem::NoPort {
+                    device: device.name(),
+                }),
+                Backend::Tty => {
+                    problems.extend(device.ports.iter().filter(|port| !port.accessible).map(
+                        |port| Problem::PortAccessDenied {
+                            device: device.name(),
+                            port: port.path.clone(),
+                        },
+                    ))
+                }
+            }
+        }
+        if self.modem_manager && !self.udev_rules_installed {
+            problems.push(Problem::ModemManager);
+        }
+        problems
+    }
+}
+
+/// Returns udev rules granting access to SM2M devices and their serial ports
+/// and hiding them from ModemManager, to be installed at [`UDEV_RULES_PATH`].
+pub fn udev_rules() -> String {
+    let access = r#"MODE="0660", GROUP="plugdev", TAG+="uaccess", ENV{ID_MM_DEVICE_IGNORE}="1""#;
+    let usb = format!(
+        r#"SUBSYSTEM=="usb", ATTR{{idVendor}}=="{:04x}", ATTR{{idProduct}}=="{:04x}""#,
+        VID, PID
+    );
+    let tty = format!(
+        r#"SUBSYSTEM=="tty", ATTRS{{idVendor}}=="{:04x}", ATTRS{{idProduct}}=="{:04x}""#,
+        VID, PID
+    );
+    format!(
+        "# SM2M decoders, emulators and encoders, install to {path} and reload with\n\
+         # `udevadm control --reload-rules && udevadm trigger`.\n\
+         {usb}, ATTR{{product}}==\"*SM2M*\", {access}\n\
+         {tty}, ATTRS{{product}}==\"*SM2M*\", {access}\n\
+         # Legacy encoder firmware reports a generic product and its role in the serial number.\n\
+         {usb}, ATTR{{serial}}==\"SM2M-*\", {access}\n\
+         {tty}, ATTRS{{serial}}==\"SM2M-*\", {access}\n",
+        path = UDEV_RULES_PATH,
+        usb = usb,
+        tty = tty,
+        access = access,
+    )
+}
+
+/// Inspects SM2M devices, their kernel drivers and ModemManager under `roots`.
+pub fn inspect(roots: &Roots, backend: Backend) -> Result<Diagnosis, DriverError> {
+    let devices_dir = roots.sysfs.join("bus").join("usb").join("devices");
+    let entries = fs::read_dir(&devices_dir).map_err(DriverError::Sysfs)?;
+    let mut devices = Vec::new();
+    for entry in entries {
+        let entry = entry.map_err(DriverError::Sysfs)?;
+        if entry.file_name().to_string_lossy().contains(':') {
+            continue;
+        }
+        if let Some(device) = read_device(&entry.path(), &roots.dev) {
+            devices.push(device);
+        }
+    }
+
+    devices.sort_by(|left, right| left.location.ports.cmp(&right.location.ports));
+    Ok(Diagnosis {
+        backend,
+        devices,
+        modem_manager: is_running(&roots.proc, MODEM_MANAGER),
+        udev_rules_installed: roots.udev_rules.exists(),
+    })
+}
+
+fn read_device(usb_dir: &Path, dev_root: &Path) -> Option<DeviceReport> {
+    let vendor_id = sysfs::read_hex(usb_dir, "idVendor").ok()?;
+    let product_id = sysfs::read_hex(usb_dir, "idProduct").ok()?;
+    if vendor_id != VID || product_id != PID {
+        return None;
+    }
+
+    let location = DeviceLocation {
+        bus: sysfs::read_decimal(usb_dir, "busnum").ok()?,
+        address: sysfs::read_decimal(usb_dir, "devnum").ok()?,
+        ports: sysfs::parse_devpath(&sysfs::read_string(usb_dir, "devpath").ok()?),
+    };
+    let node = dev_root
+        .join("bus")
+        .join("usb")
+        .join(format!("{:03}", location.bus))
+        .join(format!("{:03}", location.address));
+    let mut drivers = Vec::new();
+    let mut ports = Vec::new();
+    for interface in fs::read_dir(usb_dir).ok()?.flatten() {
+        if !interface.file_name().to_string_lossy().contains(':') {
+            continue;
+        }
+        if let Ok(driver) = fs::read_link(interface.path().join("driver")) {
+            if let Some(name) = driver.file_name() {
+                drivers.push(name.to_string_lossy().into_owned());
+            }
+        }
+        if let Ok(ttys) = fs::read_dir(interface.path().join("tty")) {
+            for tty in ttys.flatten() {
+                let path = dev_root.join(tty.file_name());
+                ports.push(PortReport {
+                    accessible: is_accessible(&path),
+                    path,
+                });
+            }
+        }
+    }
+    drivers.sort();
+    drivers.dedup();
+    ports.sort_by(|left, right| left.path.cmp(&right.path));
+
+    Some(DeviceReport {
+        product: sysfs::read_string(usb_dir, "product").unwrap_or_default(),
+        serial_number: sysfs::read_string(usb_dir, "serial").unwrap_or_default(),
+        node_accessible: is_accessible(&node),
+        node,
+        location,
+        open_error: None,
+        drivers,
+        ports,
+    })
+}
+
+fn is_accessible(path: &Path) -> bool {
+    match CString::new(path.as_os_str().as_bytes()) {
+        Ok(path) => unsafe { libc::access(path.as_ptr(), libc::R_OK | libc::W_OK) == 0 },
+        Err(_) => false,
+    }
+}
+
+fn is_running(proc_root: &Path, name: &str) -> bool {
+    let entries = match fs::read_dir(proc_root) {
+        Ok(entries) => entries,
+        Err(_) => return false,
+    };
+    entries.flatten().any(|entry| {
+        entry.file_name().to_string_lossy().parse::<u32>().is_ok()
+            && sysfs::read_string(&entry.path(), "comm").is_ok_and(|comm| comm == name)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::fs::symlink;
+
+    use super::*;
+
+    fn create_device(root: &Path, devpath: &str, product: &str, driver: Option<&str>) {
+        let usb_dir = root
+            .join("sys/bus/usb/devices")
+            .join(format!("1-{}", devpath));
+        let interface_dir = usb_dir.join(format!("1-{}:1.0", devpath));
+        fs::create_dir_all(&interface_dir).unwrap();
+        fs::write(usb_dir.join("idVendor"), "0483\n").unwrap();
+        fs::write(usb_dir.join("idProduct"), "5740\n").unwrap();
+        fs::write(usb_dir.join("product"), format!("{}\n", product)).unwrap();
+        fs::write(usb_dir.join("serial"), "27003A00\n").unwrap();
+        fs::write(usb_dir.join("busnum"), "1\n").unwrap();
+        fs::write(usb_dir.join("devnum"), "7\n").unwrap();
+        fs::write(usb_dir.join("devpath"), format!("{}\n", devpath)).unwrap();
+        if let Some(driver) = driver {
+            let driver_dir = root.join("sys/bus/usb/drivers").join(driver);
+            fs::create_dir_all(&driver_dir).unwrap();
+            symlink(&driver_dir, interface_dir.join("driver")).unwrap();
+            fs::create_dir_all(interface_dir.join("tty/ttyACM0")).unwrap();
+        }
+    }
+
+    fn roots(root: &Path) -> Roots {
+        Roots {
+            sysfs: root.join("sys"),
+            dev: root.join("dev"),
+            proc: root.join("proc"),
+            udev_rules: root.join("70-sm2m.rules"),
+        }
+    }
+
+    fn report(open_error: Option<rusb::Error>, ports: Vec<PortReport>) -> DeviceReport {
+        DeviceReport {
+            product: "An26 SM2M Decoder".to_owned(),
+            serial_number: "27003A00".to_owned(),
+            location: DeviceLocation {
+                bus: 1,
+                address: 7,
+                ports: vec![2],
+            },
+            node: PathBuf::from("/dev/bus/usb/001/007"),
+            node_accessible: true,
+            open_error,
+            drivers: Vec::new(),
+            ports,
+        }
+    }
+
+    #[test]
+    fn inspect_devices_drivers_and_modem_manager() {
+        let root = tempfile::tempdir().unwrap();
+        create_device(root.path(), "2", "An26 SM2M Decoder", Some("cdc_acm"));
+        let hub = root.path().join("sys/bus/usb/devices/usb1");
+        fs::create_dir_all(&hub).unwrap();
+        fs::write(hub.join("idVendor"), "1d6b\n").unwrap();
+        fs::write(hub.join("idProduct"), "0002\n").unwrap();
+        fs::create_dir_all(root.path().join("proc/812")).unwrap();
+        fs::write(root.path().join("proc/812/comm"), "ModemManager\n").unwrap();
+
+        let diagnosis = inspect(&roots(root.path()), Backend::Tty).unwrap();
+
+        assert_eq!(diagnosis.devices.len(), 1);
+        let device = &diagnosis.devices[0];
+        assert_eq!(device.product, "An26 SM2M Decoder");
+        assert_eq!(device.node, root.path().join("dev/bus/usb/001/007"));
+        assert_eq!(device.drivers, vec!["cdc_acm".to_owned()]);
+        assert_eq!(device.ports[0].path, root.path().join("dev/ttyACM0"));
+        assert!(diagnosis.modem_manager);
+        assert!(!diagnosis.udev_rules_installed);
+    }
+
+    #[test]
+    fn explain_libusb_problems() {
+        let diagnosis = Diagnosis {
+            backend: Backend::Libusb,
+            devices: vec![
+                report(Some(rusb::Error::Access), Vec::new()),
+                report(Some(rusb::Error::Busy), Vec::new()),
+                report(None, Vec::new()),
+            ],
+            modem_manager: true,
+            udev_rules_installed: true,
+        };
+
+        let problems = diagnosis.problems();
+
+        assert_eq!(
+            problems,
+            vec![
+                Problem::AccessDenied {
+                    device: "An26 SM2M Decoder 27003A00".to_owned(),
+                    node: PathBuf::from("/dev/bus/usb/001/007"),
+                },
+                Problem::OpenFailed {
+                    device: "An26 SM2M Decoder 27003A00".to_owned(),
+                    error: rusb::Error::Busy,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn explain_tty_problems() {
+        let port = PortReport {
+            path: PathBuf::from("/dev/ttyACM0"),
+            accessible: false,
+        };
+        let diagnosis = Diagnosis {
+            backend: Backend::Tty,
+            devices: vec![report(None, Vec::new()), report(None, vec![port])],
+            modem_manager: true,
+            udev_rules_installed: false,
+        };
+
+        let problems = diagnosis.problems();
+
+        assert!(matches!(problems[0], Problem::NoPort { .. }));
+        assert!(matches!(problems[1], Problem::PortAccessDenied { .. }));
+        assert_eq!(problems[2], Problem::ModemManager);
+    }
+
+    #[test]
+    fn report_missing_device() {
+        let root = tempfile::tempdir().unwrap();
+        fs::create_dir_all(root.path().join("sys/bus/usb/devices")).unwrap();
+
+        let diagnosis = inspect(&roots(root.path()), Backend::Libusb).unwrap();
+
+        assert_eq!(diagnosis.problems(), vec![Problem::NoDevice]);
+    }
+
+    #[test]
+    fn generate_udev_rules_for_sm2m_devices() {
+        let rules = udev_rules();
+        let rule_lines = rules
+            .lines()
+            .filter(|line| !line.starts_with('#'))
+            .collect::<Vec<_>>();
+
+        assert_eq!(rule_lines.len(), 4);
+        assert!(rule_lines.iter().all(|line| line.contains("\"0483\"")
+            && line.contains("\"5740\"")
+            && line.contains("ENV{ID_MM_DEVICE_IGNORE}=\"1\"")));
+        assert!(rule_lines[0].contains("ATTR{product}==\"*SM2M*\""));
+    }
+}
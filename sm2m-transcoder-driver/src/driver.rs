@@ -1,20 +1,149 @@
 use std::time;
 
+#[cfg(target_os = "linux")]
+use crate::diagnose::{self, Diagnosis, Roots};
+#[cfg(target_os = "linux")]
+use crate::tty::{
+    device::TtyDevice,
+    sysfs::{self, TtyPort},
+};
 use crate::{
-    base::{device_lookup::DeviceLookup, device_lookup_helper},
+    base::{device_lookup::DeviceLookup, device_lookup_helper, transport::Transport},
     error::DriverError,
+    hotplug::{DeviceLocation, HotplugMonitor},
+    info::{self, DeviceInfo, DeviceRole},
 };
 
+const HOTPLUG_POLL_INTERVAL: time::Duration = time::Duration::from_millis(500);
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    /// Claims the CDC data interface through libusb and talks to bulk endpoints directly.
+    #[default]
+    Libusb,
+    /// Opens the `/dev/ttyACM*` node provided by the kernel `cdc_acm` driver.
+    #[cfg(target_os = "linux")]
+    Tty,
+}
+
+pub type LibusbDevice = crate::base::device::Device<rusb::Context>;
+
+pub enum UsbDevice {
+    Libusb(LibusbDevice),
+    #[cfg(target_os = "linux")]
+    Tty(TtyDevice),
+}
+
+impl UsbDevice {
+    pub fn location(&self) -> DeviceLocation {
+        match self {
+            UsbDevice::Libusb(device) => device.location(),
+            #[cfg(target_os = "linux")]
+            UsbDevice::Tty(device) => device.location(),
+        }
+    }
+}
+
+impl Transport for UsbDevice {
+    fn read(&mut self, buf: &mut [u8], timeout: time::Duration) -> Result<usize, DriverError> {
+        match self {
+            UsbDevice::Libusb(device) => device.read(buf, timeout),
+            #[cfg(target_os = "linux")]
+            UsbDevice::Tty(device) => device.read(buf, timeout),
+        }
+    }
+
+    fn write(&mut self, buf: &[u8], timeout: time::Duration) -> Result<usize, DriverError> {
+        match self {
+            UsbDevice::Libusb(device) => device.write(buf, timeout),
+            #[cfg(target_os = "linux")]
+            UsbDevice::Tty(device) => device.write(buf, timeout),
+        }
+    }
+
+    fn reset(&mut self) -> Result<(), DriverError> {
+        match self {
+            UsbDevice::Libusb(device) => device.reset(),
+            #[cfg(target_os = "linux")]
+            UsbDevice::Tty(device) => device.reset(),
+        }
+    }
+
+    fn clear_halt(&mut self) -> Result<(), DriverError> {
+        match self {
+            UsbDevice::Libusb(device) => device.clear_halt(),
+            #[cfg(target_os = "linux")]
+            UsbDevice::Tty(device) => device.clear_halt(),
+        }
+    }
+}
+
+enum Candidate {
+    Libusb(DeviceLookup<rusb::Context>),
+    #[cfg(target_os = "linux")]
+    Tty(TtyPort),
+}
+
+impl Candidate {
+    fn info(&self) -> DeviceInfo {
+        match self {
+            Candidate::Libusb(device_lookup) => DeviceInfo {
+                role: DeviceRole::from_strings(
+                    &device_lookup.product,
+                    &device_lookup.serial_number,
+                ),
+                product: device_lookup.product.clone(),
+                serial_number: device_lookup.serial_number.clone(),
+                bus: device_lookup.bus_number(),
+                ports: device_lookup.port_numbers(),
+                firmware: None,
+                capabilities: None,
+            },
+            #[cfg(target_os = "linux")]
+            Candidate::Tty(port) => DeviceInfo {
+                role: DeviceRole::from_strings(&port.product, &port.serial_number),
+                product: port.product.clone(),
+                serial_number: port.serial_number.clone(),
+                bus: port.location.bus,
+                ports: port.location.ports.clone(),
+                firmware: None,
+                capabilities: None,
+            },
+        }
+    }
+
+    fn open(self) -> Result<UsbDevice, DriverError> {
+        match self {
+            Candidate::Libusb(mut device_lookup) => {
+                let readable_endpoint = device_lookup.find_readable_endpoint()?;
+                let writeable_endpoint = device_lookup.find_writeable_endpoint()?;
+                let device =
+                    LibusbDevice::from(device_lookup, readable_endpoint, writeable_endpoint)?;
+                Ok(UsbDevice::Libusb(device))
+            }
+            #[cfg(target_os = "linux")]
+            Candidate::Tty(port) => Ok(UsbDevice::Tty(TtyDevice::open(&port)?)),
+        }
+    }
+}
+
 pub struct UsbDriver {
     context: rusb::Context,
+    backend: Backend,
 }
 
-pub type UsbDevice = crate::base::device::Device<rusb::Context>;
-
 impl UsbDriver {
     pub fn new() -> Result<Self, DriverError> {
+        Self::with_backend(Backend::default())
+    }
+
+    pub fn with_backend(backend: Backend) -> Result<Self, DriverError> {
         let context = rusb::Context::new().map_err(DriverError::Init)?;
-        Ok(Self { context })
+        Ok(Self { context, backend })
+    }
+
+    pub fn backend(&self) -> Backend {
+        self.backend
     }
 
     pub fn version() -> String {
@@ -32,47 +161,172 @@ impl UsbDriver {
         )
     }
 
+    /// Starts watching SM2M device arrivals and departures. Uses libusb hotplug
+    /// notifications where the platform supports them and falls back to
+    /// periodic bus enumeration otherwise.
+    pub fn hotplug(&self) -> Result<HotplugMonitor, DriverError> {
+        if rusb::has_hotplug() {
+            HotplugMonitor::libusb(self.context.clone())
+        } else {
+            Ok(self.polling_hotplug())
+        }
+    }
+
+    /// Watches SM2M devices by periodic bus enumeration, for platforms where
+    /// registering libusb hotplug notifications fails.
+    pub fn polling_hotplug(&self) -> HotplugMonitor {
+        HotplugMonitor::polling(self.context.clone(), HOTPLUG_POLL_INTERVAL)
+    }
+
+    /// Explains why attached devices can't be opened with the selected
+    /// backend, see [`Diagnosis::problems`].
+    #[cfg(target_os = "linux")]
+    pub fn diagnose(&self) -> Result<Diagnosis, DriverError> {
+        let mut diagnosis = diagnose::inspect(&Roots::default(), self.backend)?;
+        if self.backend == Backend::Libusb {
+            let devices =
+                rusb::UsbContext::devices(&self.context).map_err(DriverError::DeviceList)?;
+            for device in devices.iter() {
+                let location = DeviceLocation::from(&device);
+                if let Some(report) = diagnosis.devices.iter_mut().find(|report| {
+                    report.location.bus == location.bus
+                        && report.location.address == location.address
+                }) {
+                    report.open_error = device.open().err();
+                }
+            }
+        }
+        Ok(diagnosis)
+    }
+
+    /// Lists attached SM2M devices. A failing device is logged and skipped or
+    /// listed without firmware details, it never hides the other devices.
+    pub fn list_devices(
+        &mut self,
+        timeout: time::Duration,
+    ) -> Result<Vec<DeviceInfo>, DriverError> {
+        let candidates = self.candidates(timeout)?;
+        let mut devices = Vec::with_capacity(candidates.len());
+        for candidate in candidates {
+            let mut info = candidate.info();
+            // A device which can't be opened is still listed, without firmware details.
+            match candidate.open() {
+                Ok(mut device) => info::identify(&mut device, &mut info),
+                Err(error) => warn!(
+                    "failed to open {:?} with serial {:?} on bus {} ports {:?}: {}",
+                    info.role, info.serial_number, info.bus, info.ports, error
+                ),
+            }
+            devices.push(info);
+        }
+        Ok(devices)
+    }
+
+    pub fn open(
+        &mut self,
+        info: &DeviceInfo,
+        timeout: time::Duration,
+    ) -> Result<UsbDevice, DriverError> {
+        let device = self.find_device(timeout, |candidate| {
+            candidate.serial_number == info.serial_number
+                && candidate.bus == info.bus
+                && candidate.ports == info.ports
+        })?;
+        device.ok_or_else(|| DriverError::DeviceNotFound(info.serial_number.clone()))
+    }
+
+    pub fn open_by_serial(
+        &mut self,
+        serial_number: &str,
+        timeout: time::Duration,
+    ) -> Result<Option<UsbDevice>, DriverError> {
+        self.find_device(timeout, |candidate| {
+            candidate.serial_number == serial_number
+        })
+    }
+
     pub fn find_emulator(
         &mut self,
         timeout: time::Duration,
     ) -> Result<Option<UsbDevice>, DriverError> {
-        let device_lookup =
-            device_lookup_helper::find_device(&mut self.context, "SM2M-EMULATOR", timeout)?;
-        match device_lookup {
-            Some(device_lookup) => Ok(Some(Self::create_device(device_lookup)?)),
-            None => Ok(None),
-        }
+        self.find_device(timeout, |candidate| candidate.role == DeviceRole::Emulator)
     }
 
     pub fn find_decoder(
         &mut self,
         timeout: time::Duration,
     ) -> Result<Option<UsbDevice>, DriverError> {
-        let device_lookup =
-            device_lookup_helper::find_device(&mut self.context, "SM2M-DECODER", timeout)?;
-        match device_lookup {
-            Some(device_lookup) => Ok(Some(Self::create_device(device_lookup)?)),
-            None => Ok(None),
-        }
+        self.find_device(timeout, |candidate| candidate.role == DeviceRole::Decoder)
     }
 
     pub fn find_encoder(
         &mut self,
         timeout: time::Duration,
     ) -> Result<Option<UsbDevice>, DriverError> {
-        let device_lookup =
-            device_lookup_helper::find_device(&mut self.context, "SM2M-ENCODER", timeout)?;
-        match device_lookup {
-            Some(device_lookup) => Ok(Some(Self::create_device(device_lookup)?)),
+        self.find_device(timeout, |candidate| candidate.role == DeviceRole::Encoder)
+    }
+
+    fn find_device<P>(
+        &mut self,
+        timeout: time::Duration,
+        predicate: P,
+    ) -> Result<Option<UsbDevice>, DriverError>
+    where
+        P: Fn(&DeviceInfo) -> bool,
+    {
+        let candidate = self.candidates(timeout)?.into_iter().find(|candidate| {
+            let info = candidate.info();
+            let matches = predicate(&info);
+            debug!(
+                "{} {:?} {:?} with serial {:?} on bus {} ports {:?}",
+                if matches { "selected" } else { "skipped" },
+                info.role,
+                info.product,
+                info.serial_number,
+                info.bus,
+                info.ports
+            );
+            matches
+        });
+        match candidate {
+            Some(candidate) => {
+                let role = candidate.info().role;
+                let mut device = candidate.open()?;
+                info::negotiate(&mut device, role)?;
+                Ok(Some(device))
+            }
             None => Ok(None),
         }
     }
 
-    fn create_device(
-        mut device_lookup: DeviceLookup<rusb::Context>,
-    ) -> Result<UsbDevice, DriverError> {
-        let readable_endpoint = device_lookup.find_readable_endpoint()?;
-        let writeable_endpoint = device_lookup.find_writeable_endpoint()?;
-        UsbDevice::from(device_lookup, readable_endpoint, writeable_endpoint)
+    fn candidates(&mut self, timeout: time::Duration) -> Result<Vec<Candidate>, DriverError> {
+        match self.backend {
+            Backend::Libusb => {
+                let device_lookups =
+                    device_lookup_helper::list_devices(&mut self.context, timeout)?;
+                Ok(device_lookups.into_iter().map(Candidate::Libusb).collect())
+            }
+            #[cfg(target_os = "linux")]
+            Backend::Tty => {
+                let ports = sysfs::list_ports()?;
+                Ok(ports
+                    .into_iter()
+                    .filter(|port| {
+                        let accepted = port.vendor_id == device_lookup_helper::VID
+                            && port.product_id == device_lookup_helper::PID;
+                        debug!(
+                            "{} {:04x}:{:04x} port {}, serial {:?}",
+                            if accepted { "accepted" } else { "rejected" },
+                            port.vendor_id,
+                            port.product_id,
+                            port.path.display(),
+                            port.serial_number
+                        );
+                        accepted
+                    })
+                    .map(Candidate::Tty)
+                    .collect())
+            }
+        }
     }
 }
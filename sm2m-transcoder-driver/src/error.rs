@@ -1,3 +1,6 @@
+use std::io;
+
+use sm2m_protocol::{frame::FrameError, packet::PacketError};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -6,14 +9,20 @@ pub enum DriverError {
     Init(#[source] rusb::Error),
     #[error("can't read USB device list, reason: {0}")]
     DeviceList(#[source] rusb::Error),
+    #[error("can't register USB hotplug callback, reason: {0}")]
+    Hotplug(#[source] rusb::Error),
     #[error("can't read device descriptor, reason: {0}")]
     ReadDeviceDescriptor(#[source] rusb::Error),
     #[error("can't open device {1}:{2}, reason: {0}")]
     OpenDevice(#[source] rusb::Error, u16, u16),
     #[error("can't read serial number for device {1}:{2}, reason: {0}")]
     SerialNumber(#[source] rusb::Error, u16, u16),
+    #[error("can't read product string for device {1}:{2}, reason: {0}")]
+    ProductString(#[source] rusb::Error, u16, u16),
     #[error("can't read languages for device {1}:{2}, reason: {0}")]
     ReadLanguages(#[source] rusb::Error, u16, u16),
+    #[error("SM2M device {0} is not attached")]
+    DeviceNotFound(String),
     #[error("USB device {0}:{1} does not have readable endpoint")]
     NoReadableEndpoint(u16, u16),
     #[error("USB device {0}:{1} does not have writeable endpoint")]
@@ -34,12 +43,171 @@ pub enum DriverError {
     EnableEndpoint(#[source] rusb::Error, u8, u16, u16),
     #[error("can't reset device {1}:{2}, reason: {0}")]
     Reset(#[source] rusb::Error, u16, u16),
+    #[error("can't clear halt of endpoint {1}, reason: {0}")]
+    ClearHalt(#[source] rusb::Error, u8),
     #[error("can't read from USB interface {1}, reason: {0}")]
     Read(#[source] rusb::Error, u8),
     #[error("can't write to USB interface {1}, reason: {0}")]
     Write(#[source] rusb::Error, u8),
-    #[error("unsupported input transfer type {:?} for address {1}")]
+    #[error("can't encode frame, reason: {0:?}")]
+    EncodeFrame(FrameError),
+    #[error("can't encode packet, reason: {0:?}")]
+    EncodePacket(PacketError),
+    #[error("can't decode packet, reason: {0:?}")]
+    DecodePacket(PacketError),
+    #[error("no complete frame received within {0:?}")]
+    FrameTimeout(std::time::Duration),
+    #[error("operation did not complete within {0:?}")]
+    Timeout(std::time::Duration),
+    #[error("unexpected response {0}")]
+    UnexpectedResponse(String),
+    #[error("device firmware speaks protocol revision {device}, host requires revision {host}")]
+    IncompatibleFirmware { device: u8, host: u8 },
+    #[error("emulator channel {0} is out of range, emulator has {1} channels")]
+    InvalidChannel(usize, usize),
+    #[error("unsupported input transfer type {0:?} for address {1}")]
     UnsupportedInputTransferType(rusb::TransferType, u8),
-    #[error("unsupported output transfer type {:?} for address {1}")]
+    #[error("unsupported output transfer type {0:?} for address {1}")]
     UnsupportedOutputTransferType(rusb::TransferType, u8),
+    #[error("can't access capture, reason: {0}")]
+    Capture(#[source] io::Error),
+    #[error("invalid capture, reason: {0}")]
+    InvalidCapture(String),
+    #[error("capture replay reached the end of capture")]
+    CaptureEnded,
+    #[error("can't read sysfs device list, reason: {0}")]
+    Sysfs(#[source] io::Error),
+    #[error("can't open serial port {1}, reason: {0}")]
+    OpenPort(#[source] io::Error, String),
+    #[error("can't configure serial port {1}, reason: {0}")]
+    ConfigurePort(#[source] io::Error, String),
+    #[error("can't read from serial port {1}, reason: {0}")]
+    ReadPort(#[source] io::Error, String),
+    #[error("can't write to serial port {1}, reason: {0}")]
+    WritePort(#[source] io::Error, String),
+    #[error("can't open pseudo-terminal, reason: {0}")]
+    OpenPty(#[source] io::Error),
+    #[error("can't read channel map, reason: {0}")]
+    ReadChannelMap(#[source] io::Error),
+    #[error("invalid channel map, reason: {0}")]
+    InvalidChannelMap(String),
+    #[error("frame of {0} words is shorter than {1} words required by channel map")]
+    FrameTooShort(usize, usize),
+    #[error("frame of {0} words exceeds {1} words decoder is able to receive")]
+    FrameOverflow(u8, u8),
+}
+
+/// How a failed operation affects the device, see [`DriverError::class`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorClass {
+    /// Operation ran out of time, the device is usable and the operation can be retried.
+    Timeout,
+    /// Endpoint is halted, clearing the halt makes it usable again.
+    Stall,
+    /// Device is gone and has to be opened again once it is back.
+    Disconnected,
+    /// Device is attached but misbehaves, resetting it may help.
+    Failure,
+}
+
+impl DriverError {
+    pub fn class(&self) -> ErrorClass {
+        if self.is_timeout() {
+            return ErrorClass::Timeout;
+        }
+
+        match self {
+            DriverError::Read(rusb::Error::Pipe, _) | DriverError::Write(rusb::Error::Pipe, _) => {
+                ErrorClass::Stall
+            }
+            DriverError::Read(rusb::Error::NoDevice, _)
+            | DriverError::Write(rusb::Error::NoDevice, _)
+            | DriverError::Reset(rusb::Error::NoDevice, ..)
+            | DriverError::ClearHalt(rusb::Error::NoDevice, _)
+            | DriverError::OpenDevice(rusb::Error::NoDevice, ..)
+            | DriverError::DeviceNotFound(_) => ErrorClass::Disconnected,
+            DriverError::OpenPort(error, _)
+            | DriverError::ConfigurePort(error, _)
+            | DriverError::ReadPort(error, _)
+            | DriverError::WritePort(error, _)
+                if is_port_gone(error) =>
+            {
+                ErrorClass::Disconnected
+            }
+            _ => ErrorClass::Failure,
+        }
+    }
+
+    /// Returns `true` when the operation ran out of time without data, the
+    /// device is still usable and the operation can be retried.
+    pub fn is_timeout(&self) -> bool {
+        match self {
+            DriverError::Read(rusb::Error::Timeout, _)
+            | DriverError::Write(rusb::Error::Timeout, _)
+            | DriverError::FrameTimeout(_)
+            | DriverError::Timeout(_) => true,
+            DriverError::ReadPort(error, _) | DriverError::WritePort(error, _) => {
+                error.kind() == io::ErrorKind::TimedOut
+            }
+            _ => false,
+        }
+    }
+}
+
+fn is_port_gone(error: &io::Error) -> bool {
+    #[cfg(target_os = "linux")]
+    if matches!(
+        error.raw_os_error(),
+        Some(libc::ENODEV) | Some(libc::ENXIO) | Some(libc::EIO)
+    ) {
+        return true;
+    }
+    error.kind() == io::ErrorKind::NotFound
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classify_errors() {
+        let class = |error: DriverError| error.class();
+
+        assert_eq!(
+            class(DriverError::Read(rusb::Error::Timeout, 1)),
+            ErrorClass::Timeout
+        );
+        assert_eq!(
+            class(DriverError::Write(rusb::Error::Pipe, 1)),
+            ErrorClass::Stall
+        );
+        assert_eq!(
+            class(DriverError::Read(rusb::Error::NoDevice, 1)),
+            ErrorClass::Disconnected
+        );
+        assert_eq!(
+            class(DriverError::DeviceNotFound("1".to_owned())),
+            ErrorClass::Disconnected
+        );
+        assert_eq!(
+            class(DriverError::Read(rusb::Error::Io, 1)),
+            ErrorClass::Failure
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn classify_missing_port_as_disconnected() {
+        let error = io::Error::from_raw_os_error(libc::EIO);
+        let timeout = io::Error::from(io::ErrorKind::TimedOut);
+
+        assert_eq!(
+            DriverError::ReadPort(error, "ttyACM0".to_owned()).class(),
+            ErrorClass::Disconnected
+        );
+        assert_eq!(
+            DriverError::ReadPort(timeout, "ttyACM0".to_owned()).class(),
+            ErrorClass::Timeout
+        );
+    }
 }
@@ -0,0 +1,505 @@
+//! C API over decoders, declared in `include/sm2m.h`.
+//!
+//! Functions never unwind into the caller. They return `SM2M_OK` or a
+//! non-negative count on success and one of the negative `Sm2mError` codes on
+//! failure, the message of the last failure on the calling thread is available
+//! through `sm2m_last_error`.
+
+use std::{
+    cell::RefCell,
+    ffi::{CStr, CString},
+    os::raw::c_char,
+    panic::{self, AssertUnwindSafe},
+    ptr, slice, time,
+};
+
+use crate::{
+    base::{framed::Framed, transport::Transport},
+    devices::decoder::DecoderDevice,
+    driver::UsbDriver,
+    error::{DriverError, ErrorClass},
+    info::{DeviceInfo, DeviceRole},
+    simulated::decoder::VirtualDecoder,
+};
+
+const OPEN_TIMEOUT: time::Duration = time::Duration::from_secs(1);
+const VIRTUAL_FRAME_WORDS: u16 = 8;
+const VERSION: &[u8] = concat!(env!("CARGO_PKG_VERSION"), "\0").as_bytes();
+
+#[repr(i32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sm2mError {
+    Ok = 0,
+    InvalidArgument = -1,
+    NotFound = -2,
+    Timeout = -3,
+    Disconnected = -4,
+    Stall = -5,
+    IncompatibleFirmware = -6,
+    Protocol = -7,
+    BufferTooSmall = -8,
+    FrameOverflow = -9,
+    Io = -10,
+    Panic = -11,
+    CorruptedFrame = -12,
+}
+
+impl From<&DriverError> for Sm2mError {
+    fn from(error: &DriverError) -> Self {
+        match error {
+            DriverError::DeviceNotFound(_) => Sm2mError::NotFound,
+            DriverError::IncompatibleFirmware { .. } => Sm2mError::IncompatibleFirmware,
+            DriverError::EncodeFrame(_)
+            | DriverError::EncodePacket(_)
+            | DriverError::DecodePacket(_)
+            | DriverError::UnexpectedResponse(_) => Sm2mError::Protocol,
+            error => match error.class() {
+                ErrorClass::Timeout => Sm2mError::Timeout,
+                ErrorClass::Stall => Sm2mError::Stall,
+                ErrorClass::Disconnected => Sm2mError::Disconnected,
+                ErrorClass::Failure => Sm2mError::Io,
+            },
+        }
+    }
+}
+
+struct Failure {
+    code: Sm2mError,
+    message: String,
+}
+
+impl Failure {
+    fn new(code: Sm2mError, message: &str) -> Self {
+        Self {
+            code,
+            message: message.to_owned(),
+        }
+    }
+}
+
+impl From<DriverError> for Failure {
+    fn from(error: DriverError) -> Self {
+        Self {
+            code: Sm2mError::from(&error),
+            message: error.to_string(),
+        }
+    }
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Runs `operation` without letting panics cross the C boundary and records
+/// the failure message for `sm2m_last_error`.
+fn call<F>(operation: F) -> i32
+where
+    F: FnOnce() -> Result<i32, Failure>,
+{
+    let failure = match panic::catch_unwind(AssertUnwindSafe(operation)) {
+        Ok(Ok(result)) => return result,
+        Ok(Err(failure)) => failure,
+        Err(_) => Failure::new(Sm2mError::Panic, "driver panicked"),
+    };
+    let message = CString::new(failure.message.replace('\0', " ")).ok();
+    LAST_ERROR.with(|last_error| *last_error.borrow_mut() = message);
+    failure.code as i32
+}
+
+#[repr(i32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sm2mRole {
+    Unknown = 0,
+    Decoder = 1,
+    Emulator = 2,
+    Encoder = 3,
+}
+
+impl From<DeviceRole> for Sm2mRole {
+    fn from(role: DeviceRole) -> Self {
+        match role {
+            DeviceRole::Decoder => Sm2mRole::Decoder,
+            DeviceRole::Emulator => Sm2mRole::Emulator,
+            DeviceRole::Encoder => Sm2mRole::Encoder,
+            DeviceRole::Unknown => Sm2mRole::Unknown,
+        }
+    }
+}
+
+/// Attached device, strings are NUL terminated and truncated to fit.
+#[repr(C)]
+pub struct Sm2mDeviceInfo {
+    pub role: Sm2mRole,
+    pub serial_number: [c_char; 64],
+    pub product: [c_char; 64],
+    pub bus: u8,
+    /// `false` when firmware protocol revision does not match the driver.
+    pub compatible: bool,
+}
+
+impl From<&DeviceInfo> for Sm2mDeviceInfo {
+    fn from(info: &DeviceInfo) -> Self {
+        let mut device = Self {
+            role: info.role.into(),
+            serial_number: [0; 64],
+            product: [0; 64],
+            bus: info.bus,
+            compatible: info.capabilities.is_some(),
+        };
+        copy_string(&info.serial_number, &mut device.serial_number);
+        copy_string(&info.product, &mut device.product);
+        device
+    }
+}
+
+fn copy_string(value: &str, buf: &mut [c_char]) {
+    let size = value.len().min(buf.len() - 1);
+    for (target, byte) in buf.iter_mut().zip(&value.as_bytes()[..size]) {
+        *target = *byte as c_char;
+    }
+    buf[size] = 0;
+}
+
+/// Opened decoder, owned by the caller until `sm2m_close`.
+pub struct Sm2mDecoder {
+    device: Framed<Box<dyn Transport + Send>>,
+}
+
+impl Sm2mDecoder {
+    fn into_raw<T: Transport + Send + 'static>(transport: T) -> *mut Self {
+        let transport: Box<dyn Transport + Send> = Box::new(transport);
+        Box::into_raw(Box::new(Self {
+            device: Framed::new(transport),
+        }))
+    }
+}
+
+/// Returns driver version as a static NUL terminated string.
+#[no_mangle]
+pub extern "C" fn sm2m_version() -> *const c_char {
+    VERSION.as_ptr() as *const c_char
+}
+
+/// Returns message of the last failure on the calling thread, `NULL` when
+/// nothing failed yet. The message stays valid until the next failure.
+#[no_mangle]
+pub extern "C" fn sm2m_last_error() -> *const c_char {
+    LAST_ERROR.with(|last_error| {
+        last_error
+            .borrow()
+            .as_ref()
+            .map_or(ptr::null(), |message| message.as_ptr())
+    })
+}
+
+/// Fills up to `capacity` entries of `devices` and returns number of attached
+/// devices, which may exceed `capacity`.
+///
+/// # Safety
+///
+/// `devices` must point to `capacity` writable entries or be `NULL` when
+/// `capacity` is zero.
+#[no_mangle]
+pub unsafe extern "C" fn sm2m_list(devices: *mut Sm2mDeviceInfo, capacity: usize) -> i32 {
+    call(|| {
+        if devices.is_null() && capacity > 0 {
+            return Err(Failure::new(Sm2mError::InvalidArgument, "devices is NULL"));
+        }
+
+        let found = UsbDriver::new()?.list_devices(OPEN_TIMEOUT)?;
+        for (index, info) in found.iter().take(capacity).enumerate() {
+            devices.add(index).write(Sm2mDeviceInfo::from(info));
+        }
+        Ok(found.len().min(i32::MAX as usize) as i32)
+    })
+}
+
+/// Opens decoder with `serial_number`, or the first attached decoder when it
+/// is `NULL`, and stores its handle into `decoder`.
+///
+/// # Safety
+///
+/// `serial_number` must be `NULL` or a NUL terminated string, `decoder` must
+/// point to writable handle.
+#[no_mangle]
+pub unsafe extern "C" fn sm2m_open_decoder(
+    serial_number: *const c_char,
+    decoder: *mut *mut Sm2mDecoder,
+) -> i32 {
+    call(|| {
+        if decoder.is_null() {
+            return Err(Failure::new(Sm2mError::InvalidArgument, "decoder is NULL"));
+        }
+        let serial_number = if serial_number.is_null() {
+            None
+        } else {
+            Some(CStr::from_ptr(serial_number).to_str().map_err(|_| {
+                Failure::new(Sm2mError::InvalidArgument, "serial number is not UTF-8")
+            })?)
+        };
+
+        let mut driver = UsbDriver::new()?;
+        let info = driver
+            .list_devices(OPEN_TIMEOUT)?
+            .into_iter()
+            .find(|info| {
+                info.role == DeviceRole::Decoder
+                    && serial_number.is_none_or(|serial| info.serial_number == serial)
+            })
+            .ok_or_else(|| {
+                DriverError::DeviceNotFound(serial_number.unwrap_or("decoder").to_owned())
+            })?;
+        let device = driver.open(&info, OPEN_TIMEOUT)?;
+        decoder.write(Sm2mDecoder::into_raw(device));
+        Ok(Sm2mError::Ok as i32)
+    })
+}
+
+/// Opens software decoder streaming `fps` frames per second, as fast as they
+/// are read when zero. Frame `n` holds 8 words counting up from `n`, so
+/// applications can be tested without hardware.
+///
+/// # Safety
+///
+/// `decoder` must point to writable handle.
+#[no_mangle]
+pub unsafe extern "C" fn sm2m_open_virtual_decoder(
+    fps: u32,
+    decoder: *mut *mut Sm2mDecoder,
+) -> i32 {
+    call(|| {
+        if decoder.is_null() {
+            return Err(Failure::new(Sm2mError::InvalidArgument, "decoder is NULL"));
+        }
+
+        let source = (0u32..).map(|frame| {
+            (0..VIRTUAL_FRAME_WORDS)
+                .map(|word| (frame as u16).wrapping_add(word))
+                .collect()
+        });
+        decoder.write(Sm2mDecoder::into_raw(
+            VirtualDecoder::new(source).with_rate(fps),
+        ));
+        Ok(Sm2mError::Ok as i32)
+    })
+}
+
+/// Makes decoder verify and strip checksum word trailing every frame, the
+/// emulator has to produce it. Keep it disabled with a real SM2M computer.
+///
+/// # Safety
+///
+/// `decoder` must be a handle returned by one of the open functions and not
+/// closed yet.
+#[no_mangle]
+pub unsafe extern "C" fn sm2m_set_checksum(decoder: *mut Sm2mDecoder, enabled: bool) -> i32 {
+    call(|| {
+        let decoder = decoder
+            .as_mut()
+            .ok_or_else(|| Failure::new(Sm2mError::InvalidArgument, "decoder is NULL"))?;
+        decoder.device.set_checksum(enabled)?;
+        Ok(Sm2mError::Ok as i32)
+    })
+}
+
+/// Waits up to `timeout_ms` for the next parameters frame, copies its words
+/// into `words` and returns their count.
+///
+/// A frame longer than `capacity` is dropped with `SM2M_ERROR_BUFFER_TOO_SMALL`,
+/// a frame the decoder could not hold is reported as `SM2M_ERROR_FRAME_OVERFLOW`
+/// and a frame dropped because of checksum mismatch as `SM2M_ERROR_CORRUPTED_FRAME`.
+///
+/// # Safety
+///
+/// `decoder` must be a handle returned by one of the open functions and not
+/// closed yet, `words` must point to `capacity` writable words.
+#[no_mangle]
+pub unsafe extern "C" fn sm2m_read_frame(
+    decoder: *mut Sm2mDecoder,
+    words: *mut u16,
+    capacity: usize,
+    timeout_ms: u32,
+) -> i32 {
+    call(|| {
+        let decoder = decoder
+            .as_mut()
+            .ok_or_else(|| Failure::new(Sm2mError::InvalidArgument, "decoder is NULL"))?;
+        if words.is_null() && capacity > 0 {
+            return Err(Failure::new(Sm2mError::InvalidArgument, "words is NULL"));
+        }
+
+        let timeout = time::Duration::from_millis(timeout_ms.into());
+        decoder.device.set_read_timeout(timeout);
+        let frame = decoder.device.read_params()?;
+        if let Some(overflow) = frame.overflow {
+            return Err(Failure {
+                code: Sm2mError::FrameOverflow,
+                message: format!(
+                    "decoder expected {} words, received {}",
+                    overflow.expected, overflow.received
+                ),
+            });
+        }
+        if let Some(dropped) = frame.corrupted {
+            return Err(Failure {
+                code: Sm2mError::CorruptedFrame,
+                message: format!("decoder dropped {} corrupted frames", dropped),
+            });
+        }
+        if frame.words.len() > capacity {
+            return Err(Failure {
+                code: Sm2mError::BufferTooSmall,
+                message: format!(
+                    "frame of {} words does not fit into {} words",
+                    frame.words.len(),
+                    capacity
+                ),
+            });
+        }
+        if !frame.words.is_empty() {
+            slice::from_raw_parts_mut(words, frame.words.len()).copy_from_slice(&frame.words);
+        }
+        Ok(frame.words.len() as i32)
+    })
+}
+
+/// Closes decoder and frees its handle, `NULL` is ignored.
+///
+/// # Safety
+///
+/// `decoder` must be a handle returned by one of the open functions and is
+/// invalid after the call.
+#[no_mangle]
+pub unsafe extern "C" fn sm2m_close(decoder: *mut Sm2mDecoder) {
+    if !decoder.is_null() {
+        call(|| {
+            drop(Box::from_raw(decoder));
+            Ok(Sm2mError::Ok as i32)
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sm2m_protocol::packet::PacketError;
+
+    use super::*;
+
+    fn open_virtual(fps: u32) -> *mut Sm2mDecoder {
+        let mut decoder = ptr::null_mut();
+        let result = unsafe { sm2m_open_virtual_decoder(fps, &mut decoder) };
+        assert_eq!(result, Sm2mError::Ok as i32);
+        decoder
+    }
+
+    fn last_error() -> String {
+        unsafe { CStr::from_ptr(sm2m_last_error()) }
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    #[test]
+    fn read_frames_from_virtual_decoder() {
+        let decoder = open_virtual(0);
+        let mut words = [0u16; 16];
+
+        let first = unsafe { sm2m_read_frame(decoder, words.as_mut_ptr(), words.len(), 100) };
+        let second = unsafe { sm2m_read_frame(decoder, words.as_mut_ptr(), words.len(), 100) };
+        unsafe { sm2m_close(decoder) };
+
+        assert_eq!((first, second), (8, 8));
+        assert_eq!(words[..8], [1, 2, 3, 4, 5, 6, 7, 8]);
+    }
+
+    #[test]
+    fn reject_small_buffer() {
+        let decoder = open_virtual(0);
+        let mut words = [0u16; 4];
+
+        let result = unsafe { sm2m_read_frame(decoder, words.as_mut_ptr(), words.len(), 100) };
+        unsafe { sm2m_close(decoder) };
+
+        assert_eq!(result, Sm2mError::BufferTooSmall as i32);
+        assert_eq!(last_error(), "frame of 8 words does not fit into 4 words");
+    }
+
+    #[test]
+    fn report_corrupted_frames() {
+        let decoder = open_virtual(0);
+        let mut words = [0u16; 16];
+
+        let enabled = unsafe { sm2m_set_checksum(decoder, true) };
+        let result = unsafe { sm2m_read_frame(decoder, words.as_mut_ptr(), words.len(), 100) };
+        unsafe { sm2m_close(decoder) };
+
+        assert_eq!(enabled, Sm2mError::Ok as i32);
+        assert_eq!(result, Sm2mError::CorruptedFrame as i32);
+        assert_eq!(last_error(), "decoder dropped 1 corrupted frames");
+    }
+
+    #[test]
+    fn reject_null_arguments() {
+        let mut words = [0u16; 4];
+
+        let read = unsafe { sm2m_read_frame(ptr::null_mut(), words.as_mut_ptr(), 4, 100) };
+        let open = unsafe { sm2m_open_virtual_decoder(0, ptr::null_mut()) };
+        unsafe { sm2m_close(ptr::null_mut()) };
+
+        assert_eq!(read, Sm2mError::InvalidArgument as i32);
+        assert_eq!(open, Sm2mError::InvalidArgument as i32);
+        assert_eq!(last_error(), "decoder is NULL");
+    }
+
+    #[test]
+    fn map_driver_errors() {
+        let cases = [
+            (
+                DriverError::DeviceNotFound("1".to_owned()),
+                Sm2mError::NotFound,
+            ),
+            (
+                DriverError::IncompatibleFirmware { device: 1, host: 2 },
+                Sm2mError::IncompatibleFirmware,
+            ),
+            (
+                DriverError::DecodePacket(PacketError::UnknownOpcode(0)),
+                Sm2mError::Protocol,
+            ),
+            (
+                DriverError::FrameTimeout(time::Duration::ZERO),
+                Sm2mError::Timeout,
+            ),
+            (DriverError::Read(rusb::Error::Pipe, 1), Sm2mError::Stall),
+            (
+                DriverError::Read(rusb::Error::NoDevice, 1),
+                Sm2mError::Disconnected,
+            ),
+            (DriverError::Read(rusb::Error::Io, 1), Sm2mError::Io),
+        ];
+
+        for (error, code) in cases.iter() {
+            assert_eq!(Sm2mError::from(error), *code, "{}", error);
+        }
+    }
+
+    #[test]
+    fn declare_every_function_in_header() {
+        let header = include_str!("../include/sm2m.h");
+        let source = include_str!("ffi.rs");
+        let functions = source
+            .split("extern \"C\" fn ")
+            .skip(1)
+            .map(|rest| rest.split('(').next().unwrap());
+
+        for function in functions {
+            assert!(header.contains(&format!("{}(", function)), "{}", function);
+        }
+    }
+
+    #[test]
+    fn return_version() {
+        let version = unsafe { CStr::from_ptr(sm2m_version()) };
+
+        assert_eq!(version.to_str().unwrap(), UsbDriver::version());
+    }
+}
@@ -0,0 +1,285 @@
+use std::{
+    collections::HashSet,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc,
+    },
+    thread, time,
+};
+
+use rusb::UsbContext;
+
+use crate::{
+    base::device_lookup_helper::{self, PID, VID},
+    error::DriverError,
+};
+
+const EVENTS_TIMEOUT: time::Duration = time::Duration::from_millis(100);
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DeviceLocation {
+    pub bus: u8,
+    pub address: u8,
+    pub ports: Vec<u8>,
+}
+
+impl<T: rusb::UsbContext> From<&rusb::Device<T>> for DeviceLocation {
+    fn from(device: &rusb::Device<T>) -> Self {
+        Self {
+            bus: device.bus_number(),
+            address: device.address(),
+            ports: device.port_numbers().unwrap_or_default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HotplugEvent {
+    Arrived(DeviceLocation),
+    Left(DeviceLocation),
+}
+
+pub trait DeviceEnumerator: Send + 'static {
+    fn enumerate(&mut self) -> Result<Vec<DeviceLocation>, DriverError>;
+}
+
+impl DeviceEnumerator for rusb::Context {
+    fn enumerate(&mut self) -> Result<Vec<DeviceLocation>, DriverError> {
+        device_lookup_helper::list_locations(self)
+    }
+}
+
+/// Stream of SM2M device arrivals and departures.
+///
+/// Devices attached before the monitor is started are reported as arrivals.
+/// The background thread is stopped when the monitor is dropped.
+pub struct HotplugMonitor {
+    events_rx: mpsc::Receiver<HotplugEvent>,
+    running: Arc<AtomicBool>,
+    handle: Option<thread::JoinHandle<()>>,
+}
+
+impl HotplugMonitor {
+    pub fn libusb(context: rusb::Context) -> Result<Self, DriverError> {
+        let (events_tx, events_rx) = mpsc::channel();
+        let (init_tx, init_rx) = mpsc::channel();
+        let running = Arc::new(AtomicBool::new(true));
+        let thread_running = running.clone();
+        let handle = thread::spawn(move || {
+            libusb_loop(context, events_tx, init_tx, thread_running);
+        });
+        let monitor = Self {
+            events_rx,
+            running,
+            handle: Some(handle),
+        };
+        match init_rx.recv() {
+            Ok(Ok(())) => Ok(monitor),
+            Ok(Err(error)) => Err(DriverError::Hotplug(error)),
+            Err(_) => Err(DriverError::Hotplug(rusb::Error::Other)),
+        }
+    }
+
+    pub fn polling<E: DeviceEnumerator>(enumerator: E, interval: time::Duration) -> Self {
+        let (events_tx, events_rx) = mpsc::channel();
+        let running = Arc::new(AtomicBool::new(true));
+        let thread_running = running.clone();
+        let handle = thread::spawn(move || {
+            polling_loop(enumerator, interval, events_tx, thread_running);
+        });
+        Self {
+            events_rx,
+            running,
+            handle: Some(handle),
+        }
+    }
+
+    pub fn try_recv(&self) -> Option<HotplugEvent> {
+        self.events_rx.try_recv().ok()
+    }
+
+    pub fn recv_timeout(&self, timeout: time::Duration) -> Option<HotplugEvent> {
+        self.events_rx.recv_timeout(timeout).ok()
+    }
+
+    pub fn stop(&mut self) {
+        if let Some(handle) = self.handle.take() {
+            self.running.store(false, Ordering::Relaxed);
+            handle.join().ok();
+        }
+    }
+}
+
+impl Iterator for HotplugMonitor {
+    type Item = HotplugEvent;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.events_rx.recv().ok()
+    }
+}
+
+impl Drop for HotplugMonitor {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+struct HotplugCallback {
+    events_tx: mpsc::Sender<HotplugEvent>,
+}
+
+impl rusb::Hotplug<rusb::Context> for HotplugCallback {
+    fn device_arrived(&mut self, device: rusb::Device<rusb::Context>) {
+        let location = DeviceLocation::from(&device);
+        self.events_tx.send(HotplugEvent::Arrived(location)).ok();
+    }
+
+    fn device_left(&mut self, device: rusb::Device<rusb::Context>) {
+        let location = DeviceLocation::from(&device);
+        self.events_tx.send(HotplugEvent::Left(location)).ok();
+    }
+}
+
+fn libusb_loop(
+    context: rusb::Context,
+    events_tx: mpsc::Sender<HotplugEvent>,
+    init_tx: mpsc::Sender<Result<(), rusb::Error>>,
+    running: Arc<AtomicBool>,
+) {
+    let callback = Box::new(HotplugCallback { events_tx });
+    let registration = rusb::HotplugBuilder::new()
+        .vendor_id(VID)
+        .product_id(PID)
+        .enumerate(true)
+        .register(&context, callback);
+    let _registration = match registration {
+        Ok(registration) => {
+            init_tx.send(Ok(())).ok();
+            registration
+        }
+        Err(error) => {
+            init_tx.send(Err(error)).ok();
+            return;
+        }
+    };
+
+    while running.load(Ordering::Relaxed) {
+        if context.handle_events(Some(EVENTS_TIMEOUT)).is_err() {
+            thread::sleep(EVENTS_TIMEOUT);
+        }
+    }
+}
+
+fn polling_loop<E: DeviceEnumerator>(
+    mut enumerator: E,
+    interval: time::Duration,
+    events_tx: mpsc::Sender<HotplugEvent>,
+    running: Arc<AtomicBool>,
+) {
+    let mut attached = HashSet::new();
+    while running.load(Ordering::Relaxed) {
+        if let Ok(locations) = enumerator.enumerate() {
+            for event in diff_locations(&mut attached, locations) {
+                if events_tx.send(event).is_err() {
+                    return;
+                }
+            }
+        }
+        thread::sleep(interval);
+    }
+}
+
+fn diff_locations(
+    attached: &mut HashSet<DeviceLocation>,
+    locations: Vec<DeviceLocation>,
+) -> Vec<HotplugEvent> {
+    let current = locations.into_iter().collect::<HashSet<_>>();
+    let mut events = attached
+        .difference(&current)
+        .cloned()
+        .map(HotplugEvent::Left)
+        .collect::<Vec<_>>();
+    events.extend(
+        current
+            .difference(attached)
+            .cloned()
+            .map(HotplugEvent::Arrived),
+    );
+    *attached = current;
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::base::mock::MockBus;
+
+    use super::*;
+
+    const POLL_INTERVAL: time::Duration = time::Duration::from_millis(5);
+    const EVENT_TIMEOUT: time::Duration = time::Duration::from_secs(1);
+
+    fn location(address: u8) -> DeviceLocation {
+        DeviceLocation {
+            bus: 1,
+            address,
+            ports: vec![address],
+        }
+    }
+
+    #[test]
+    fn report_attached_devices_on_start() {
+        let bus = MockBus::new();
+        bus.attach(location(1));
+
+        let monitor = HotplugMonitor::polling(bus.clone(), POLL_INTERVAL);
+
+        assert_eq!(
+            monitor.recv_timeout(EVENT_TIMEOUT),
+            Some(HotplugEvent::Arrived(location(1)))
+        );
+    }
+
+    #[test]
+    fn report_arrivals_and_departures() {
+        let bus = MockBus::new();
+        let monitor = HotplugMonitor::polling(bus.clone(), POLL_INTERVAL);
+
+        bus.attach(location(2));
+        assert_eq!(
+            monitor.recv_timeout(EVENT_TIMEOUT),
+            Some(HotplugEvent::Arrived(location(2)))
+        );
+        bus.detach(&location(2));
+        assert_eq!(
+            monitor.recv_timeout(EVENT_TIMEOUT),
+            Some(HotplugEvent::Left(location(2)))
+        );
+    }
+
+    #[test]
+    fn report_reattached_device_as_new_arrival() {
+        let mut attached = HashSet::new();
+
+        let first = diff_locations(&mut attached, vec![location(1)]);
+        let second = diff_locations(&mut attached, vec![location(3)]);
+
+        assert_eq!(first, vec![HotplugEvent::Arrived(location(1))]);
+        assert_eq!(
+            second,
+            vec![
+                HotplugEvent::Left(location(1)),
+                HotplugEvent::Arrived(location(3))
+            ]
+        );
+    }
+
+    #[test]
+    fn stay_silent_without_changes() {
+        let mut attached = HashSet::new();
+
+        diff_locations(&mut attached, vec![location(1)]);
+        let events = diff_locations(&mut attached, vec![location(1)]);
+
+        assert!(events.is_empty());
+    }
+}
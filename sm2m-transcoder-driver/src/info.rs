@@ -0,0 +1,523 @@
+use std::{fmt, time};
+
+pub use sm2m_protocol::capabilities::{Capabilities, Features, Role, PROTOCOL_REVISION};
+use sm2m_protocol::packet::{Packet, MAX_PACKET_SIZE};
+
+use crate::{
+    base::{
+        request::{Request, Requester, RetryPolicy},
+        transport::Transport,
+    },
+    devices::{decoder, emulator},
+    error::DriverError,
+    protocol::{self, SM2MDevice},
+};
+
+const VERSION_READ_ATTEMPTS: usize = 8;
+const CAPABILITIES_READ_ATTEMPTS: usize = 8;
+const QUERY_TIMEOUT: time::Duration = time::Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DeviceRole {
+    Decoder,
+    Emulator,
+    Encoder,
+    Unknown,
+}
+
+impl DeviceRole {
+    pub fn from_strings(product: &str, serial_number: &str) -> Self {
+        let product = product.to_lowercase();
+        if product.contains("decoder") {
+            Self::Decoder
+        } else if product.contains("emulator") {
+            Self::Emulator
+        } else if product.contains("encoder") {
+            Self::Encoder
+        } else {
+            Self::from_legacy_serial_number(serial_number)
+        }
+    }
+
+    fn from_legacy_serial_number(serial_number: &str) -> Self {
+        match serial_number {
+            "SM2M-DECODER" => Self::Decoder,
+            "SM2M-EMULATOR" => Self::Emulator,
+            "SM2M-ENCODER" => Self::Encoder,
+            _ => Self::Unknown,
+        }
+    }
+}
+
+impl fmt::Display for DeviceRole {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Decoder => "decoder",
+            Self::Emulator => "emulator",
+            Self::Encoder => "encoder",
+            Self::Unknown => "unknown",
+        };
+        f.write_str(name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FirmwareVersion {
+    pub major: u8,
+    pub minor: u8,
+    pub patch: u8,
+}
+
+impl FirmwareVersion {
+    pub fn new(major: u8, minor: u8, patch: u8) -> Self {
+        Self {
+            major,
+            minor,
+            patch,
+        }
+    }
+}
+
+impl fmt::Display for FirmwareVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceInfo {
+    pub role: DeviceRole,
+    pub product: String,
+    pub serial_number: String,
+    pub bus: u8,
+    pub ports: Vec<u8>,
+    pub firmware: Option<FirmwareVersion>,
+    pub capabilities: Option<Capabilities>,
+}
+
+impl DeviceInfo {
+    /// Returns the physical location of the device in `bus-port.port` form,
+    /// the same notation used by sysfs and `lsusb -t`.
+    pub fn path(&self) -> String {
+        let ports = self
+            .ports
+            .iter()
+            .map(u8::to_string)
+            .collect::<Vec<_>>()
+            .join(".");
+        format!("{}-{}", self.bus, ports)
+    }
+}
+
+pub fn query_firmware_version<T: Transport>(
+    device: &mut T,
+    role: DeviceRole,
+) -> Option<FirmwareVersion> {
+    match role {
+        DeviceRole::Decoder => query_version(&mut requester::<_, decoder::Command>(device)),
+        DeviceRole::Emulator => query_version(&mut requester::<_, emulator::Command>(device)),
+        DeviceRole::Encoder => query_legacy_version(device),
+        DeviceRole::Unknown => None,
+    }
+}
+
+/// Requests device capabilities and makes sure the firmware speaks the same
+/// protocol revision as the host. Firmware predating capabilities is reported
+/// as revision `0`, a device which does not answer at all as
+/// `DriverError::Timeout`.
+pub fn negotiate<T: Transport>(
+    device: &mut T,
+    role: DeviceRole,
+) -> Result<Option<Capabilities>, DriverError> {
+    match role {
+        DeviceRole::Decoder => negotiate_framed::<_, decoder::Command>(device).map(Some),
+        DeviceRole::Emulator => negotiate_framed::<_, emulator::Command>(device).map(Some),
+        DeviceRole::Encoder => check_revision(negotiate_legacy(device)?).map(Some),
+        DeviceRole::Unknown => Ok(None),
+    }
+}
+
+/// Fills firmware version and capabilities of `info`. Both queries share one
+/// framed link, so the frame sequence is not restarted between them. Details
+/// the device fails to report are left `None`.
+pub fn identify<T: Transport>(device: &mut T, info: &mut DeviceInfo) {
+    match info.role {
+        DeviceRole::Decoder => identify_framed::<_, decoder::Command>(device, info),
+        DeviceRole::Emulator => identify_framed::<_, emulator::Command>(device, info),
+        DeviceRole::Encoder => {
+            info.firmware = query_legacy_version(device);
+            info.capabilities = negotiate(device, info.role).ok().flatten();
+        }
+        DeviceRole::Unknown => {}
+    }
+}
+
+/// Commands of framed devices used by the open handshake.
+trait Handshake: Request + Copy {
+    const GET_VERSION: Self;
+    const GET_CAPABILITIES: Self;
+
+    fn version(response: &Self::Response) -> Option<FirmwareVersion>;
+    fn capabilities(response: &Self::Response) -> Option<Capabilities>;
+}
+
+impl Handshake for decoder::Command {
+    const GET_VERSION: Self = Self::GetVersion;
+    const GET_CAPABILITIES: Self = Self::GetCapabilities;
+
+    fn version(response: &decoder::Response) -> Option<FirmwareVersion> {
+        match *response {
+            decoder::Response::Version(major, minor, patch) => {
+                Some(FirmwareVersion::new(major, minor, patch))
+            }
+            _ => None,
+        }
+    }
+
+    fn capabilities(response: &decoder::Response) -> Option<Capabilities> {
+        match *response {
+            decoder::Response::Capabilities(capabilities) => Some(capabilities),
+            _ => None,
+        }
+    }
+}
+
+impl Handshake for emulator::Command {
+    const GET_VERSION: Self = Self::GetVersion;
+    const GET_CAPABILITIES: Self = Self::GetCapabilities;
+
+    fn version(response: &emulator::Response) -> Option<FirmwareVersion> {
+        match *response {
+            emulator::Response::Version(major, minor, patch) => {
+                Some(FirmwareVersion::new(major, minor, patch))
+            }
+            _ => None,
+        }
+    }
+
+    fn capabilities(response: &emulator::Response) -> Option<Capabilities> {
+        match *response {
+            emulator::Response::Capabilities(capabilities) => Some(capabilities),
+            _ => None,
+        }
+    }
+}
+
+fn requester<T: Transport, C: Request>(device: T) -> Requester<T, C> {
+    Requester::with_policy(
+        device,
+        RetryPolicy {
+            timeout: QUERY_TIMEOUT,
+            retries: 0,
+        },
+    )
+}
+
+fn identify_framed<T: Transport, C: Handshake>(device: &mut T, info: &mut DeviceInfo) {
+    let mut device = requester::<_, C>(device);
+    info.firmware = query_version(&mut device);
+    info.capabilities = query_capabilities(&mut device)
+        .and_then(check_revision)
+        .ok();
+}
+
+fn query_version<T: Transport, C: Handshake>(
+    device: &mut Requester<T, C>,
+) -> Option<FirmwareVersion> {
+    C::version(&device.request(C::GET_VERSION).ok()?)
+}
+
+fn query_capabilities<T: Transport, C: Handshake>(
+    device: &mut Requester<T, C>,
+) -> Result<Capabilities, DriverError> {
+    let response = device.request(C::GET_CAPABILITIES)?;
+    C::capabilities(&response).ok_or_else(|| unexpected_response(response))
+}
+
+fn query_legacy_version<T: Transport>(device: &mut T) -> Option<FirmwareVersion> {
+    device.write_packet(protocol::Command::GetVersion).ok()?;
+    for _ in 0..VERSION_READ_ATTEMPTS {
+        match device.read_packet() {
+            Ok(protocol::Response::Version(major, minor)) => {
+                return Some(FirmwareVersion::new(major, minor, 0))
+            }
+            Ok(_) | Err(DriverError::DecodePacket(_)) => continue,
+            Err(_) => return None,
+        }
+    }
+    None
+}
+
+/// Firmware which answers the version request, framed or not, but not the
+/// capabilities one predates capabilities and is reported as revision `0`.
+fn negotiate_framed<T: Transport, C: Handshake>(
+    device: &mut T,
+) -> Result<Capabilities, DriverError> {
+    let error = {
+        let mut device = requester::<_, C>(&mut *device);
+        match query_capabilities(&mut device) {
+            Err(error @ DriverError::Timeout(_)) if query_version(&mut device).is_none() => error,
+            Err(DriverError::Timeout(_)) => return Err(incompatible_firmware(0)),
+            result => return check_revision(result?),
+        }
+    };
+    if answers_unframed_version::<_, C>(device) {
+        Err(incompatible_firmware(0))
+    } else {
+        Err(error)
+    }
+}
+
+/// Firmware predating framing answers packets sent as they are, one per transfer.
+fn answers_unframed_version<T: Transport, C: Handshake>(device: &mut T) -> bool {
+    let mut buf = [0u8; MAX_PACKET_SIZE];
+    let size = match C::GET_VERSION.encode_into(&mut buf) {
+        Ok(size) => size,
+        Err(_) => return false,
+    };
+    if device.write_all(&buf[..size]).is_err() {
+        return false;
+    }
+    for _ in 0..VERSION_READ_ATTEMPTS {
+        let size = match device.read(&mut buf, QUERY_TIMEOUT) {
+            Ok(size) => size,
+            Err(_) => return false,
+        };
+        let response = C::Response::decode(&buf[..size]);
+        if response.ok().as_ref().and_then(C::version).is_some() {
+            return true;
+        }
+    }
+    false
+}
+
+/// Firmware which answers the legacy capabilities request with something
+/// else predates capabilities and is reported as revision `0`.
+fn negotiate_legacy<T: Transport>(device: &mut T) -> Result<Capabilities, DriverError> {
+    device.write_packet(protocol::Command::GetCapabilities)?;
+    for _ in 0..CAPABILITIES_READ_ATTEMPTS {
+        match device.read_packet() {
+            Ok(protocol::Response::Capabilities(capabilities)) => return Ok(capabilities),
+            Ok(_) | Err(DriverError::DecodePacket(_)) => continue,
+            Err(error) => return Err(error),
+        }
+    }
+    Err(incompatible_firmware(0))
+}
+
+fn check_revision(capabilities: Capabilities) -> Result<Capabilities, DriverError> {
+    if capabilities.revision == PROTOCOL_REVISION {
+        Ok(capabilities)
+    } else {
+        Err(incompatible_firmware(capabilities.revision))
+    }
+}
+
+fn unexpected_response<R: fmt::Debug>(response: R) -> DriverError {
+    DriverError::UnexpectedResponse(format!("{:?}", response))
+}
+
+fn incompatible_firmware(device: u8) -> DriverError {
+    DriverError::IncompatibleFirmware {
+        device,
+        host: PROTOCOL_REVISION,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::base::mock::{self, MockTransport};
+
+    use super::*;
+
+    #[test]
+    fn parse_role_from_product_string() {
+        assert_eq!(
+            DeviceRole::from_strings("An26 SM2M Decoder", "1A2B"),
+            DeviceRole::Decoder
+        );
+        assert_eq!(
+            DeviceRole::from_strings("SM2M Emulator", "1A2B"),
+            DeviceRole::Emulator
+        );
+        assert_eq!(
+            DeviceRole::from_strings("STM32 Virtual ComPort", "SM2M-ENCODER"),
+            DeviceRole::Encoder
+        );
+        assert_eq!(
+            DeviceRole::from_strings("STM32 Virtual ComPort", "1A2B"),
+            DeviceRole::Unknown
+        );
+    }
+
+    #[test]
+    fn format_device_path() {
+        let info = DeviceInfo {
+            role: DeviceRole::Decoder,
+            product: "An26 SM2M Decoder".to_owned(),
+            serial_number: "1A2B".to_owned(),
+            bus: 3,
+            ports: vec![1, 4],
+            firmware: None,
+            capabilities: None,
+        };
+
+        assert_eq!(info.path(), "3-1.4");
+    }
+
+    #[test]
+    fn query_decoder_version_skipping_params() {
+        let mut device = MockTransport::new();
+        device
+            .push_read(&mock::frame(0, &[2, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]))
+            .push_read(&mock::frame(1, &[1, 1, 2, 3]));
+
+        let version = query_firmware_version(&mut device, DeviceRole::Decoder);
+
+        assert_eq!(version, Some(FirmwareVersion::new(1, 2, 3)));
+        assert_eq!(device.written(), vec![mock::frame(0, &[1])]);
+    }
+
+    #[test]
+    fn query_encoder_legacy_version() {
+        let mut device = MockTransport::new();
+        device.push_read(&[0x12, 5]);
+
+        let version = query_firmware_version(&mut device, DeviceRole::Encoder);
+
+        assert_eq!(version, Some(FirmwareVersion::new(1, 5, 0)));
+    }
+
+    #[test]
+    fn skip_version_query_on_timeout() {
+        let mut device = MockTransport::new();
+
+        let version = query_firmware_version(&mut device, DeviceRole::Decoder);
+
+        assert_eq!(version, None);
+    }
+
+    #[test]
+    fn negotiate_decoder_capabilities_skipping_params() {
+        let mut device = MockTransport::new();
+        device
+            .push_read(&mock::frame(0, &[2, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]))
+            .push_read(&mock::frame(1, &[0x0f, PROTOCOL_REVISION, 1, 30, 0x03, 0]));
+
+        let capabilities = negotiate(&mut device, DeviceRole::Decoder).unwrap();
+
+        assert_eq!(
+            capabilities,
+            Some(Capabilities::new(
+                Role::Decoder,
+                30,
+                Features::FRAMING | Features::PARAMS_STREAM
+            ))
+        );
+        assert_eq!(device.written(), vec![mock::frame(0, &[0x0f])]);
+    }
+
+    #[test]
+    fn negotiate_encoder_capabilities() {
+        let mut device = MockTransport::new();
+        device.push_read(&[0x0f, PROTOCOL_REVISION, 3, 12, 0x14, 0]);
+
+        let capabilities = negotiate(&mut device, DeviceRole::Encoder).unwrap();
+
+        assert_eq!(capabilities.map(|c| c.max_params), Some(12));
+        assert_eq!(device.written(), vec![vec![0x0f]]);
+    }
+
+    #[test]
+    fn reject_firmware_with_other_protocol_revision() {
+        let mut device = MockTransport::new();
+        device.push_read(&mock::frame(0, &[0x0f, 7, 2, 12, 0x0d, 0]));
+
+        let result = negotiate(&mut device, DeviceRole::Emulator);
+
+        assert!(matches!(
+            result,
+            Err(DriverError::IncompatibleFirmware {
+                device: 7,
+                host: PROTOCOL_REVISION
+            })
+        ));
+    }
+
+    #[test]
+    fn report_timeout_when_capabilities_are_not_answered() {
+        let mut device = MockTransport::new();
+
+        let result = negotiate(&mut device, DeviceRole::Decoder);
+
+        assert!(matches!(result, Err(DriverError::Timeout(_))));
+    }
+
+    #[test]
+    fn reject_framed_firmware_without_capabilities() {
+        let mut device = MockTransport::new();
+        device.respond(&mock::frame(1, &[1]), &mock::frame(0, &[1, 1, 0, 0]));
+
+        let result = negotiate(&mut device, DeviceRole::Decoder);
+
+        assert!(matches!(
+            result,
+            Err(DriverError::IncompatibleFirmware {
+                device: 0,
+                host: PROTOCOL_REVISION
+            })
+        ));
+    }
+
+    #[test]
+    fn reject_unframed_firmware() {
+        let mut device = MockTransport::new();
+        device.respond(&[1], &[1, 1, 0, 0]);
+
+        let result = negotiate(&mut device, DeviceRole::Emulator);
+
+        assert!(matches!(
+            result,
+            Err(DriverError::IncompatibleFirmware {
+                device: 0,
+                host: PROTOCOL_REVISION
+            })
+        ));
+    }
+
+    #[test]
+    fn identify_device_over_one_framed_link() {
+        let mut device = MockTransport::new();
+        device
+            .push_read(&mock::frame(0, &[1, 1, 2, 3]))
+            .push_read(&mock::frame(1, &[0x0f, PROTOCOL_REVISION, 1, 30, 0x03, 0]));
+        let mut info = DeviceInfo {
+            role: DeviceRole::Decoder,
+            product: "An26 SM2M Decoder".to_owned(),
+            serial_number: "1A2B".to_owned(),
+            bus: 3,
+            ports: vec![1, 4],
+            firmware: None,
+            capabilities: None,
+        };
+
+        identify(&mut device, &mut info);
+
+        assert_eq!(info.firmware, Some(FirmwareVersion::new(1, 2, 3)));
+        assert_eq!(info.capabilities.map(|c| c.max_params), Some(30));
+        assert_eq!(
+            device.written(),
+            vec![mock::frame(0, &[1]), mock::frame(1, &[0x0f])]
+        );
+    }
+
+    #[test]
+    fn skip_negotiation_for_unknown_device() {
+        let mut device = MockTransport::new();
+
+        let capabilities = negotiate(&mut device, DeviceRole::Unknown).unwrap();
+
+        assert_eq!(capabilities, None);
+        assert!(device.written().is_empty());
+    }
+}
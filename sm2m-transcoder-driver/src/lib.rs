@@ -1,8 +1,22 @@
+#[macro_use]
+mod logging;
+
 pub mod base;
+pub mod capture;
+pub mod channel_map;
 pub mod devices;
+#[cfg(target_os = "linux")]
+pub mod diagnose;
 pub mod driver;
 pub mod error;
+pub mod ffi;
+pub mod hotplug;
+pub mod info;
 pub mod protocol;
+pub mod simulated;
+pub mod stats;
+#[cfg(target_os = "linux")]
+pub mod tty;
 
 #[cfg(test)]
 mod tests {
@@ -17,6 +31,8 @@ mod tests {
     #[test]
     fn return_libusb_version() {
         let version = UsbDriver::libusb_version();
-        assert_eq!(version, "1.0.24.11584");
+        let parts = version.split('.').collect::<Vec<_>>();
+        assert_eq!(parts.len(), 4);
+        assert!(parts.iter().all(|part| part.parse::<u16>().is_ok()));
     }
 }
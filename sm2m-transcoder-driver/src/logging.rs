@@ -0,0 +1,117 @@
+//! Logging through the `log` facade, compiled out unless the `log` feature is enabled.
+
+use std::fmt;
+
+macro_rules! debug {
+    ($($arg:tt)+) => {{
+        #[cfg(feature = "log")]
+        ::log::debug!($($arg)+);
+        #[cfg(not(feature = "log"))]
+        let _ = ::std::format_args!($($arg)+);
+    }};
+}
+
+macro_rules! warn {
+    ($($arg:tt)+) => {{
+        #[cfg(feature = "log")]
+        ::log::warn!($($arg)+);
+        #[cfg(not(feature = "log"))]
+        let _ = ::std::format_args!($($arg)+);
+    }};
+}
+
+macro_rules! trace {
+    ($($arg:tt)+) => {{
+        #[cfg(feature = "log")]
+        ::log::trace!($($arg)+);
+        #[cfg(not(feature = "log"))]
+        let _ = ::std::format_args!($($arg)+);
+    }};
+}
+
+/// Formats bytes as space separated hex pairs only when the record is written.
+pub(crate) struct Hex<'a>(pub &'a [u8]);
+
+impl fmt::Display for Hex<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, byte) in self.0.iter().enumerate() {
+            if index > 0 {
+                f.write_str(" ")?;
+            }
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+/// Logs transfer of the `result` bytes of `buf` at trace level.
+pub(crate) fn transfer<T>(
+    direction: &str,
+    target: fmt::Arguments<'_>,
+    buf: &[u8],
+    result: &Result<usize, T>,
+) where
+    T: fmt::Display,
+{
+    match result {
+        Ok(size) => trace!(
+            "{} {} {} bytes: {}",
+            direction,
+            target,
+            size,
+            Hex(&buf[..*size])
+        ),
+        Err(error) => trace!("{} {} failed: {}", direction, target, error),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_hexdump() {
+        assert_eq!(Hex(&[0xa5, 0x01, 0x0f]).to_string(), "a5 01 0f");
+        assert_eq!(Hex(&[]).to_string(), "");
+    }
+
+    #[cfg(feature = "log")]
+    #[test]
+    fn log_transfers_at_trace_level() {
+        use std::sync::Mutex;
+
+        static RECORDS: Mutex<Vec<(log::Level, String)>> = Mutex::new(Vec::new());
+
+        struct Logger;
+
+        impl log::Log for Logger {
+            fn enabled(&self, _: &log::Metadata<'_>) -> bool {
+                true
+            }
+
+            fn log(&self, record: &log::Record<'_>) {
+                let message = (record.level(), record.args().to_string());
+                RECORDS.lock().unwrap().push(message);
+            }
+
+            fn flush(&self) {}
+        }
+
+        log::set_logger(&Logger).unwrap();
+        log::set_max_level(log::LevelFilter::Trace);
+
+        let result: Result<usize, String> = Ok(2);
+        transfer(
+            "read from",
+            format_args!("endpoint 0x81"),
+            &[1, 2, 3],
+            &result,
+        );
+
+        let records = RECORDS.lock().unwrap();
+        assert!(records.contains(&(
+            log::Level::Trace,
+            "read from endpoint 0x81 2 bytes: 01 02".to_owned()
+        )));
+    }
+}
@@ -1,81 +1,158 @@
 use std::time;
 
-use crate::{driver::UsbDevice, error::DriverError};
-
-pub enum UsbInPacket {
-    GetVersion,
-    Ping(u8, u8),
-    LedOn,
-    LedOff,
-    SetParam(u8, u16),
-    GetParam(u8),
-}
+pub use sm2m_protocol::encoder::{Command, Response};
+use sm2m_protocol::packet::{Packet, MAX_PACKET_SIZE};
 
-#[derive(Debug, PartialEq)]
-pub enum UsbOutPacket {
-    Error(u8),
-    Version(u8, u8),
-    Pong(u8, u8),
-    Param(u8, u16),
-    Unknown,
-}
+use crate::{base::transport::Transport, error::DriverError};
 
+const READ_TIMEOUT: time::Duration = time::Duration::from_secs(1);
+
+/// Legacy SM2M encoder protocol, packets are sent unframed one per USB transfer.
 pub trait SM2MDevice {
-    fn write_packet(&mut self, packet: UsbInPacket) -> Result<usize, DriverError>;
-    fn read_packet(&mut self) -> Result<UsbOutPacket, DriverError>;
+    fn write_packet(&mut self, command: Command) -> Result<usize, DriverError>;
+    fn read_packet(&mut self) -> Result<Response, DriverError>;
+
+    /// Sends ping and waits for pong with the next version, the round trip
+    /// is recorded into transport stats.
+    fn ping(&mut self, version: u8) -> Result<time::Duration, DriverError>;
 }
 
-impl SM2MDevice for UsbDevice {
-    fn write_packet(&mut self, packet: UsbInPacket) -> Result<usize, DriverError> {
-        match packet {
-            UsbInPacket::GetVersion => {
-                let buf = [1];
-                self.write_all(&buf)
-            }
-            UsbInPacket::Ping(version, payload) => {
-                let buf = [2 | payload << 4, version];
-                self.write_all(&buf)
-            }
-            UsbInPacket::LedOn => self.write_all(&[3 | 1 << 4]),
-            UsbInPacket::LedOff => self.write_all(&[3]),
-            UsbInPacket::SetParam(index, value) => {
-                let buf = [4 | index << 4, value as u8, (value >> 8) as u8];
-                self.write_all(&buf)
-            }
-            UsbInPacket::GetParam(index) => {
-                let buf = [5 | index << 4];
-                self.write_all(&buf)
-            }
+impl<T: Transport> SM2MDevice for T {
+    fn write_packet(&mut self, command: Command) -> Result<usize, DriverError> {
+        let mut buf = [0u8; MAX_PACKET_SIZE];
+        let size = command
+            .encode_into(&mut buf)
+            .map_err(DriverError::EncodePacket)?;
+        debug!("sending {:?}", command);
+        let size = self.write_all(&buf[..size])?;
+        if let Some(stats) = self.stats() {
+            stats.record_packet_written();
         }
+        Ok(size)
     }
 
-    fn read_packet(&mut self) -> Result<UsbOutPacket, DriverError> {
-        const READ_TIMEOUT: time::Duration = time::Duration::from_secs(1);
+    fn read_packet(&mut self) -> Result<Response, DriverError> {
         let mut buf = [0u8; 64];
-        self.read(&mut buf, READ_TIMEOUT)?;
-        let opcode = buf[0] & 0x0f;
-        let packet = match opcode {
-            1 => {
-                let reason = buf[0] >> 4;
-                UsbOutPacket::Error(reason)
-            }
-            2 => {
-                let major = buf[0] >> 4;
-                let minor = buf[1];
-                UsbOutPacket::Version(major, minor)
+        let size = self.read(&mut buf, READ_TIMEOUT)?;
+        if let Some(stats) = self.stats() {
+            stats.record_packet_read();
+        }
+        let response = Response::decode(&buf[..size]).map_err(DriverError::DecodePacket);
+        match &response {
+            Ok(response) => debug!("received {:?}", response),
+            Err(error) => debug!("received undecodable packet: {}", error),
+        }
+        response
+    }
+
+    fn ping(&mut self, version: u8) -> Result<time::Duration, DriverError> {
+        let payload = version & 0x0f;
+        let started = time::Instant::now();
+        self.write_packet(Command::Ping { version, payload })?;
+        match self.read_packet()? {
+            Response::Pong {
+                version: pong_version,
+                payload: pong_payload,
+            } if pong_version == version.wrapping_add(1) && pong_payload == payload => {
+                let latency = started.elapsed();
+                if let Some(stats) = self.stats() {
+                    stats.record_latency(latency);
+                }
+                Ok(latency)
             }
-            3 => {
-                let payload = buf[0] >> 4;
-                let version = buf[1];
-                UsbOutPacket::Pong(version, payload)
+            response => Err(DriverError::UnexpectedResponse(format!("{:?}", response))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sm2m_protocol::packet::PacketError;
+
+    use crate::{base::mock::MockTransport, stats::Metered};
+
+    use super::*;
+
+    #[test]
+    fn encode_inbound_packets() {
+        let transport = MockTransport::new();
+        let mut device = transport.clone();
+
+        device.write_packet(Command::GetVersion).unwrap();
+        device
+            .write_packet(Command::Ping {
+                version: 1,
+                payload: 15,
+            })
+            .unwrap();
+        device.write_packet(Command::LedOn).unwrap();
+        device.write_packet(Command::LedOff).unwrap();
+        device
+            .write_packet(Command::SetParam {
+                index: 2,
+                value: 0x5555,
+            })
+            .unwrap();
+        device.write_packet(Command::GetParam(3)).unwrap();
+
+        assert_eq!(
+            transport.written(),
+            vec![
+                vec![1],
+                vec![0xf2, 1],
+                vec![0x13],
+                vec![3],
+                vec![0x24, 0x55, 0x55],
+                vec![0x35],
+            ]
+        );
+    }
+
+    #[test]
+    fn decode_outbound_packets() {
+        let mut device = MockTransport::new();
+        device
+            .push_read(&[0x11])
+            .push_read(&[0x12, 5])
+            .push_read(&[0xf3, 2])
+            .push_read(&[0x24, 0x55, 0x55])
+            .push_read(&[0]);
+
+        assert_eq!(device.read_packet().unwrap(), Response::Error(1));
+        assert_eq!(device.read_packet().unwrap(), Response::Version(1, 5));
+        assert_eq!(
+            device.read_packet().unwrap(),
+            Response::Pong {
+                version: 2,
+                payload: 15
             }
-            4 => {
-                let index = buf[0] >> 4;
-                let param = buf[1] as u16 | (buf[2] as u16) << 8;
-                UsbOutPacket::Param(index, param)
+        );
+        assert_eq!(
+            device.read_packet().unwrap(),
+            Response::Param {
+                index: 2,
+                value: 0x5555
             }
-            _ => UsbOutPacket::Unknown,
-        };
-        Ok(packet)
+        );
+        assert!(matches!(
+            device.read_packet(),
+            Err(DriverError::DecodePacket(PacketError::UnknownOpcode(0)))
+        ));
+    }
+
+    #[test]
+    fn measure_ping_latency() {
+        let transport = MockTransport::new();
+        transport.push_read(&[0x13, 2]).push_read(&[0x43, 9]);
+        let mut device = Metered::new(transport.clone());
+
+        device.ping(1).unwrap();
+        let result = device.ping(4);
+        let stats = device.recorder().snapshot();
+
+        assert!(matches!(result, Err(DriverError::UnexpectedResponse(_))));
+        assert_eq!(transport.written(), vec![vec![0x12, 1], vec![0x42, 4]]);
+        assert_eq!((stats.packets_written, stats.packets_read), (2, 2));
+        assert_eq!(stats.latency.count(), 1);
     }
 }
@@ -0,0 +1,164 @@
+use std::sync::{Arc, Mutex};
+
+use sm2m_protocol::sync::{FrameSync, SyncEvent};
+
+use super::emulator::Producer;
+
+/// Data bus wiring a virtual emulator to a virtual decoder, see
+/// [`VirtualEmulator::bus`](super::emulator::VirtualEmulator::bus).
+///
+/// The bus yields parameters frames the decoder firmware would detect from
+/// words written by the emulator, using the same [`FrameSync`]. Overflowing
+/// frames are skipped. Lines can be forced low or high to emulate broken wires.
+pub struct VirtualBus {
+    producer: Arc<Mutex<Producer>>,
+    stuck_low: u16,
+    stuck_high: u16,
+    sync: FrameSync,
+}
+
+impl VirtualBus {
+    pub(crate) fn new(producer: Arc<Mutex<Producer>>) -> Self {
+        Self {
+            producer,
+            stuck_low: 0,
+            stuck_high: 0,
+            sync: FrameSync::default(),
+        }
+    }
+
+    /// Forces every line set in `lines` to read low.
+    pub fn stuck_low(mut self, lines: u16) -> Self {
+        self.stuck_low |= lines;
+        self
+    }
+
+    /// Forces every line set in `lines` to read high.
+    pub fn stuck_high(mut self, lines: u16) -> Self {
+        self.stuck_high |= lines;
+        self
+    }
+
+    fn receive(&mut self, word: u16) -> Option<Vec<u16>> {
+        let word = (word & !self.stuck_low) | self.stuck_high;
+        // The virtual decoder stamps frames itself when it takes them.
+        match self.sync.push(word, 0)? {
+            SyncEvent::Frame { params, .. } => Some(params.to_vec()),
+            SyncEvent::Overflow(_) | SyncEvent::Corrupted(_) => None,
+        }
+    }
+}
+
+impl Iterator for VirtualBus {
+    type Item = Vec<u16>;
+
+    /// Returns the next detected frame or `None` when the emulator has not
+    /// produced one yet.
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let word = {
+                let mut producer = self
+                    .producer
+                    .lock()
+                    .unwrap_or_else(|error| error.into_inner());
+                producer.tick();
+                producer.pop_word()?
+            };
+            if let Some(words) = self.receive(word) {
+                return Some(words);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{thread, time};
+
+    use sm2m_protocol::emulator::MAX_CHANNELS_COUNT;
+
+    use crate::{
+        base::framed::Framed,
+        devices::{decoder::DecoderDevice, emulator_session::EmulatorSession},
+        simulated::{decoder::VirtualDecoder, emulator::VirtualEmulator},
+    };
+
+    fn produce(emulator: VirtualEmulator) {
+        produce_with_checksum(emulator, false);
+    }
+
+    fn produce_with_checksum(emulator: VirtualEmulator, checksum: bool) {
+        let mut session = EmulatorSession::open(emulator).unwrap();
+        session.set_checksum(checksum).unwrap();
+        session.channel(0).from(10).enable().unwrap();
+        session.channel(1).constant(0x0c00).enable().unwrap();
+        session.start(200).unwrap();
+        thread::sleep(time::Duration::from_millis(50));
+        session.stop().unwrap();
+    }
+
+    #[test]
+    fn detect_frames_after_synchronisation() {
+        let emulator = VirtualEmulator::new();
+        let bus = emulator.bus();
+
+        produce(emulator);
+        let frames = bus.collect::<Vec<_>>();
+
+        assert!(frames.len() >= 2);
+        assert!(frames.iter().all(|frame| frame.len() == MAX_CHANNELS_COUNT));
+        assert!(frames.windows(2).all(|pair| pair[1][0] == pair[0][0] + 1));
+        assert!(frames.iter().all(|frame| frame[1] == 0x0c00));
+    }
+
+    #[test]
+    fn apply_stuck_lines() {
+        let emulator = VirtualEmulator::new();
+        let bus = emulator.bus().stuck_low(0x0800).stuck_high(0x0001);
+
+        produce(emulator);
+        let frames = bus.collect::<Vec<_>>();
+
+        assert!(!frames.is_empty());
+        assert!(frames.iter().all(|frame| frame[1] == 0x0401));
+    }
+
+    #[test]
+    fn lose_frames_with_broken_marker_line() {
+        let emulator = VirtualEmulator::new();
+        let mut bus = emulator.bus().stuck_low(0x0001);
+
+        produce(emulator);
+
+        assert_eq!(bus.next(), None);
+    }
+
+    #[test]
+    fn verify_checksum_produced_by_emulator() {
+        let emulator = VirtualEmulator::new();
+        let bus = emulator.bus();
+
+        produce_with_checksum(emulator, true);
+        let mut decoder = Framed::new(VirtualDecoder::new(bus));
+        decoder.set_checksum(true).unwrap();
+        let frame = decoder.read_params().unwrap();
+
+        assert_eq!(frame.words.len(), MAX_CHANNELS_COUNT);
+        assert_eq!(frame.corrupted, None);
+    }
+
+    #[test]
+    fn drop_frames_corrupted_by_stuck_line() {
+        let emulator = VirtualEmulator::new();
+        let bus = emulator.bus().stuck_high(0x0100);
+
+        produce_with_checksum(emulator, true);
+        let mut decoder = Framed::new(VirtualDecoder::new(bus));
+        decoder.set_checksum(true).unwrap();
+        let first = decoder.read_params().unwrap();
+        let second = decoder.read_params().unwrap();
+
+        assert_eq!(first.corrupted, Some(1));
+        assert_eq!(second.corrupted, Some(2));
+    }
+}
@@ -0,0 +1,311 @@
+use std::{thread, time};
+
+use sm2m_protocol::{
+    bus,
+    capabilities::{Capabilities, Features, Role},
+    decoder::{Command, DecoderStats, Response, MAX_PARAMS_COUNT},
+    packet::Packet,
+    queue::{FrameQueue, Push},
+    sync::SyncEvent,
+};
+
+use crate::{base::transport::Transport, error::DriverError, info::FirmwareVersion};
+
+use super::link::Link;
+
+const SOURCE_POLL_INTERVAL: time::Duration = time::Duration::from_millis(1);
+const QUEUE_SIZE: usize = 8;
+
+/// Pure-Rust decoder answering host commands like the firmware and streaming
+/// parameters frames taken from `source`.
+///
+/// Frames longer than `MAX_PARAMS_COUNT` words are reported as overflow. While
+/// `source` returns `None` the decoder stays silent and reads time out. In
+/// checksum mode the last word of every source frame is verified and stripped.
+/// Frames pass through the firmware frame queue, so the delivery policy
+/// applies.
+pub struct VirtualDecoder<S> {
+    link: Link,
+    source: S,
+    queue: FrameQueue<QUEUE_SIZE>,
+    checksum: bool,
+    stats: DecoderStats,
+    version: FirmwareVersion,
+    interval: Option<time::Duration>,
+    next_frame: time::Instant,
+    powered_up: time::Instant,
+}
+
+impl<S: Iterator<Item = Vec<u16>>> VirtualDecoder<S> {
+    pub fn new(source: S) -> Self {
+        Self {
+            link: Link::default(),
+            source,
+            queue: FrameQueue::new(),
+            checksum: false,
+            stats: DecoderStats::default(),
+            version: FirmwareVersion::new(1, 0, 0),
+            interval: None,
+            next_frame: time::Instant::now(),
+            powered_up: time::Instant::now(),
+        }
+    }
+
+    /// Limits streaming to `fps` frames per second, frames are streamed as
+    /// fast as the host reads them by default.
+    pub fn with_rate(mut self, fps: u32) -> Self {
+        self.interval = (fps > 0).then(|| time::Duration::from_secs(1) / fps);
+        self
+    }
+
+    pub fn with_version(mut self, version: FirmwareVersion) -> Self {
+        self.version = version;
+        self
+    }
+
+    fn handle(&mut self, command: Command) {
+        let response = match command {
+            Command::GetVersion => {
+                Response::Version(self.version.major, self.version.minor, self.version.patch)
+            }
+            Command::GetCapabilities => Response::Capabilities(Capabilities::new(
+                Role::Decoder,
+                MAX_PARAMS_COUNT as u8,
+                Features::FRAMING
+                    | Features::PARAMS_STREAM
+                    | Features::CHECKSUM
+                    | Features::STATS
+                    | Features::DELIVERY,
+            )),
+            Command::SetChecksum(enabled) => {
+                self.checksum = enabled;
+                return;
+            }
+            Command::SetDelivery(policy) => {
+                self.queue.set_policy(policy);
+                return;
+            }
+            Command::GetStats => Response::Stats(self.stats),
+            Command::ResetStats => {
+                self.stats = DecoderStats::default();
+                return;
+            }
+        };
+        self.link.send(&response);
+    }
+
+    /// Captures source frames until the queue yields an event to send.
+    fn stream(&mut self, timeout: time::Duration) -> Result<(), DriverError> {
+        let started = time::Instant::now();
+        loop {
+            if let Some(event) = self.queue.pop() {
+                self.link.send(&Response::from(event));
+                return Ok(());
+            }
+            let event = self.capture(timeout.saturating_sub(started.elapsed()))?;
+            if self.queue.push(event) == Push::Dropped {
+                self.stats.dropped_frames = self.stats.dropped_frames.wrapping_add(1);
+            }
+        }
+    }
+
+    fn capture(&mut self, timeout: time::Duration) -> Result<SyncEvent, DriverError> {
+        if let Some(interval) = self.interval {
+            let now = time::Instant::now();
+            if self.next_frame > now + timeout {
+                thread::sleep(timeout);
+                return Err(DriverError::Timeout(timeout));
+            }
+            thread::sleep(self.next_frame.saturating_duration_since(now));
+            self.next_frame = self.next_frame.max(now) + interval;
+        }
+
+        let started = time::Instant::now();
+        let words = loop {
+            if let Some(words) = self.source.next() {
+                break words;
+            }
+            let elapsed = started.elapsed();
+            if elapsed >= timeout {
+                return Err(DriverError::Timeout(timeout));
+            }
+            thread::sleep(SOURCE_POLL_INTERVAL.min(timeout - elapsed));
+        };
+        let timestamp = self.powered_up.elapsed().as_micros() as u64;
+        // Source frames are preceded by a marker on the bus.
+        self.stats.words = self.stats.words.wrapping_add(words.len() as u32 + 1);
+        self.stats.markers = self.stats.markers.wrapping_add(1);
+
+        let words = if self.checksum {
+            match bus::verify(&words) {
+                Some(params) => params.to_vec(),
+                None => {
+                    self.stats.corrupted = self.stats.corrupted.wrapping_add(1);
+                    return Ok(SyncEvent::Corrupted(self.stats.corrupted));
+                }
+            }
+        } else {
+            words
+        };
+
+        if words.len() > MAX_PARAMS_COUNT {
+            self.stats.overflows = self.stats.overflows.wrapping_add(1);
+            Ok(SyncEvent::Overflow(words.len()))
+        } else {
+            self.stats.frames = self.stats.frames.wrapping_add(1);
+            Ok(SyncEvent::Frame {
+                timestamp,
+                params: words.iter().copied().collect(),
+            })
+        }
+    }
+}
+
+impl<S: Iterator<Item = Vec<u16>>> Transport for VirtualDecoder<S> {
+    fn read(&mut self, buf: &mut [u8], timeout: time::Duration) -> Result<usize, DriverError> {
+        if !self.link.has_outgoing() {
+            self.stream(timeout)?;
+        }
+        Ok(self.link.read(buf))
+    }
+
+    fn write(&mut self, buf: &[u8], _timeout: time::Duration) -> Result<usize, DriverError> {
+        for frame in self.link.receive(buf) {
+            if let Ok(command) = Command::decode(frame.payload()) {
+                self.handle(command);
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn reset(&mut self) -> Result<(), DriverError> {
+        self.link.reset();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::iter;
+
+    use crate::{
+        base::{framed::Framed, request::Requester},
+        devices::decoder::{DecoderDevice, DeliveryPolicy, ParamsOverflow},
+        info::{self, DeviceRole},
+    };
+
+    use super::*;
+
+    const READ_TIMEOUT: time::Duration = time::Duration::from_millis(10);
+
+    #[test]
+    fn answer_version_and_capabilities() {
+        let mut device =
+            VirtualDecoder::new(iter::empty()).with_version(FirmwareVersion::new(2, 3, 4));
+
+        let capabilities = info::negotiate(&mut device, DeviceRole::Decoder)
+            .unwrap()
+            .unwrap();
+        let mut device = Requester::new(device);
+        let version = device.request(Command::GetVersion).unwrap();
+
+        assert_eq!(capabilities.role, Role::Decoder);
+        assert_eq!(capabilities.max_params as usize, MAX_PARAMS_COUNT);
+        assert_eq!(version, Response::Version(2, 3, 4));
+    }
+
+    #[test]
+    fn stream_params_from_source() {
+        let source = vec![vec![1, 2, 3], vec![4]].into_iter();
+        let mut device = Framed::new(VirtualDecoder::new(source));
+        device.set_read_timeout(READ_TIMEOUT);
+
+        assert_eq!(device.read_params().unwrap().words.as_slice(), &[1, 2, 3]);
+        assert_eq!(device.read_params().unwrap().words.as_slice(), &[4]);
+        assert!(device.read_params().unwrap_err().is_timeout());
+    }
+
+    #[test]
+    fn report_overflow_for_long_frames() {
+        let source = iter::once(vec![0; MAX_PARAMS_COUNT + 1]);
+        let mut device = Framed::new(VirtualDecoder::new(source));
+
+        let frame = device.read_params().unwrap();
+
+        assert!(frame.words.is_empty());
+        assert_eq!(
+            frame.overflow,
+            Some(ParamsOverflow {
+                expected: MAX_PARAMS_COUNT as u8,
+                received: MAX_PARAMS_COUNT as u8 + 1,
+            })
+        );
+    }
+
+    #[test]
+    fn verify_checksum() {
+        let source = vec![vec![1, 2, bus::checksum(&[1, 2])], vec![1, 2, 0]].into_iter();
+        let mut device = Framed::new(VirtualDecoder::new(source));
+        device.set_checksum(true).unwrap();
+
+        assert_eq!(device.read_params().unwrap().words.as_slice(), &[1, 2]);
+        assert_eq!(device.read_params().unwrap().corrupted, Some(1));
+    }
+
+    #[test]
+    fn count_streamed_frames() {
+        let source = vec![vec![1, 2], vec![0; MAX_PARAMS_COUNT + 1], vec![3]].into_iter();
+        let mut device = Framed::new(VirtualDecoder::new(source));
+        for _ in 0..3 {
+            device.read_params().unwrap();
+        }
+
+        let stats = device.decoder_stats().unwrap();
+
+        assert_eq!(stats.words, MAX_PARAMS_COUNT as u32 + 7);
+        assert_eq!((stats.markers, stats.frames, stats.overflows), (3, 2, 1));
+
+        device.reset_decoder_stats().unwrap();
+
+        assert_eq!(device.decoder_stats().unwrap(), DecoderStats::default());
+    }
+
+    #[test]
+    fn stamp_frames_with_decoder_clock() {
+        let source = iter::repeat(vec![1]);
+        let mut device = Framed::new(VirtualDecoder::new(source).with_rate(100));
+
+        let first = device.read_params().unwrap().timestamp;
+        let second = device.read_params().unwrap().timestamp;
+
+        assert!(second >= first + 5_000);
+    }
+
+    #[test]
+    fn decimate_streamed_frames() {
+        let source = iter::repeat(vec![1]);
+        let mut device = Framed::new(VirtualDecoder::new(source).with_rate(200));
+        device.set_delivery(DeliveryPolicy::Decimate(50)).unwrap();
+
+        let first = device.read_params().unwrap().timestamp;
+        let second = device.read_params().unwrap().timestamp;
+        let stats = device.decoder_stats().unwrap();
+
+        assert!(second >= first + 20_000);
+        assert!(stats.frames >= 5);
+        assert_eq!(stats.dropped_frames, 0);
+    }
+
+    #[test]
+    fn stream_at_given_rate() {
+        let source = iter::repeat(vec![1]);
+        let mut device = Framed::new(VirtualDecoder::new(source).with_rate(100));
+        let started = time::Instant::now();
+
+        for _ in 0..5 {
+            device.read_params().unwrap();
+        }
+
+        assert!(started.elapsed() >= time::Duration::from_millis(40));
+    }
+}
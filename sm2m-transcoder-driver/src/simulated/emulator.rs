@@ -0,0 +1,246 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex, MutexGuard},
+    thread, time,
+};
+
+use sm2m_protocol::{
+    bus::{self, START_MARKER},
+    capabilities::{Capabilities, Features, Role},
+    emulator::{Command, Response, Values, MAX_CHANNELS_COUNT},
+    generator::channels::Channels,
+    packet::Packet,
+};
+
+use crate::{base::transport::Transport, error::DriverError, info::FirmwareVersion};
+
+use super::{bus::VirtualBus, link::Link};
+
+/// Words kept on a bus nobody reads before the oldest ones are dropped.
+const MAX_BUS_WORDS: usize = 64 * 1024;
+
+/// Pure-Rust emulator running channel generators like the firmware.
+///
+/// Generators advance once per producer tick, ticks elapsed since the last
+/// access are caught up lazily whenever the host or the bus talks to the emulator.
+pub struct VirtualEmulator {
+    link: Link,
+    producer: Arc<Mutex<Producer>>,
+    version: FirmwareVersion,
+}
+
+pub(crate) struct Producer {
+    channels: Channels,
+    checksum: bool,
+    period: Option<time::Duration>,
+    next_tick: time::Instant,
+    bus: Option<VecDeque<u16>>,
+}
+
+impl Producer {
+    /// Runs every tick due by now writing produced frames to the bus.
+    pub(crate) fn tick(&mut self) {
+        if let Some(period) = self.period {
+            let now = time::Instant::now();
+            while self.next_tick <= now {
+                let values = self.channels.values();
+                self.channels.generate();
+                if let Some(bus) = &mut self.bus {
+                    bus.push_back(START_MARKER);
+                    bus.extend(values.iter().copied());
+                    if self.checksum {
+                        bus.push_back(bus::checksum(&values));
+                    }
+                    let overflow = bus.len().saturating_sub(MAX_BUS_WORDS);
+                    bus.drain(..overflow);
+                }
+                self.next_tick += period;
+            }
+        }
+    }
+
+    pub(crate) fn pop_word(&mut self) -> Option<u16> {
+        self.bus.as_mut().and_then(VecDeque::pop_front)
+    }
+}
+
+impl Default for VirtualEmulator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl VirtualEmulator {
+    pub fn new() -> Self {
+        let producer = Producer {
+            channels: Channels::default(),
+            checksum: false,
+            period: None,
+            next_tick: time::Instant::now(),
+            bus: None,
+        };
+        Self {
+            link: Link::default(),
+            producer: Arc::new(Mutex::new(producer)),
+            version: FirmwareVersion::new(1, 0, 0),
+        }
+    }
+
+    pub fn with_version(mut self, version: FirmwareVersion) -> Self {
+        self.version = version;
+        self
+    }
+
+    /// Connects data bus which carries every frame produced from now on, the
+    /// bus is a parameters source for `VirtualDecoder`.
+    pub fn bus(&self) -> VirtualBus {
+        self.lock().bus.get_or_insert_with(VecDeque::new);
+        VirtualBus::new(self.producer.clone())
+    }
+
+    /// Returns current value of every channel.
+    pub fn values(&self) -> Values {
+        let mut producer = self.lock();
+        producer.tick();
+        producer.channels.values()
+    }
+
+    pub fn is_producing(&self) -> bool {
+        self.lock().period.is_some()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Producer> {
+        self.producer
+            .lock()
+            .unwrap_or_else(|error| error.into_inner())
+    }
+
+    fn handle(&mut self, command: Command) {
+        let mut producer = self.lock();
+        producer.tick();
+        let response = match command {
+            Command::GetVersion => Some(Response::Version(
+                self.version.major,
+                self.version.minor,
+                self.version.patch,
+            )),
+            Command::GetCapabilities => Some(Response::Capabilities(Capabilities::new(
+                Role::Emulator,
+                MAX_CHANNELS_COUNT as u8,
+                Features::FRAMING | Features::GENERATORS | Features::VALUES | Features::CHECKSUM,
+            ))),
+            Command::EnableGenerator {
+                index,
+                period,
+                value,
+                step,
+            } => {
+                producer.channels.enable(index, period, value, step);
+                None
+            }
+            Command::DisableGenerator(index) => {
+                producer.channels.disable(index);
+                None
+            }
+            Command::StartProducer(0) | Command::StopProducer => {
+                producer.period = None;
+                None
+            }
+            Command::StartProducer(fps) => {
+                let period = time::Duration::from_secs(1) / fps as u32;
+                producer.period = Some(period);
+                producer.next_tick = time::Instant::now() + period;
+                None
+            }
+            Command::GetValues => Some(Response::Values(producer.channels.values())),
+            Command::SetChecksum(enabled) => {
+                producer.checksum = enabled;
+                None
+            }
+        };
+        drop(producer);
+        if let Some(response) = response {
+            self.link.send(&response);
+        }
+    }
+}
+
+impl Transport for VirtualEmulator {
+    fn read(&mut self, buf: &mut [u8], timeout: time::Duration) -> Result<usize, DriverError> {
+        if self.link.has_outgoing() {
+            Ok(self.link.read(buf))
+        } else {
+            thread::sleep(timeout);
+            Err(DriverError::Timeout(timeout))
+        }
+    }
+
+    fn write(&mut self, buf: &[u8], _timeout: time::Duration) -> Result<usize, DriverError> {
+        for frame in self.link.receive(buf) {
+            if let Ok(command) = Command::decode(frame.payload()) {
+                self.handle(command);
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn reset(&mut self) -> Result<(), DriverError> {
+        self.link.reset();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{devices::emulator_session::EmulatorSession, info};
+
+    use super::*;
+
+    #[test]
+    fn report_channels_and_capabilities() {
+        let mut device = VirtualEmulator::new();
+
+        let capabilities = info::negotiate(&mut device, info::DeviceRole::Emulator)
+            .unwrap()
+            .unwrap();
+        let session = EmulatorSession::open(device).unwrap();
+
+        assert_eq!(capabilities.role, Role::Emulator);
+        assert_eq!(session.channels_count(), MAX_CHANNELS_COUNT);
+    }
+
+    #[test]
+    fn generate_values_while_producing() {
+        let mut session = EmulatorSession::open(VirtualEmulator::new()).unwrap();
+        session
+            .channel(0)
+            .from(10)
+            .step(1)
+            .every(1)
+            .enable()
+            .unwrap();
+        session.channel(1).constant(7).enable().unwrap();
+        assert_eq!(session.value(0).unwrap(), 10);
+
+        session.start(200).unwrap();
+        thread::sleep(time::Duration::from_millis(50));
+        session.stop().unwrap();
+        let value = session.value(0).unwrap();
+        thread::sleep(time::Duration::from_millis(20));
+
+        assert!(value > 10);
+        assert_eq!(session.value(0).unwrap(), value);
+        assert_eq!(session.value(1).unwrap(), 7);
+        assert_eq!(session.value(2).unwrap(), 0);
+    }
+
+    #[test]
+    fn reset_disabled_channel() {
+        let mut session = EmulatorSession::open(VirtualEmulator::new()).unwrap();
+        session.channel(3).from(100).enable().unwrap();
+        session.channel(3).disable().unwrap();
+
+        assert_eq!(session.value(3).unwrap(), 0);
+        assert!(!session.into_inner().get_ref().is_producing());
+    }
+}
@@ -0,0 +1,56 @@
+use std::collections::VecDeque;
+
+use sm2m_protocol::{
+    deframer::Deframer,
+    frame::{Frame, FrameWriter, MAX_FRAME_SIZE},
+    packet::{Packet, MAX_PACKET_SIZE},
+};
+
+/// Firmware side of the framed USB link shared by virtual devices.
+#[derive(Default)]
+pub struct Link {
+    deframer: Deframer,
+    writer: FrameWriter,
+    outgoing: VecDeque<u8>,
+}
+
+impl Link {
+    /// Returns every frame completed by bytes written by host.
+    pub fn receive(&mut self, bytes: &[u8]) -> Vec<Frame> {
+        let mut frames = Vec::new();
+        for byte in bytes {
+            if let Some(frame) = self.deframer.push(*byte) {
+                frames.push(frame);
+                frames.extend(std::iter::from_fn(|| self.deframer.pop()));
+            }
+        }
+        frames
+    }
+
+    pub fn send<P: Packet>(&mut self, packet: &P) {
+        let mut payload = [0u8; MAX_PACKET_SIZE];
+        let mut frame = [0u8; MAX_FRAME_SIZE];
+        if let Ok(size) = packet.encode_into(&mut payload) {
+            if let Ok(size) = self.writer.encode_into(&payload[..size], &mut frame) {
+                self.outgoing.extend(&frame[..size]);
+            }
+        }
+    }
+
+    pub fn has_outgoing(&self) -> bool {
+        !self.outgoing.is_empty()
+    }
+
+    pub fn read(&mut self, buf: &mut [u8]) -> usize {
+        let size = buf.len().min(self.outgoing.len());
+        for (byte, outgoing) in buf.iter_mut().zip(self.outgoing.drain(..size)) {
+            *byte = outgoing;
+        }
+        size
+    }
+
+    pub fn reset(&mut self) {
+        self.deframer.reset();
+        self.outgoing.clear();
+    }
+}
@@ -0,0 +1,7 @@
+pub mod bus;
+pub mod decoder;
+pub mod emulator;
+#[cfg(target_os = "linux")]
+pub mod pty;
+
+mod link;
@@ -0,0 +1,163 @@
+use std::{
+    ffi::CStr,
+    fs, io,
+    io::Write,
+    os::unix::{fs::OpenOptionsExt, io::FromRawFd},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread, time,
+};
+
+use crate::{
+    base::transport::Transport,
+    error::DriverError,
+    tty::device::{make_raw, read_nonblocking, wait_for},
+};
+
+const POLL_INTERVAL: time::Duration = time::Duration::from_millis(1);
+const BUFFER_SIZE: usize = 256;
+
+/// Exposes a transport, usually a virtual device, on a Linux pseudo-terminal
+/// so any program can open it at `path()` like a CDC-ACM serial port.
+///
+/// Bytes are pumped between the terminal and the transport on a background
+/// thread until the bridge is dropped.
+pub struct PtyBridge {
+    path: PathBuf,
+    running: Arc<AtomicBool>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl PtyBridge {
+    pub fn spawn<T: Transport + Send + 'static>(transport: T) -> Result<Self, DriverError> {
+        let (master, path) = open_pty().map_err(DriverError::OpenPty)?;
+        // Bridge keeps its own raw slave handle, so bytes are never echoed back
+        // and the master does not hang up between clients.
+        let slave = fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .custom_flags(libc::O_NOCTTY)
+            .open(&path)
+            .map_err(DriverError::OpenPty)?;
+        make_raw(&slave).map_err(DriverError::OpenPty)?;
+
+        let running = Arc::new(AtomicBool::new(true));
+        let thread = {
+            let running = running.clone();
+            thread::spawn(move || pump(master, slave, transport, &running))
+        };
+        Ok(Self {
+            path,
+            running,
+            thread: Some(thread),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for PtyBridge {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            thread.join().ok();
+        }
+    }
+}
+
+fn open_pty() -> io::Result<(fs::File, PathBuf)> {
+    unsafe {
+        let master = libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY | libc::O_NONBLOCK);
+        if master < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let master_file = fs::File::from_raw_fd(master);
+        if libc::grantpt(master) < 0 || libc::unlockpt(master) < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let name = libc::ptsname(master);
+        if name.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+
+        let path = PathBuf::from(CStr::from_ptr(name).to_string_lossy().into_owned());
+        Ok((master_file, path))
+    }
+}
+
+fn pump<T: Transport>(
+    mut master: fs::File,
+    _slave: fs::File,
+    mut transport: T,
+    running: &AtomicBool,
+) {
+    let mut buf = [0u8; BUFFER_SIZE];
+    while running.load(Ordering::Relaxed) {
+        if wait_for(&master, libc::POLLIN, POLL_INTERVAL).is_ok() {
+            if let Ok(size) = read_nonblocking(&mut master, &mut buf) {
+                transport.write_all(&buf[..size]).ok();
+            }
+        }
+
+        while wait_for(&master, libc::POLLOUT, time::Duration::ZERO).is_ok() {
+            match transport.read(&mut buf, time::Duration::ZERO) {
+                Ok(size) if size > 0 => write_master(&mut master, &buf[..size], running),
+                _ => break,
+            }
+        }
+    }
+}
+
+fn write_master(master: &mut fs::File, mut bytes: &[u8], running: &AtomicBool) {
+    while !bytes.is_empty() && running.load(Ordering::Relaxed) {
+        match master.write(bytes) {
+            Ok(size) => bytes = &bytes[size..],
+            Err(error) if error.kind() == io::ErrorKind::WouldBlock => {
+                wait_for(master, libc::POLLOUT, POLL_INTERVAL).ok();
+            }
+            Err(_) => return,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::iter;
+
+    use crate::{
+        base::{framed::Framed, request::Requester},
+        devices::decoder::{Command, DecoderDevice, Response},
+        hotplug::DeviceLocation,
+        simulated::decoder::VirtualDecoder,
+        tty::device::TtyDevice,
+    };
+
+    use super::*;
+
+    #[test]
+    fn expose_virtual_decoder_as_serial_port() {
+        let source = iter::repeat(vec![1, 2, 3]);
+        let bridge = PtyBridge::spawn(VirtualDecoder::new(source).with_rate(100)).unwrap();
+        let location = DeviceLocation {
+            bus: 0,
+            address: 0,
+            ports: vec![],
+        };
+        let device = TtyDevice::open_path(bridge.path(), location).unwrap();
+        let mut device = Framed::new(device);
+
+        let frame = device.read_params().unwrap();
+        let mut device = Requester::new(device.into_inner());
+        let version = device.request(Command::GetVersion).unwrap();
+
+        assert_eq!(frame.words.as_slice(), &[1, 2, 3]);
+        assert_eq!(version, Response::Version(1, 0, 0));
+    }
+}
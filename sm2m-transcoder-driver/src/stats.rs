@@ -0,0 +1,349 @@
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time,
+};
+
+use crate::{
+    base::transport::Transport,
+    error::{DriverError, ErrorClass},
+};
+
+/// Upper bounds of latency histogram buckets in microseconds, the last
+/// bucket holds every longer round trip.
+pub const LATENCY_BUCKETS_US: [u64; 12] = [
+    250,
+    500,
+    1_000,
+    2_000,
+    4_000,
+    8_000,
+    16_000,
+    32_000,
+    64_000,
+    128_000,
+    256_000,
+    u64::MAX,
+];
+
+/// Round trip latency distribution, see [`LATENCY_BUCKETS_US`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LatencyHistogram {
+    pub buckets: [u64; LATENCY_BUCKETS_US.len()],
+    pub total_us: u64,
+    pub max_us: u64,
+}
+
+impl LatencyHistogram {
+    pub fn count(&self) -> u64 {
+        self.buckets.iter().sum()
+    }
+
+    pub fn mean(&self) -> Option<time::Duration> {
+        match self.count() {
+            0 => None,
+            count => Some(time::Duration::from_micros(self.total_us / count)),
+        }
+    }
+
+    pub fn max(&self) -> Option<time::Duration> {
+        (self.count() > 0).then(|| time::Duration::from_micros(self.max_us))
+    }
+
+    /// Returns upper bound of the bucket holding `percent` percentile, the
+    /// last bucket is bounded by the maximum latency.
+    pub fn percentile(&self, percent: f64) -> Option<time::Duration> {
+        let count = self.count();
+        if count == 0 {
+            return None;
+        }
+
+        let rank = ((count as f64 * percent / 100.0).ceil() as u64).clamp(1, count);
+        let mut seen = 0;
+        for (bucket, bound) in self.buckets.iter().zip(LATENCY_BUCKETS_US.iter()) {
+            seen += bucket;
+            if seen >= rank {
+                return Some(time::Duration::from_micros((*bound).min(self.max_us)));
+            }
+        }
+        self.max()
+    }
+}
+
+/// Snapshot of device traffic counters taken by [`StatsRecorder::snapshot`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DeviceStats {
+    pub bytes_read: u64,
+    pub bytes_written: u64,
+    pub packets_read: u64,
+    pub packets_written: u64,
+    pub read_timeouts: u64,
+    pub write_timeouts: u64,
+    pub stalls: u64,
+    pub disconnects: u64,
+    pub failures: u64,
+    pub reconnects: u64,
+    pub latency: LatencyHistogram,
+}
+
+impl DeviceStats {
+    /// Returns number of errors other than timeouts.
+    pub fn errors(&self) -> u64 {
+        self.stalls + self.disconnects + self.failures
+    }
+}
+
+#[derive(Default)]
+struct Counters {
+    bytes_read: AtomicU64,
+    bytes_written: AtomicU64,
+    packets_read: AtomicU64,
+    packets_written: AtomicU64,
+    read_timeouts: AtomicU64,
+    write_timeouts: AtomicU64,
+    stalls: AtomicU64,
+    disconnects: AtomicU64,
+    failures: AtomicU64,
+    reconnects: AtomicU64,
+    latency_buckets: [AtomicU64; LATENCY_BUCKETS_US.len()],
+    latency_total_us: AtomicU64,
+    latency_max_us: AtomicU64,
+}
+
+/// Lock-free device traffic counters.
+///
+/// Clones share the same counters, so the thread talking to a device records
+/// while any other thread takes snapshots.
+#[derive(Clone, Default)]
+pub struct StatsRecorder {
+    counters: Arc<Counters>,
+}
+
+impl StatsRecorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn snapshot(&self) -> DeviceStats {
+        let counters = &*self.counters;
+        let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+        let mut latency = LatencyHistogram {
+            total_us: load(&counters.latency_total_us),
+            max_us: load(&counters.latency_max_us),
+            ..LatencyHistogram::default()
+        };
+        for (bucket, counter) in latency.buckets.iter_mut().zip(&counters.latency_buckets) {
+            *bucket = load(counter);
+        }
+        DeviceStats {
+            bytes_read: load(&counters.bytes_read),
+            bytes_written: load(&counters.bytes_written),
+            packets_read: load(&counters.packets_read),
+            packets_written: load(&counters.packets_written),
+            read_timeouts: load(&counters.read_timeouts),
+            write_timeouts: load(&counters.write_timeouts),
+            stalls: load(&counters.stalls),
+            disconnects: load(&counters.disconnects),
+            failures: load(&counters.failures),
+            reconnects: load(&counters.reconnects),
+            latency,
+        }
+    }
+
+    pub fn record_read(&self, result: &Result<usize, DriverError>) {
+        match result {
+            Ok(size) => increase(&self.counters.bytes_read, *size as u64),
+            Err(error) => self.record_error(error, &self.counters.read_timeouts),
+        }
+    }
+
+    pub fn record_write(&self, result: &Result<usize, DriverError>) {
+        match result {
+            Ok(size) => increase(&self.counters.bytes_written, *size as u64),
+            Err(error) => self.record_error(error, &self.counters.write_timeouts),
+        }
+    }
+
+    pub fn record_packet_read(&self) {
+        increase(&self.counters.packets_read, 1);
+    }
+
+    pub fn record_packet_written(&self) {
+        increase(&self.counters.packets_written, 1);
+    }
+
+    pub fn record_reconnect(&self) {
+        increase(&self.counters.reconnects, 1);
+    }
+
+    pub fn record_latency(&self, latency: time::Duration) {
+        let micros = latency.as_micros().min(u64::MAX as u128) as u64;
+        let bucket = LATENCY_BUCKETS_US
+            .iter()
+            .position(|bound| micros <= *bound)
+            .unwrap_or(LATENCY_BUCKETS_US.len() - 1);
+        increase(&self.counters.latency_buckets[bucket], 1);
+        increase(&self.counters.latency_total_us, micros);
+        self.counters
+            .latency_max_us
+            .fetch_max(micros, Ordering::Relaxed);
+    }
+
+    fn record_error(&self, error: &DriverError, timeouts: &AtomicU64) {
+        let counter = match error.class() {
+            ErrorClass::Timeout => timeouts,
+            ErrorClass::Stall => &self.counters.stalls,
+            ErrorClass::Disconnected => &self.counters.disconnects,
+            ErrorClass::Failure => &self.counters.failures,
+        };
+        increase(counter, 1);
+    }
+}
+
+fn increase(counter: &AtomicU64, value: u64) {
+    counter.fetch_add(value, Ordering::Relaxed);
+}
+
+/// Transport which records every transfer of the wrapped transport.
+///
+/// Protocol layers on top, such as `Framed`, find the recorder through
+/// `Transport::stats` and count packets as well.
+pub struct Metered<T: Transport> {
+    transport: T,
+    stats: StatsRecorder,
+}
+
+impl<T: Transport> Metered<T> {
+    pub fn new(transport: T) -> Self {
+        Self::with_recorder(transport, StatsRecorder::new())
+    }
+
+    /// Records into `stats`, so counters can outlive the transport.
+    pub fn with_recorder(transport: T, stats: StatsRecorder) -> Self {
+        Self { transport, stats }
+    }
+
+    pub fn recorder(&self) -> StatsRecorder {
+        self.stats.clone()
+    }
+
+    pub fn get_ref(&self) -> &T {
+        &self.transport
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.transport
+    }
+
+    pub fn into_inner(self) -> T {
+        self.transport
+    }
+}
+
+impl<T: Transport> Transport for Metered<T> {
+    fn read(&mut self, buf: &mut [u8], timeout: time::Duration) -> Result<usize, DriverError> {
+        let result = self.transport.read(buf, timeout);
+        self.stats.record_read(&result);
+        result
+    }
+
+    fn write(&mut self, buf: &[u8], timeout: time::Duration) -> Result<usize, DriverError> {
+        let result = self.transport.write(buf, timeout);
+        self.stats.record_write(&result);
+        result
+    }
+
+    fn reset(&mut self) -> Result<(), DriverError> {
+        self.transport.reset()
+    }
+
+    fn clear_halt(&mut self) -> Result<(), DriverError> {
+        self.transport.clear_halt()
+    }
+
+    fn stats(&self) -> Option<&StatsRecorder> {
+        Some(&self.stats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::base::{
+        framed::Framed,
+        mock::{self, MockTransport},
+    };
+
+    use super::*;
+
+    const IO_TIMEOUT: time::Duration = time::Duration::from_millis(10);
+
+    #[test]
+    fn count_bytes_packets_and_errors() {
+        let transport = MockTransport::new();
+        transport
+            .push_read(&mock::frame(0, &[1, 2]))
+            .push_read_error(rusb::Error::Pipe)
+            .push_read_error(rusb::Error::NoDevice);
+        let mut device = Framed::new(Metered::new(transport));
+        let recorder = device.get_ref().recorder();
+
+        device.write_frame(&[1]).unwrap();
+        device.read_frame(IO_TIMEOUT).unwrap();
+        device.read_frame(IO_TIMEOUT).unwrap_err();
+        device.read_frame(IO_TIMEOUT).unwrap_err();
+        device.read_frame(IO_TIMEOUT).unwrap_err();
+        let stats = recorder.snapshot();
+
+        assert_eq!((stats.bytes_written, stats.packets_written), (6, 1));
+        assert_eq!((stats.bytes_read, stats.packets_read), (7, 1));
+        assert_eq!(stats.read_timeouts, 1);
+        assert_eq!((stats.stalls, stats.disconnects, stats.failures), (1, 1, 0));
+        assert_eq!(stats.errors(), 2);
+    }
+
+    #[test]
+    fn share_counters_between_clones() {
+        let recorder = StatsRecorder::new();
+        let clone = recorder.clone();
+
+        clone.record_reconnect();
+        clone.record_write(&Ok(3));
+
+        let stats = recorder.snapshot();
+        assert_eq!(stats.reconnects, 1);
+        assert_eq!(stats.bytes_written, 3);
+    }
+
+    #[test]
+    fn build_latency_histogram() {
+        let recorder = StatsRecorder::new();
+        for micros in [100, 300, 900, 900, 5_000] {
+            recorder.record_latency(time::Duration::from_micros(micros));
+        }
+        recorder.record_latency(time::Duration::from_secs(1));
+
+        let latency = recorder.snapshot().latency;
+        assert_eq!(latency.buckets[..6], [1, 1, 2, 0, 0, 1]);
+        assert_eq!(latency.buckets[LATENCY_BUCKETS_US.len() - 1], 1);
+        assert_eq!(latency.count(), 6);
+        assert_eq!(latency.max(), Some(time::Duration::from_secs(1)));
+        assert_eq!(
+            latency.percentile(50.0),
+            Some(time::Duration::from_millis(1))
+        );
+        assert_eq!(
+            latency.percentile(100.0),
+            Some(time::Duration::from_secs(1))
+        );
+    }
+
+    #[test]
+    fn report_empty_latency() {
+        let latency = LatencyHistogram::default();
+
+        assert_eq!(latency.mean(), None);
+        assert_eq!(latency.percentile(99.0), None);
+    }
+}
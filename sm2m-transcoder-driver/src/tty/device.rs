@@ -0,0 +1,231 @@
+use std::{
+    fs, io,
+    io::{Read, Write},
+    os::unix::{fs::OpenOptionsExt, io::AsRawFd},
+    path::{Path, PathBuf},
+    time,
+};
+
+use crate::{base::transport::Transport, error::DriverError, hotplug::DeviceLocation, logging};
+
+use super::sysfs::TtyPort;
+
+/// CDC-ACM serial port opened through the kernel `cdc_acm` driver in raw mode.
+pub struct TtyDevice {
+    file: fs::File,
+    path: PathBuf,
+    location: DeviceLocation,
+    original_termios: libc::termios,
+}
+
+impl TtyDevice {
+    pub fn open(port: &TtyPort) -> Result<Self, DriverError> {
+        Self::open_path(&port.path, port.location.clone())
+    }
+
+    pub fn open_path(path: &Path, location: DeviceLocation) -> Result<Self, DriverError> {
+        let file = fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .custom_flags(libc::O_NOCTTY | libc::O_NONBLOCK)
+            .open(path)
+            .map_err(|error| DriverError::OpenPort(error, path.display().to_string()))?;
+        let original_termios = make_raw(&file)
+            .map_err(|error| DriverError::ConfigurePort(error, path.display().to_string()))?;
+        Ok(Self {
+            file,
+            path: path.to_owned(),
+            location,
+            original_termios,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn location(&self) -> DeviceLocation {
+        self.location.clone()
+    }
+
+    fn port_name(&self) -> String {
+        self.path.display().to_string()
+    }
+}
+
+impl Drop for TtyDevice {
+    fn drop(&mut self) {
+        unsafe {
+            libc::tcsetattr(self.file.as_raw_fd(), libc::TCSANOW, &self.original_termios);
+        }
+    }
+}
+
+impl Transport for TtyDevice {
+    fn read(&mut self, buf: &mut [u8], timeout: time::Duration) -> Result<usize, DriverError> {
+        let result = wait_for(&self.file, libc::POLLIN, timeout)
+            .and_then(|_| read_nonblocking(&mut self.file, buf))
+            .map_err(|error| DriverError::ReadPort(error, self.port_name()));
+        logging::transfer(
+            "read from",
+            format_args!("{}", self.path.display()),
+            buf,
+            &result,
+        );
+        result
+    }
+
+    fn write(&mut self, buf: &[u8], timeout: time::Duration) -> Result<usize, DriverError> {
+        let result = wait_for(&self.file, libc::POLLOUT, timeout)
+            .and_then(|_| self.file.write(buf))
+            .map_err(|error| DriverError::WritePort(error, self.port_name()));
+        logging::transfer(
+            "wrote to",
+            format_args!("{}", self.path.display()),
+            buf,
+            &result,
+        );
+        result
+    }
+
+    fn reset(&mut self) -> Result<(), DriverError> {
+        let result = unsafe { libc::tcflush(self.file.as_raw_fd(), libc::TCIOFLUSH) };
+        if result < 0 {
+            Err(DriverError::ConfigurePort(
+                io::Error::last_os_error(),
+                self.port_name(),
+            ))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+pub(crate) fn make_raw(file: &fs::File) -> io::Result<libc::termios> {
+    let fd = file.as_raw_fd();
+    let mut termios = unsafe { std::mem::zeroed::<libc::termios>() };
+    if unsafe { libc::tcgetattr(fd, &mut termios) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let original_termios = termios;
+    unsafe { libc::cfmakeraw(&mut termios) };
+    termios.c_cflag |= libc::CLOCAL | libc::CREAD;
+    termios.c_cc[libc::VMIN] = 0;
+    termios.c_cc[libc::VTIME] = 0;
+    if unsafe { libc::tcsetattr(fd, libc::TCSANOW, &termios) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(original_termios)
+}
+
+pub(crate) fn wait_for(
+    file: &fs::File,
+    events: libc::c_short,
+    timeout: time::Duration,
+) -> io::Result<()> {
+    let timeout_ms = timeout.as_millis().min(libc::c_int::MAX as u128) as libc::c_int;
+    let mut poll_fd = libc::pollfd {
+        fd: file.as_raw_fd(),
+        events,
+        revents: 0,
+    };
+    loop {
+        let result = unsafe { libc::poll(&mut poll_fd, 1, timeout_ms) };
+        if result > 0 {
+            return Ok(());
+        } else if result == 0 {
+            return Err(io::Error::from(io::ErrorKind::TimedOut));
+        }
+
+        let error = io::Error::last_os_error();
+        if error.kind() != io::ErrorKind::Interrupted {
+            return Err(error);
+        }
+    }
+}
+
+pub(crate) fn read_nonblocking(file: &mut fs::File, buf: &mut [u8]) -> io::Result<usize> {
+    match file.read(buf) {
+        Err(error) if error.kind() == io::ErrorKind::WouldBlock => {
+            Err(io::Error::from(io::ErrorKind::TimedOut))
+        }
+        result => result,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{ffi::CStr, os::unix::io::FromRawFd};
+
+    use super::*;
+
+    const IO_TIMEOUT: time::Duration = time::Duration::from_millis(100);
+
+    fn open_pty() -> (fs::File, PathBuf) {
+        unsafe {
+            let master = libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY);
+            assert!(master >= 0, "Error opening pseudo-terminal");
+            assert_eq!(libc::grantpt(master), 0);
+            assert_eq!(libc::unlockpt(master), 0);
+            let name = CStr::from_ptr(libc::ptsname(master));
+            let path = PathBuf::from(name.to_string_lossy().into_owned());
+            (fs::File::from_raw_fd(master), path)
+        }
+    }
+
+    fn location() -> DeviceLocation {
+        DeviceLocation {
+            bus: 1,
+            address: 2,
+            ports: vec![3],
+        }
+    }
+
+    #[test]
+    fn read_bytes_written_by_peer() {
+        let (mut master, path) = open_pty();
+        let mut device = TtyDevice::open_path(&path, location()).unwrap();
+
+        master.write_all(&[1, 0, 0xff, 0x0d, 0x0a]).unwrap();
+        let mut buf = [0u8; 64];
+        let size = device.read(&mut buf, IO_TIMEOUT).unwrap();
+
+        assert_eq!(&buf[..size], &[1, 0, 0xff, 0x0d, 0x0a]);
+    }
+
+    #[test]
+    fn write_bytes_to_peer() {
+        let (mut master, path) = open_pty();
+        let mut device = TtyDevice::open_path(&path, location()).unwrap();
+
+        let size = device.write_all(&[2, 0, 1, 0x11, 0x13]).unwrap();
+        let mut buf = [0u8; 64];
+        let read = master.read(&mut buf).unwrap();
+
+        assert_eq!(size, 5);
+        assert_eq!(&buf[..read], &[2, 0, 1, 0x11, 0x13]);
+    }
+
+    #[test]
+    fn time_out_without_data() {
+        let (_master, path) = open_pty();
+        let mut device = TtyDevice::open_path(&path, location()).unwrap();
+
+        let mut buf = [0u8; 64];
+        let error = device.read(&mut buf, IO_TIMEOUT).unwrap_err();
+
+        match error {
+            DriverError::ReadPort(error, _) => assert_eq!(error.kind(), io::ErrorKind::TimedOut),
+            error => panic!("Unexpected error {:?}", error),
+        }
+    }
+
+    #[test]
+    fn report_missing_port() {
+        let result = TtyDevice::open_path(Path::new("/dev/ttyACM-missing"), location());
+
+        assert!(matches!(result, Err(DriverError::OpenPort(..))));
+    }
+}
@@ -0,0 +1,183 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{error::DriverError, hotplug::DeviceLocation};
+
+const SYSFS_ROOT: &str = "/sys";
+const DEV_ROOT: &str = "/dev";
+const ACM_PREFIX: &str = "ttyACM";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TtyPort {
+    pub path: PathBuf,
+    pub vendor_id: u16,
+    pub product_id: u16,
+    pub product: String,
+    pub serial_number: String,
+    pub location: DeviceLocation,
+}
+
+pub fn list_ports() -> Result<Vec<TtyPort>, DriverError> {
+    list_ports_in(Path::new(SYSFS_ROOT), Path::new(DEV_ROOT))
+}
+
+/// Lists CDC-ACM ports described in `sysfs_root`, resolving device nodes in `dev_root`.
+pub fn list_ports_in(sysfs_root: &Path, dev_root: &Path) -> Result<Vec<TtyPort>, DriverError> {
+    let class_dir = sysfs_root.join("class").join("tty");
+    let entries = fs::read_dir(&class_dir).map_err(DriverError::Sysfs)?;
+    let mut ports = Vec::new();
+    for entry in entries {
+        let entry = entry.map_err(DriverError::Sysfs)?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name.starts_with(ACM_PREFIX) {
+            if let Ok(port) = read_port(&entry.path(), dev_root.join(&name)) {
+                ports.push(port);
+            }
+        }
+    }
+
+    ports.sort_by_key(|port| {
+        (
+            port.location.bus,
+            port.location.ports.clone(),
+            acm_number(&port.path),
+        )
+    });
+    Ok(ports)
+}
+
+/// Number of `ttyACM<N>` node, ports of one device are ordered by it rather
+/// than by name so `ttyACM10` follows `ttyACM2`.
+fn acm_number(path: &Path) -> Option<u32> {
+    path.file_name()?
+        .to_str()?
+        .strip_prefix(ACM_PREFIX)?
+        .parse()
+        .ok()
+}
+
+fn read_port(class_entry: &Path, path: PathBuf) -> io::Result<TtyPort> {
+    let interface_dir = fs::canonicalize(class_entry.join("device"))?;
+    let usb_dir = interface_dir
+        .parent()
+        .ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))?;
+    Ok(TtyPort {
+        path,
+        vendor_id: read_hex(usb_dir, "idVendor")?,
+        product_id: read_hex(usb_dir, "idProduct")?,
+        product: read_string(usb_dir, "product").unwrap_or_default(),
+        serial_number: read_string(usb_dir, "serial").unwrap_or_default(),
+        location: DeviceLocation {
+            bus: read_decimal(usb_dir, "busnum")?,
+            address: read_decimal(usb_dir, "devnum")?,
+            ports: parse_devpath(&read_string(usb_dir, "devpath")?),
+        },
+    })
+}
+
+pub(crate) fn read_string(dir: &Path, attribute: &str) -> io::Result<String> {
+    let value = fs::read_to_string(dir.join(attribute))?;
+    Ok(value.trim().to_owned())
+}
+
+pub(crate) fn read_hex(dir: &Path, attribute: &str) -> io::Result<u16> {
+    let value = read_string(dir, attribute)?;
+    u16::from_str_radix(&value, 16).map_err(|_| io::Error::from(io::ErrorKind::InvalidData))
+}
+
+pub(crate) fn read_decimal(dir: &Path, attribute: &str) -> io::Result<u8> {
+    let value = read_string(dir, attribute)?;
+    value
+        .parse()
+        .map_err(|_| io::Error::from(io::ErrorKind::InvalidData))
+}
+
+pub(crate) fn parse_devpath(devpath: &str) -> Vec<u8> {
+    devpath
+        .split('.')
+        .filter_map(|port| port.parse().ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::fs::symlink;
+
+    use super::*;
+
+    fn create_port(root: &Path, name: &str, devpath: &str, serial: &str) {
+        let usb_dir = root.join("devices").join(format!("1-{}", devpath));
+        let interface_dir = usb_dir.join(format!("1-{}:1.0", devpath));
+        fs::create_dir_all(&interface_dir).unwrap();
+        fs::write(usb_dir.join("idVendor"), "0483\n").unwrap();
+        fs::write(usb_dir.join("idProduct"), "5740\n").unwrap();
+        fs::write(usb_dir.join("product"), "An26 SM2M Decoder\n").unwrap();
+        fs::write(usb_dir.join("serial"), format!("{}\n", serial)).unwrap();
+        fs::write(usb_dir.join("busnum"), "1\n").unwrap();
+        fs::write(usb_dir.join("devnum"), "7\n").unwrap();
+        fs::write(usb_dir.join("devpath"), format!("{}\n", devpath)).unwrap();
+        let class_entry = root.join("class").join("tty").join(name);
+        fs::create_dir_all(&class_entry).unwrap();
+        symlink(&interface_dir, class_entry.join("device")).unwrap();
+    }
+
+    #[test]
+    fn list_acm_ports() {
+        let root = tempfile::tempdir().unwrap();
+        create_port(root.path(), "ttyACM1", "3.4", "27003A00");
+        create_port(root.path(), "ttyACM0", "3", "10002B00");
+        fs::create_dir_all(root.path().join("class").join("tty").join("ttyS0")).unwrap();
+
+        let ports = list_ports_in(root.path(), Path::new("/dev")).unwrap();
+
+        assert_eq!(ports.len(), 2);
+        assert_eq!(ports[0].path, PathBuf::from("/dev/ttyACM0"));
+        assert_eq!(ports[0].serial_number, "10002B00");
+        assert_eq!(ports[1].vendor_id, 0x0483);
+        assert_eq!(ports[1].product_id, 0x5740);
+        assert_eq!(ports[1].product, "An26 SM2M Decoder");
+        assert_eq!(
+            ports[1].location,
+            DeviceLocation {
+                bus: 1,
+                address: 7,
+                ports: vec![3, 4],
+            }
+        );
+    }
+
+    #[test]
+    fn order_ports_by_usb_location() {
+        let root = tempfile::tempdir().unwrap();
+        create_port(root.path(), "ttyACM2", "4", "27003A00");
+        create_port(root.path(), "ttyACM10", "1.2", "10002B00");
+        create_port(root.path(), "ttyACM3", "2", "31004C00");
+
+        let ports = list_ports_in(root.path(), Path::new("/dev")).unwrap();
+
+        let paths = ports
+            .iter()
+            .map(|port| port.path.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            paths,
+            vec![
+                PathBuf::from("/dev/ttyACM10"),
+                PathBuf::from("/dev/ttyACM3"),
+                PathBuf::from("/dev/ttyACM2"),
+            ]
+        );
+    }
+
+    #[test]
+    fn skip_ports_without_usb_parent() {
+        let root = tempfile::tempdir().unwrap();
+        fs::create_dir_all(root.path().join("class").join("tty").join("ttyACM0")).unwrap();
+
+        let ports = list_ports_in(root.path(), Path::new("/dev")).unwrap();
+
+        assert!(ports.is_empty());
+    }
+}
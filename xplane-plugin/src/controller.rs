@@ -77,6 +77,15 @@ impl Controller {
     }
 
     fn handle_events(&mut self) {
+        let stats = self.usb_thread_handle.stats();
+        let connected = self.usb_thread_handle.is_connected();
+        self.input_metrics
+            .borrow_mut()
+            .update_input(&stats, connected);
+        self.output_metrics
+            .borrow_mut()
+            .update_output(&stats, connected);
+
         if let Some(payload) = self.usb_thread_handle.read() {
             println!("Received data");
             let input_params = XPlaneInputParams::from(payload.as_ref());
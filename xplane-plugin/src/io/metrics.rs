@@ -1,5 +1,7 @@
 use std::time::Duration;
 
+use sm2m_transcoder_driver::stats::DeviceStats;
+
 pub enum IOState {
     Connected,
     Disconnected,
@@ -11,6 +13,16 @@ impl Default for IOState {
     }
 }
 
+impl From<bool> for IOState {
+    fn from(connected: bool) -> Self {
+        if connected {
+            Self::Connected
+        } else {
+            Self::Disconnected
+        }
+    }
+}
+
 #[derive(Default)]
 pub struct IOMetrics {
     pub state: IOState,
@@ -21,6 +33,23 @@ pub struct IOMetrics {
 }
 
 impl IOMetrics {
+    /// Takes traffic received from decoder from driver stats, errors other
+    /// than timeouts are counted here as the plugin only reads from decoder.
+    pub fn update_input(&mut self, stats: &DeviceStats, connected: bool) {
+        self.state = IOState::from(connected);
+        self.transferred = stats.bytes_read as usize;
+        self.packets = stats.packets_read as usize;
+        self.errors = stats.errors() as usize;
+    }
+
+    /// Takes traffic sent to decoder from driver stats.
+    pub fn update_output(&mut self, stats: &DeviceStats, connected: bool) {
+        self.state = IOState::from(connected);
+        self.transferred = stats.bytes_written as usize;
+        self.packets = stats.packets_written as usize;
+        self.errors = stats.write_timeouts as usize;
+    }
+
     pub fn bps(&mut self, delta: &Duration) -> f32 {
         let actual_transferred = self.transferred - self.last_transferred;
         let bps = actual_transferred as f32 / delta.as_secs_f32();
@@ -48,4 +77,32 @@ mod test {
         assert_float_eq!(bps1, 20.00, abs <= PRECISION);
         assert_float_eq!(bps2, 0.0, abs <= PRECISION);
     }
+
+    #[test]
+    fn should_take_driver_stats() {
+        let stats = DeviceStats {
+            bytes_read: 120,
+            packets_read: 4,
+            bytes_written: 6,
+            packets_written: 1,
+            stalls: 1,
+            write_timeouts: 2,
+            ..DeviceStats::default()
+        };
+        let mut input = IOMetrics::default();
+        let mut output = IOMetrics::default();
+
+        input.update_input(&stats, true);
+        output.update_output(&stats, true);
+
+        assert!(matches!(input.state, IOState::Connected));
+        assert_eq!(
+            (input.transferred, input.packets, input.errors),
+            (120, 4, 1)
+        );
+        assert_eq!(
+            (output.transferred, output.packets, output.errors),
+            (6, 1, 2)
+        );
+    }
 }
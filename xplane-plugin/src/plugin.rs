@@ -1,6 +1,6 @@
 use std::sync::mpsc;
 
-use sm2m_transcoder_driver::driver::USBDriver;
+use sm2m_transcoder_driver::driver::UsbDriver;
 use xplm::{
     flight_loop::FlightLoop,
     plugin::{Plugin, PluginInfo},
@@ -32,7 +32,7 @@ impl Plugin for SM2MPlugin {
         instance::create(&mut menu)?;
         let inspector = InspectorWindow::new(tx.clone(), WINDOW_WIDTH, WINDOW_HEIGHT, PLUGIN_NAME)?;
         let data_refs = DataRefs::new()?;
-        let usb_driver = USBDriver::new()?;
+        let usb_driver = UsbDriver::new()?;
         let usb_thread_handle = usb::thread::start(usb_driver);
         let controller = Controller::new(menu, inspector, data_refs, usb_thread_handle, rx);
         let mut flight_loop = FlightLoop::new(controller);
@@ -1,35 +1,73 @@
 use std::sync::mpsc;
 
 use sm2m_transcoder_driver::{
-    driver::{USBDevice, USBDriver},
+    base::transport::Transport,
+    driver::{UsbDevice, UsbDriver},
     error::DriverError,
+    hotplug::DeviceLocation,
+    stats::{Metered, StatsRecorder},
 };
 
 use super::thread::{FIND_TIMEOUT, IO_TIMEOUT};
 
+/// Decoder with its traffic recorded into the USB thread stats.
+type Device = Metered<UsbDevice>;
+
 pub enum State {
+    WaitForDecoder,
     FindDecoder,
-    BeforeMarker(USBDevice),
-    BeforePayload(USBDevice),
-    PayloadSize(USBDevice, usize),
-    AfterPayloadSize(USBDevice, usize),
-    BeforeStartMarker(USBDevice, usize),
-    ReadPayload(USBDevice, usize, Vec<u8>),
+    BeforeMarker(Device),
+    BeforePayload(Device),
+    PayloadSize(Device, usize),
+    AfterPayloadSize(Device, usize),
+    BeforeStartMarker(Device, usize),
+    ReadPayload(Device, usize, Vec<u8>),
 }
 
 impl Default for State {
     fn default() -> Self {
-        State::FindDecoder
+        State::WaitForDecoder
+    }
+}
+
+impl State {
+    pub fn is_waiting(&self) -> bool {
+        matches!(self, State::WaitForDecoder)
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.device().is_some()
+    }
+
+    pub fn is_connected_to(&self, location: &DeviceLocation) -> bool {
+        match self.device() {
+            Some(device) => device.get_ref().location() == *location,
+            None => false,
+        }
+    }
+
+    fn device(&self) -> Option<&Device> {
+        match self {
+            State::WaitForDecoder | State::FindDecoder => None,
+            State::BeforeMarker(device)
+            | State::BeforePayload(device)
+            | State::PayloadSize(device, _)
+            | State::AfterPayloadSize(device, _)
+            | State::BeforeStartMarker(device, _)
+            | State::ReadPayload(device, _, _) => Some(device),
+        }
     }
 }
 
 pub fn process_state(
     state: State,
-    driver: &mut USBDriver,
+    driver: &mut UsbDriver,
+    stats: &StatsRecorder,
     read_tx: &mpsc::Sender<Vec<u8>>,
 ) -> Result<State, DriverError> {
     match state {
-        State::FindDecoder => find_decoder(driver),
+        State::WaitForDecoder => Ok(State::WaitForDecoder),
+        State::FindDecoder => find_decoder(driver, stats),
         State::BeforeMarker(device) => before_marker(device),
         State::BeforePayload(device) => before_payload(device),
         State::PayloadSize(device, offset) => payload_size(device, offset),
@@ -39,14 +77,17 @@ pub fn process_state(
     }
 }
 
-fn find_decoder(driver: &mut USBDriver) -> Result<State, DriverError> {
+fn find_decoder(driver: &mut UsbDriver, stats: &StatsRecorder) -> Result<State, DriverError> {
     match driver.find_decoder(FIND_TIMEOUT)? {
-        Some(device) => Ok(State::BeforeMarker(device)),
-        None => Ok(State::FindDecoder),
+        Some(device) => Ok(State::BeforeMarker(Metered::with_recorder(
+            device,
+            stats.clone(),
+        ))),
+        None => Ok(State::WaitForDecoder),
     }
 }
 
-fn before_marker(mut device: USBDevice) -> Result<State, DriverError> {
+fn before_marker(mut device: Device) -> Result<State, DriverError> {
     if read_marker_byte(&mut device)? == ReadStartMarker::Found {
         Ok(State::BeforePayload(device))
     } else {
@@ -54,7 +95,7 @@ fn before_marker(mut device: USBDevice) -> Result<State, DriverError> {
     }
 }
 
-fn before_payload(mut device: USBDevice) -> Result<State, DriverError> {
+fn before_payload(mut device: Device) -> Result<State, DriverError> {
     match read_marker_byte(&mut device)? {
         ReadStartMarker::Found => Ok(State::PayloadSize(device, 0)),
         ReadStartMarker::NotFound => Ok(State::BeforeMarker(device)),
@@ -62,7 +103,7 @@ fn before_payload(mut device: USBDevice) -> Result<State, DriverError> {
     }
 }
 
-fn payload_size(mut device: USBDevice, offset: usize) -> Result<State, DriverError> {
+fn payload_size(mut device: Device, offset: usize) -> Result<State, DriverError> {
     match read_marker_byte(&mut device)? {
         ReadStartMarker::Found => Ok(State::AfterPayloadSize(device, offset + 1)),
         ReadStartMarker::NotFound => Ok(State::PayloadSize(device, offset + 1)),
@@ -70,7 +111,7 @@ fn payload_size(mut device: USBDevice, offset: usize) -> Result<State, DriverErr
     }
 }
 
-fn after_payload_size(mut device: USBDevice, offset: usize) -> Result<State, DriverError> {
+fn after_payload_size(mut device: Device, offset: usize) -> Result<State, DriverError> {
     match read_marker_byte(&mut device)? {
         ReadStartMarker::Found => Ok(State::ReadPayload(
             device,
@@ -82,7 +123,7 @@ fn after_payload_size(mut device: USBDevice, offset: usize) -> Result<State, Dri
     }
 }
 
-fn before_start_marker(mut device: USBDevice, size: usize) -> Result<State, DriverError> {
+fn before_start_marker(mut device: Device, size: usize) -> Result<State, DriverError> {
     match read_marker(&mut device)? {
         ReadStartMarker::Found => Ok(State::ReadPayload(device, size, Vec::with_capacity(size))),
         ReadStartMarker::NotFound => Ok(State::BeforeMarker(device)),
@@ -91,7 +132,7 @@ fn before_start_marker(mut device: USBDevice, size: usize) -> Result<State, Driv
 }
 
 fn read_payload(
-    mut device: USBDevice,
+    mut device: Device,
     size: usize,
     mut payload: Vec<u8>,
     read_tx: &mpsc::Sender<Vec<u8>>,
@@ -104,6 +145,9 @@ fn read_payload(
     if payload.len() < size {
         Ok(State::ReadPayload(device, size, payload))
     } else {
+        if let Some(stats) = device.stats() {
+            stats.record_packet_read();
+        }
         if let Err(error) = read_tx.send(payload) {
             xplm::debug!("USB decoder error: {}", error);
         }
@@ -118,7 +162,7 @@ enum ReadStartMarker {
     NoData,
 }
 
-fn read_marker_byte(device: &mut USBDevice) -> Result<ReadStartMarker, DriverError> {
+fn read_marker_byte(device: &mut Device) -> Result<ReadStartMarker, DriverError> {
     const START_MARKER: u8 = 170;
     let mut buf = [0u8; 1];
     if device.read(&mut buf, IO_TIMEOUT)? > 0 {
@@ -132,7 +176,7 @@ fn read_marker_byte(device: &mut USBDevice) -> Result<ReadStartMarker, DriverErr
     }
 }
 
-fn read_marker(device: &mut USBDevice) -> Result<ReadStartMarker, DriverError> {
+fn read_marker(device: &mut Device) -> Result<ReadStartMarker, DriverError> {
     const START_MARKER: u16 = 43690;
     let mut buf = [0u8; 2];
     if device.read(&mut buf, IO_TIMEOUT)? > 0 {
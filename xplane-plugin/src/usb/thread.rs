@@ -1,39 +1,89 @@
-use sm2m_transcoder_driver::{driver::USBDriver, error::DriverError};
-use std::{sync::mpsc, thread, time};
+use sm2m_transcoder_driver::{
+    driver::UsbDriver,
+    hotplug::{HotplugEvent, HotplugMonitor},
+    stats::StatsRecorder,
+};
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc,
+    },
+    thread, time,
+};
 
 use super::{decoder, thread_handle::USBThreadHandle};
 
 pub const FIND_TIMEOUT: time::Duration = time::Duration::from_millis(50);
 pub const IO_TIMEOUT: time::Duration = time::Duration::from_millis(10);
 
-pub fn start(driver: USBDriver) -> USBThreadHandle {
+pub fn start(driver: UsbDriver) -> USBThreadHandle {
     let (term_tx, term_rx) = mpsc::channel();
     let (write_tx, write_rx) = mpsc::channel();
     let (read_tx, read_rx) = mpsc::channel();
-    let handle = thread::spawn(move || thread_loop(driver, term_rx, write_rx, read_tx));
-    USBThreadHandle::new(term_tx, write_tx, read_rx, Some(handle))
+    let stats = StatsRecorder::new();
+    let connected = Arc::new(AtomicBool::new(false));
+    let link = Link {
+        stats: stats.clone(),
+        connected: connected.clone(),
+    };
+    let handle = thread::spawn(move || thread_loop(driver, term_rx, write_rx, read_tx, link));
+    USBThreadHandle::new(term_tx, write_tx, read_rx, stats, connected, Some(handle))
+}
+
+/// Decoder link state shared with [`USBThreadHandle`].
+struct Link {
+    stats: StatsRecorder,
+    connected: Arc<AtomicBool>,
 }
 
 fn thread_loop(
-    mut driver: USBDriver,
+    mut driver: UsbDriver,
     term_rx: mpsc::Receiver<()>,
     write_rx: mpsc::Receiver<Vec<u8>>,
     read_tx: mpsc::Sender<Vec<u8>>,
+    link: Link,
 ) {
+    let monitor = match driver.hotplug() {
+        Ok(monitor) => monitor,
+        Err(error) => {
+            xplm::debugln!("USB hotplug error, polling devices instead: {:?}", error);
+            driver.polling_hotplug()
+        }
+    };
     let mut decoder_state = decoder::State::default();
 
     loop {
         match term_rx.try_recv() {
             Err(mpsc::TryRecvError::Empty) => {
-                match decoder::process_state(decoder_state, &mut driver, &read_tx) {
+                decoder_state = handle_hotplug(decoder_state, &monitor);
+                match decoder::process_state(decoder_state, &mut driver, &link.stats, &read_tx) {
                     Ok(new_state) => decoder_state = new_state,
                     Err(error) => {
                         xplm::debugln!("USB thread error: {:?}", error);
-                        decoder_state = decoder::State::default();
+                        thread::sleep(FIND_TIMEOUT);
+                        decoder_state = decoder::State::FindDecoder;
                     }
                 }
+                link.connected
+                    .store(decoder_state.is_connected(), Ordering::Relaxed);
             }
             _ => return,
         }
     }
 }
+
+fn handle_hotplug(state: decoder::State, monitor: &HotplugMonitor) -> decoder::State {
+    let event = if state.is_waiting() {
+        monitor.recv_timeout(FIND_TIMEOUT)
+    } else {
+        monitor.try_recv()
+    };
+
+    match event {
+        Some(HotplugEvent::Arrived(_)) if !state.is_connected() => decoder::State::FindDecoder,
+        Some(HotplugEvent::Left(location)) if state.is_connected_to(&location) => {
+            decoder::State::WaitForDecoder
+        }
+        _ => state,
+    }
+}
@@ -1,9 +1,19 @@
-use std::{sync::mpsc, thread};
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc,
+    },
+    thread,
+};
+
+use sm2m_transcoder_driver::stats::{DeviceStats, StatsRecorder};
 
 pub struct USBThreadHandle {
     term_tx: mpsc::Sender<()>,
     write_tx: mpsc::Sender<Vec<u8>>,
     read_rx: mpsc::Receiver<Vec<u8>>,
+    stats: StatsRecorder,
+    connected: Arc<AtomicBool>,
     handle: Option<thread::JoinHandle<()>>,
 }
 
@@ -12,16 +22,29 @@ impl USBThreadHandle {
         term_tx: mpsc::Sender<()>,
         write_tx: mpsc::Sender<Vec<u8>>,
         read_rx: mpsc::Receiver<Vec<u8>>,
+        stats: StatsRecorder,
+        connected: Arc<AtomicBool>,
         handle: Option<thread::JoinHandle<()>>,
     ) -> Self {
         Self {
             term_tx,
             write_tx,
             read_rx,
+            stats,
+            connected,
             handle,
         }
     }
 
+    /// Returns decoder traffic counters, they keep counting across reconnects.
+    pub fn stats(&self) -> DeviceStats {
+        self.stats.snapshot()
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.connected.load(Ordering::Relaxed)
+    }
+
     pub fn read(&self) -> Option<Vec<u8>> {
         self.read_rx.try_recv().ok()
     }